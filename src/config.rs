@@ -3,25 +3,35 @@ use crate::defaults::{
 };
 use crate::listen_addr::{ListenAddr, ListenAddress};
 use crate::peer_manager::config::DEFAULT_TARGET_PEERS;
-use crate::rpc::config::{InboundRateLimiterConfig, OutboundRateLimiterConfig};
-use crate::types::{ForkContext, GossipKind};
+use crate::rpc::config::{
+    ByteQuotaConfig, InboundRateLimiterConfig, InboundSchedulerConfig, OutboundRateLimiterConfig,
+    PeerQuotaMultipliers, RpcHandlerConfig,
+};
+use crate::types::{EnrAttestationBitfield, EnrSyncCommitteeBitfield, ForkContext, GossipKind};
 use crate::{Enr, PeerIdSerialized};
+use ipnet::IpNet;
 use libp2p::{Multiaddr, gossipsub};
 use local_ip_address::local_ipv6;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::num::NonZeroU16;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use types::nonstandard::Phase;
+use typenum::Unsigned as _;
+use types::{
+    altair::consts::SyncCommitteeSubnetCount, nonstandard::Phase,
+    phase0::consts::AttestationSubnetCount,
+};
 
 pub const DEFAULT_IPV4_ADDRESS: Ipv4Addr = Ipv4Addr::UNSPECIFIED;
 pub const DEFAULT_TCP_PORT: u16 = 9000u16;
 pub const DEFAULT_DISC_PORT: u16 = 9000u16;
 pub const DEFAULT_QUIC_PORT: u16 = 9001u16;
 pub const DEFAULT_IDONTWANT_MESSAGE_SIZE_THRESHOLD: usize = 1000usize;
+pub const DEFAULT_INVALID_BLOCK_STORAGE_MAX_MESSAGES: usize = 256;
+pub const DEFAULT_INVALID_BLOCK_STORAGE_MAX_BYTES: u64 = 512 * 1024 * 1024;
 
 pub struct GossipsubConfigParams {
     pub message_domain_valid_snappy: [u8; 4],
@@ -32,7 +42,8 @@ pub struct GossipsubConfigParams {
 #[serde(default)]
 /// Network configuration
 pub struct Config {
-    /// Data directory where node's keyfile is stored
+    /// Data directory where node's keyfile is stored. The ban list is also persisted here so that
+    /// banned peers stay banned across restarts.
     pub network_dir: Option<PathBuf>,
 
     /// IP addresses to listen on.
@@ -82,6 +93,13 @@ pub struct Config {
     /// List of trusted libp2p nodes which are not scored and marked as explicit.
     pub trusted_peers: Vec<PeerIdSerialized>,
 
+    /// If non-empty, only peers with addresses within these CIDR ranges are connected to.
+    pub allowed_ip_ranges: Vec<IpNet>,
+
+    /// Peers with addresses within these CIDR ranges are never connected to. Takes precedence
+    /// over `allowed_ip_ranges`.
+    pub denied_ip_ranges: Vec<IpNet>,
+
     /// Disables peer scoring altogether.
     pub disable_peer_scoring: bool,
 
@@ -117,12 +135,17 @@ pub struct Config {
     pub private: bool,
 
     /// Shutdown beacon node after sync is completed.
+    ///
+    /// Peers are sent a `ClientShutdown` goodbye once the node first becomes synced.
     pub shutdown_after_sync: bool,
 
     /// List of extra topics to initially subscribe to as strings.
     pub topics: Vec<GossipKind>,
 
     /// Whether we are running a block proposer only node.
+    ///
+    /// Such a node does not join attestation or sync committee subnets, advertises empty
+    /// `attnets`/`syncnets` and targets at most `PROPOSER_ONLY_TARGET_PEERS` peers.
     pub proposer_only: bool,
 
     /// Whether metrics are enabled.
@@ -135,11 +158,41 @@ pub struct Config {
     pub outbound_rate_limiter_config: Option<OutboundRateLimiterConfig>,
 
     /// Configures if/where invalid blocks should be stored.
+    ///
+    /// Blocks, blob sidecars and data column sidecars that fail decoding or are rejected by the
+    /// application are written to this directory along with a metadata file.
     pub invalid_block_storage: Option<PathBuf>,
 
+    /// Maximum number of messages kept in `invalid_block_storage`. The oldest are pruned first.
+    pub invalid_block_storage_max_messages: usize,
+
+    /// Maximum combined size in bytes of the files kept in `invalid_block_storage`.
+    pub invalid_block_storage_max_bytes: u64,
+
     /// Configuration for the inbound rate limiter (requests received by this node).
     pub inbound_rate_limiter_config: Option<InboundRateLimiterConfig>,
 
+    /// Byte quotas of the responses sent by this node. Only applied along with
+    /// `inbound_rate_limiter_config`, a warning is logged otherwise.
+    pub inbound_byte_quota_config: Option<ByteQuotaConfig>,
+
+    /// Multipliers of the inbound and outbound rate limiter quotas of trusted peers, peers with
+    /// full custody and explicitly listed peers.
+    pub peer_quota_multipliers: PeerQuotaMultipliers,
+
+    /// Response timeouts and substream limits of the RPC handlers, optionally per protocol.
+    pub rpc_handler_config: RpcHandlerConfig,
+
+    /// Global limits of the inbound requests served at the same time.
+    pub inbound_scheduler_config: InboundSchedulerConfig,
+
+    /// Periodically persist good peers to `network_dir` and dial them on startup.
+    pub persist_peer_db: bool,
+
+    /// Persist banned peers to `network_dir` whenever the bans change and keep them banned after a
+    /// restart.
+    pub persist_ban_list: bool,
+
     /// Configuration for the minimum message size for which IDONTWANT messages are send in the mesh.
     /// Lower the value reduces the optimization effect of the IDONTWANT messages.
     pub idontwant_message_size_threshold: usize,
@@ -280,6 +333,48 @@ impl Config {
     pub fn listen_addrs(&self) -> &ListenAddress {
         &self.listen_addresses
     }
+
+    /// Returns `true` if any allowed or denied IP ranges are configured.
+    pub fn has_ip_filter(&self) -> bool {
+        !self.allowed_ip_ranges.is_empty() || !self.denied_ip_ranges.is_empty()
+    }
+
+    /// Returns `true` if connections with `ip` are permitted by the allowed and denied IP ranges.
+    pub fn is_ip_allowed(&self, ip: &IpAddr) -> bool {
+        let allowed = self.allowed_ip_ranges.is_empty()
+            || self
+                .allowed_ip_ranges
+                .iter()
+                .any(|range| range.contains(ip));
+
+        allowed && !self.denied_ip_ranges.iter().any(|range| range.contains(ip))
+    }
+
+    /// Returns `true` if the node subscribes to every attestation and sync committee subnet,
+    /// which `import_all_attestations` requires as well. Proposer-only nodes never do.
+    pub fn subscribes_to_all_subnets(&self) -> bool {
+        (self.subscribe_all_subnets || self.import_all_attestations) && !self.proposer_only
+    }
+
+    /// Returns the `attnets` and `syncnets` bitfields advertised before any subnet is joined.
+    pub(crate) fn initial_subnet_bitfields(
+        &self,
+    ) -> (EnrAttestationBitfield, EnrSyncCommitteeBitfield) {
+        let mut attnets = EnrAttestationBitfield::default();
+        let mut syncnets = EnrSyncCommitteeBitfield::default();
+
+        if self.subscribes_to_all_subnets() {
+            for id in 0..AttestationSubnetCount::USIZE {
+                attnets.set(id, true);
+            }
+
+            for id in 0..SyncCommitteeSubnetCount::USIZE {
+                syncnets.set(id, true);
+            }
+        }
+
+        (attnets, syncnets)
+    }
 }
 
 impl Default for Config {
@@ -352,6 +447,8 @@ impl Default for Config {
             boot_nodes_multiaddr: vec![],
             libp2p_nodes: vec![],
             trusted_peers: vec![],
+            allowed_ip_ranges: vec![],
+            denied_ip_ranges: vec![],
             identify_agent_version: None,
             disable_peer_scoring: false,
             disable_discovery: false,
@@ -369,7 +466,15 @@ impl Default for Config {
             enable_light_client_server: false,
             outbound_rate_limiter_config: None,
             invalid_block_storage: None,
+            invalid_block_storage_max_messages: DEFAULT_INVALID_BLOCK_STORAGE_MAX_MESSAGES,
+            invalid_block_storage_max_bytes: DEFAULT_INVALID_BLOCK_STORAGE_MAX_BYTES,
             inbound_rate_limiter_config: None,
+            inbound_byte_quota_config: None,
+            peer_quota_multipliers: PeerQuotaMultipliers::default(),
+            rpc_handler_config: RpcHandlerConfig::default(),
+            inbound_scheduler_config: InboundSchedulerConfig::default(),
+            persist_peer_db: false,
+            persist_ban_list: false,
             idontwant_message_size_threshold: DEFAULT_IDONTWANT_MESSAGE_SIZE_THRESHOLD,
             libp2p_private_key_file: None,
         }
@@ -611,3 +716,48 @@ pub const fn is_global_ipv6(addr: &Ipv6Addr) -> bool {
             || is_unique_local(addr)
             || is_unicast_link_local(addr))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_subnets_are_advertised_when_importing_all_attestations() {
+        let mut config = Config::default();
+        let (attnets, syncnets) = config.initial_subnet_bitfields();
+
+        assert!(!config.subscribes_to_all_subnets());
+        assert!(attnets.none() && syncnets.none());
+
+        config.import_all_attestations = true;
+        let (attnets, syncnets) = config.initial_subnet_bitfields();
+
+        assert!(config.subscribes_to_all_subnets());
+        assert!((0..AttestationSubnetCount::USIZE).all(|id| attnets.get(id) == Some(true)));
+        assert!((0..SyncCommitteeSubnetCount::USIZE).all(|id| syncnets.get(id) == Some(true)));
+
+        // Proposer-only nodes do not take part in attestation or sync committee subnets.
+        config.proposer_only = true;
+        assert!(!config.subscribes_to_all_subnets());
+    }
+
+    #[test]
+    fn denied_ip_ranges_take_precedence() {
+        let mut config = Config::default();
+        let private = "10.0.0.1".parse().unwrap();
+        let public = "8.8.8.8".parse().unwrap();
+
+        assert!(!config.has_ip_filter());
+        assert!(config.is_ip_allowed(&public));
+
+        config.allowed_ip_ranges = vec!["10.0.0.0/8".parse().unwrap()];
+
+        assert!(config.is_ip_allowed(&private));
+        assert!(!config.is_ip_allowed(&public));
+
+        config.denied_ip_ranges = vec!["10.0.0.0/24".parse().unwrap()];
+
+        assert!(!config.is_ip_allowed(&private));
+        assert!(config.is_ip_allowed(&"10.0.1.1".parse().unwrap()));
+    }
+}
@@ -277,15 +277,12 @@ pub fn build_enr(
     // set the `eth2` field on our ENR
     builder.add_value::<Bytes>(ETH2_ENR_KEY, &enr_fork_id.to_ssz()?.into());
 
-    // set the "attnets" field on our ENR
-    let bitfield = EnrAttestationBitfield::default();
+    // set the "attnets" and "syncnets" fields on our ENR, which are all set when subscribing to
+    // every subnet
+    let (attnets, syncnets) = config.initial_subnet_bitfields();
 
-    builder.add_value::<Bytes>(ATTESTATION_BITFIELD_ENR_KEY, &bitfield.to_ssz()?.into());
-
-    // set the "syncnets" field on our ENR
-    let bitfield = EnrSyncCommitteeBitfield::default();
-
-    builder.add_value::<Bytes>(SYNC_COMMITTEE_BITFIELD_ENR_KEY, &bitfield.to_ssz()?.into());
+    builder.add_value::<Bytes>(ATTESTATION_BITFIELD_ENR_KEY, &attnets.to_ssz()?.into());
+    builder.add_value::<Bytes>(SYNC_COMMITTEE_BITFIELD_ENR_KEY, &syncnets.to_ssz()?.into());
 
     // only set `cgc` and `nfd` if PeerDAS fork (Fulu) epoch has been scheduled
     if chain_config.is_peerdas_scheduled() {
@@ -644,6 +644,20 @@ impl<P: Preset> Discovery<P> {
         }
     }
 
+    /// Bans IP addresses in discovery.
+    pub fn ban_ips(&mut self, ip_addresses: Vec<IpAddr>) {
+        for ip_address in ip_addresses {
+            self.discv5.ban_ip(ip_address, None);
+        }
+    }
+
+    /// Unbans IP addresses in discovery.
+    pub fn unban_ips(&mut self, ip_addresses: Vec<IpAddr>) {
+        for ip_address in ip_addresses {
+            self.discv5.ban_ip_remove(&ip_address);
+        }
+    }
+
     ///  Marks node as disconnected in the DHT, freeing up space for other nodes, this also removes
     ///  nodes from the cached ENR list.
     pub fn disconnect_peer(&mut self, peer_id: &PeerId) {
@@ -118,6 +118,7 @@ pub use prometheus_client;
 pub use config::Config as NetworkConfig;
 pub use discovery::{CombinedKeyExt, EnrExt, Eth2Enr};
 pub use discv5;
+pub use ipnet;
 pub use libp2p;
 pub use libp2p::gossipsub::{IdentTopic, MessageAcceptance, MessageId, Topic, TopicHash};
 pub use libp2p::{Multiaddr, multiaddr};
@@ -127,9 +128,14 @@ pub use peer_manager::{
     ConnectionDirection, PeerConnectionStatus, PeerInfo, PeerManager, SyncInfo, SyncStatus,
     peerdb::PeerDB,
     peerdb::client::Client,
+    peerdb::manual_bans::{Ban, BanSource, BanTarget},
     peerdb::score::{PeerAction, ReportSource},
 };
 // pub use service::{load_private_key, Context, Libp2pEvent, Service, NETWORK_KEY_FILENAME};
 pub use service::api_types::Response;
+pub use service::attestation_duties::AttestationDutyConfig;
+pub use service::fork_scheduler::{ForkTransition, ForkTransitionConfig, ForkTransitionStage};
+pub use service::rpc_client::{ResponseFuture, ResponseStream};
+pub use service::status_policy::{DefaultStatusPolicy, StatusClassification, StatusPolicy};
 pub use service::utils::*;
 pub use service::{Gossipsub, NetworkEvent};
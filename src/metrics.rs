@@ -154,6 +154,13 @@ pub static TOTAL_RPC_ERRORS_PER_CLIENT: LazyLock<Result<IntCounterVec>> = LazyLo
 pub static TOTAL_RPC_REQUESTS: LazyLock<Result<IntCounterVec>> = LazyLock::new(|| {
     try_create_int_counter_vec("libp2p_rpc_requests_total", "RPC requests total", &["type"])
 });
+pub static RPC_UNAVAILABLE_RANGE_REQUESTS: LazyLock<Result<IntCounterVec>> = LazyLock::new(|| {
+    try_create_int_counter_vec(
+        "libp2p_rpc_unavailable_range_requests_total",
+        "Inbound range requests answered with ResourceUnavailable by the RPC layer",
+        &["protocol"],
+    )
+});
 pub static PEER_ACTION_EVENTS_PER_CLIENT: LazyLock<Result<IntCounterVec>> = LazyLock::new(|| {
     try_create_int_counter_vec(
         "libp2p_peer_actions_per_client",
@@ -8,6 +8,7 @@ use anyhow::Result;
 use delay_map::HashSetDelay;
 use discv5::Enr;
 use eip_7594::{compute_subnets_from_custody_group, get_custody_groups};
+use ipnet::IpNet;
 use libp2p::identify::Info as IdentifyInfo;
 use peerdb::{BanOperation, BanResult, ScoreUpdateResult};
 use rand::seq::SliceRandom;
@@ -16,7 +17,7 @@ use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
-use tracing::{debug, error, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 use types::{fulu::primitives::CustodyIndex, phase0::primitives::SubnetId, preset::Preset};
 
 pub use libp2p::core::Multiaddr;
@@ -27,8 +28,10 @@ pub mod peerdb;
 use crate::peer_manager::peerdb::client::ClientKind;
 use crate::types::GossipKind;
 use libp2p::multiaddr;
+use peerdb::manual_bans::{Ban, ManualBans};
 pub use peerdb::peer_info::{ConnectionDirection, PeerConnectionStatus, PeerInfo};
-use peerdb::score::{PeerAction, ReportSource};
+use peerdb::score::{DEFAULT_SCORE, PeerAction, ReportSource};
+use peerdb::snapshot::{BanList, PeerDBSnapshot};
 pub use peerdb::sync_status::{SyncInfo, SyncStatus};
 use std::collections::{HashMap, HashSet, hash_map::Entry};
 use std::net::IpAddr;
@@ -58,6 +61,9 @@ pub const PEER_RECONNECTION_TIMEOUT: Duration = Duration::from_secs(600);
 pub const MIN_SYNC_COMMITTEE_PEERS: u64 = 2;
 /// Avoid pruning sampling peers if subnet peer count is below this number.
 pub const MIN_SAMPLING_COLUMN_SUBNET_PEERS: u64 = 2;
+/// Nodes subscribed to every subnet avoid pruning peers on an attestation subnet if doing so would
+/// lower the peer count of the subnet below this number.
+pub const MIN_ATTESTATION_SUBNET_PEERS: u64 = 2;
 /// A fraction of `PeerManager::target_peers` that we allow to connect to us in excess of
 /// `PeerManager::target_peers`. For clarity, if `PeerManager::target_peers` is 50 and
 /// PEER_EXCESS_FACTOR = 0.1 we allow 10% more nodes, i.e 55.
@@ -72,6 +78,9 @@ pub const MIN_OUTBOUND_ONLY_FACTOR: f32 = 0.2;
 /// limit is 55, and we are at 55 peers, the following parameter provisions a few more slots of
 /// dialing priority peers we need for validator duties.
 pub const PRIORITY_PEER_EXCESS: f32 = 0.2;
+/// The maximum number of peers a proposer-only node targets. Such nodes do not participate in
+/// attestation or sync committee subnets and need far fewer peers than a full node.
+pub const PROPOSER_ONLY_TARGET_PEERS: usize = 20;
 /// The numbre of inbound libp2p peers we have seen before we consider our NAT to be open.
 pub const LIBP2P_NAT_OPEN_THRESHOLD: usize = 3;
 
@@ -91,6 +100,8 @@ pub struct PeerManager {
     target_peers: usize,
     /// Peers queued to be dialed.
     peers_to_dial: Vec<Enr>,
+    /// Addresses recorded in a snapshot for queued peers whose ENR has no address to dial.
+    snapshot_dial_addresses: HashMap<PeerId, Vec<Multiaddr>>,
     /// The number of temporarily banned peers. This is used to prevent instantaneous
     /// reconnection.
     // NOTE: This just prevents re-connections. The state of the peer is otherwise unaffected. A
@@ -144,6 +155,10 @@ pub enum PeerManagerEvent {
     Banned(PeerId, Vec<IpAddr>),
     /// The peer should be unbanned with the associated ip addresses.
     UnBanned(PeerId, Vec<IpAddr>),
+    /// Inform the behaviour to ban these ip addresses.
+    BannedIps(Vec<IpAddr>),
+    /// The ip addresses should be unbanned.
+    UnBannedIps(Vec<IpAddr>),
     /// Request the behaviour to discover more peers and the amount of peers to discover.
     DiscoverPeers(usize),
     /// Request the behaviour to discover peers on subnets.
@@ -191,6 +206,7 @@ impl PeerManager {
             network_globals,
             events: SmallVec::new(),
             peers_to_dial: Default::default(),
+            snapshot_dial_addresses: HashMap::new(),
             inbound_ping_peers: HashSetDelay::new(Duration::from_secs(ping_interval_inbound)),
             outbound_ping_peers: HashSetDelay::new(Duration::from_secs(ping_interval_outbound)),
             status_peers: HashSetDelay::new(Duration::from_secs(status_interval)),
@@ -282,6 +298,9 @@ impl PeerManager {
             ScoreUpdateResult::NoAction => {
                 // The report had no effect on the peer and there is nothing to do.
             }
+            ScoreUpdateResult::Unbanned(_) if self.is_manually_banned(peer_id) => {
+                // The ban placed by the operator outlives the ban caused by the score.
+            }
             ScoreUpdateResult::Unbanned(unbanned_ips) => {
                 // Inform the Swarm to unban the peer
                 self.events
@@ -353,6 +372,11 @@ impl PeerManager {
         let results_count = results.len();
         let connected_or_dialing = self.network_globals.connected_or_dialing_peers();
         for (enr, min_ttl) in results {
+            if !self.is_enr_allowed(&enr) {
+                trace!(peer_id = %enr.peer_id(), "Ignoring discovered peer outside of allowed IP ranges");
+                continue;
+            }
+
             // There are two conditions in deciding whether to dial this peer.
             // 1. If we are less than our max connections. Discovery queries are executed to reach
             //    our target peers, so its fine to dial up to our max peers (which will get pruned
@@ -456,11 +480,12 @@ impl PeerManager {
     /// A peer is being dialed.
     /// Returns true, if this peer will be dialed.
     pub fn dial_peer(&mut self, peer: Enr) -> bool {
-        if self
-            .network_globals
-            .peers
-            .read()
-            .should_dial(&peer.peer_id())
+        if self.is_enr_allowed(&peer)
+            && self
+                .network_globals
+                .peers
+                .read()
+                .should_dial(&peer.peer_id())
         {
             self.peers_to_dial.push(peer);
             true
@@ -469,6 +494,208 @@ impl PeerManager {
         }
     }
 
+    /// Dials the best peers of a snapshot taken before the last shutdown, up to the target peer
+    /// count. Peers are dialed by their recorded score, then by how recently they were connected,
+    /// and peers recorded with a score below the default are skipped. Peers whose ENR has no
+    /// address to dial are dialed at their recorded addresses. Discovery fills any remaining
+    /// slots.
+    ///
+    /// Returns the number of peers queued to be dialed.
+    pub fn dial_snapshot_peers(&mut self, mut snapshot: PeerDBSnapshot) -> usize {
+        snapshot
+            .peers
+            .retain(|record| record.score >= DEFAULT_SCORE);
+        snapshot.peers.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| b.last_connected.cmp(&a.last_connected))
+        });
+
+        let mut dialed = 0;
+
+        for record in snapshot.peers {
+            if dialed >= self.target_peers {
+                break;
+            }
+
+            let Some(enr) = record.enr() else {
+                continue;
+            };
+
+            let has_enr_address = !enr.multiaddr_tcp().is_empty()
+                || (self.quic_enabled && !enr.multiaddr_quic().is_empty());
+
+            let queued = if has_enr_address || record.multiaddrs.is_empty() {
+                self.dial_peer(enr)
+            } else if self
+                .network_globals
+                .peers
+                .read()
+                .should_dial(&enr.peer_id())
+            {
+                // The addresses are checked against the IP filter when the peer is dialed.
+                self.snapshot_dial_addresses
+                    .insert(enr.peer_id(), record.multiaddrs);
+                self.peers_to_dial.push(enr);
+                true
+            } else {
+                false
+            };
+
+            if queued {
+                dialed += 1;
+            }
+        }
+
+        debug!(dialed, "Dialing peers from snapshot");
+
+        dialed
+    }
+
+    /// Restores the ban list persisted by a previous run.
+    ///
+    /// Restored peers and IP ranges are reported as banned so that they are also banned in
+    /// discovery.
+    pub fn restore_ban_list(&mut self, ban_list: BanList) {
+        let (restored, banned_ips) = self
+            .network_globals
+            .peers
+            .write()
+            .restore_ban_list(ban_list);
+
+        debug!(peers = restored.len(), "Restored ban list");
+
+        for (peer_id, banned_ips) in restored {
+            self.events
+                .push(PeerManagerEvent::Banned(peer_id, banned_ips));
+        }
+
+        if !banned_ips.is_empty() {
+            self.events.push(PeerManagerEvent::BannedIps(banned_ips));
+        }
+    }
+
+    /// Bans a peer on request of the operator for `duration`, regardless of its score.
+    ///
+    /// The peer is disconnected if it is connected.
+    pub fn ban_peer_manually(&mut self, peer_id: PeerId, duration: Duration) {
+        let is_connected = {
+            let mut peers = self.network_globals.peers.write();
+            peers.ban_peer_manually(peer_id, ManualBans::deadline(duration));
+            peers.is_connected(&peer_id)
+        };
+
+        info!(%peer_id, ?duration, "Peer banned by the operator");
+
+        if is_connected {
+            self.disconnect_peer(peer_id, GoodbyeReason::Banned);
+        }
+
+        self.events.push(PeerManagerEvent::Banned(peer_id, vec![]));
+    }
+
+    /// Lifts a ban placed by the operator. Returns `false` if the peer was not manually banned.
+    ///
+    /// The peer stays banned if its score is below the ban threshold.
+    pub fn unban_peer_manually(&mut self, peer_id: &PeerId) -> bool {
+        let (unbanned, still_banned) = {
+            let mut peers = self.network_globals.peers.write();
+            let unbanned = peers.unban_peer_manually(peer_id);
+            (unbanned, peers.ban_status(peer_id).is_some())
+        };
+
+        if !unbanned {
+            return false;
+        }
+
+        info!(%peer_id, still_banned, "Peer unbanned by the operator");
+
+        if !still_banned {
+            self.events
+                .push(PeerManagerEvent::UnBanned(*peer_id, vec![]));
+        }
+
+        true
+    }
+
+    /// Bans all addresses within `range` on request of the operator for `duration`.
+    ///
+    /// Connected peers seen within the range are disconnected.
+    pub fn ban_ip_range_manually(&mut self, range: IpNet, duration: Duration) {
+        let (banned_ips, peers_in_range) = self
+            .network_globals
+            .peers
+            .write()
+            .ban_ip_range_manually(range, ManualBans::deadline(duration));
+
+        info!(
+            %range,
+            ?duration,
+            disconnected_peers = peers_in_range.len(),
+            "IP range banned by the operator"
+        );
+
+        for peer_id in peers_in_range {
+            self.disconnect_peer(peer_id, GoodbyeReason::Banned);
+        }
+
+        if !banned_ips.is_empty() {
+            self.events.push(PeerManagerEvent::BannedIps(banned_ips));
+        }
+    }
+
+    /// Lifts a ban of an IP range placed by the operator. Returns `false` if the range was not
+    /// manually banned.
+    pub fn unban_ip_range_manually(&mut self, range: &IpNet) -> bool {
+        let Some(unbanned_ips) = self
+            .network_globals
+            .peers
+            .write()
+            .unban_ip_range_manually(range)
+        else {
+            return false;
+        };
+
+        info!(%range, "IP range unbanned by the operator");
+
+        if !unbanned_ips.is_empty() {
+            self.events
+                .push(PeerManagerEvent::UnBannedIps(unbanned_ips));
+        }
+
+        true
+    }
+
+    /// Returns all active bans placed by the operator or caused by a bad score.
+    pub fn bans(&self) -> Vec<Ban> {
+        self.network_globals.peers.read().bans()
+    }
+
+    /// Returns `true` if the ENR advertises an address permitted by the configured allowed and
+    /// denied IP ranges.
+    fn is_enr_allowed(&self, enr: &Enr) -> bool {
+        let config = &self.network_globals.network_config;
+
+        if !config.has_ip_filter() {
+            return true;
+        }
+
+        let ip4 = enr.ip4().map(IpAddr::V4);
+        let ip6 = enr.ip6().map(IpAddr::V6);
+
+        ip4.into_iter()
+            .chain(ip6)
+            .any(|ip| config.is_ip_allowed(&ip))
+    }
+
+    /// Returns true if the peer has been banned by the operator.
+    fn is_manually_banned(&self, peer_id: &PeerId) -> bool {
+        self.network_globals
+            .peers
+            .read()
+            .is_manually_banned(peer_id)
+    }
+
     /// Reports if a peer is banned or not.
     ///
     /// This is used to determine if we should accept incoming connections.
@@ -487,6 +714,7 @@ impl PeerManager {
             let previous_listening_addresses =
                 peer_info.set_listening_addresses(info.listen_addrs.clone());
             peer_info.set_client(peerdb::client::Client::from_identify_info(info));
+            peer_info.set_supported_protocols(info.protocols.iter().map(ToString::to_string));
 
             if previous_kind != peer_info.client().kind
                 || *peer_info.listening_addresses() != previous_listening_addresses
@@ -1114,6 +1342,8 @@ impl PeerManager {
     /// - Outbound peers: don't prune if it would drop below target outbound peer count
     /// - Data column sampling: ≤ MIN_SAMPLING_COLUMN_SUBNET_PEERS (2) peers per subnet
     /// - Sync committees: ≤ MIN_SYNC_COMMITTEE_PEERS (2) peers per committee
+    /// - Attestation subnets: ≤ MIN_ATTESTATION_SUBNET_PEERS (2) peers per subnet when subscribed
+    ///   to every subnet, as all of them are ours
     /// - Attestation subnets: protect peers on the scarcest attestation subnets
     ///
     /// Returns true if the peer should be protected (not pruned).
@@ -1174,6 +1404,23 @@ impl PeerManager {
                 acc
             });
 
+        // Nodes subscribed to every subnet need peers on all of them
+        if self
+            .network_globals
+            .network_config
+            .subscribes_to_all_subnets()
+        {
+            let should_protect_attestation =
+                candidate_info.attestation_subnets.iter().any(|subnet| {
+                    attestation_subnet_counts.get(subnet).copied().unwrap_or(0)
+                        <= MIN_ATTESTATION_SUBNET_PEERS as usize
+                });
+
+            if should_protect_attestation {
+                return true;
+            }
+        }
+
         if let Some(&least_dense_size) = attestation_subnet_counts.values().min() {
             let is_on_least_dense = candidate_info
                 .attestation_subnets
@@ -1408,6 +1655,26 @@ impl PeerManager {
         }
     }
 
+    /// Lifts manual bans that have expired.
+    fn unban_expired_manual_bans(&mut self) {
+        let (unbanned_peers, unbanned_ips) = self
+            .network_globals
+            .peers
+            .write()
+            .remove_expired_manual_bans();
+
+        for peer_id in unbanned_peers {
+            debug!(%peer_id, "Manual ban of peer expired");
+            self.events
+                .push(PeerManagerEvent::UnBanned(peer_id, Vec::new()));
+        }
+
+        if !unbanned_ips.is_empty() {
+            self.events
+                .push(PeerManagerEvent::UnBannedIps(unbanned_ips));
+        }
+    }
+
     /// The Peer manager's heartbeat maintains the peer count and maintains peer reputations.
     ///
     /// It will request discovery queries if the peer count has not reached the desired number of
@@ -1456,6 +1723,9 @@ impl PeerManager {
         // Unban any peers that have served their temporary ban timeout
         self.unban_temporary_banned_peers();
 
+        // Lift manual bans that have expired
+        self.unban_expired_manual_bans();
+
         // Maintains memory by shrinking mappings
         self.shrink_mappings();
     }
@@ -1704,6 +1974,7 @@ mod tests {
     use super::*;
     use crate::NetworkConfig;
     use crate::rpc::MetaDataV3;
+    use peerdb::snapshot::PeerRecord;
     use types::{
         config::Config as ChainConfig, nonstandard::Phase, phase0::primitives::ForkDigest,
         preset::Mainnet,
@@ -1759,6 +2030,70 @@ mod tests {
         }
     }
 
+    fn snapshot_record(score: f64, last_connected: u64) -> (PeerId, PeerRecord) {
+        use crate::CombinedKeyExt as _;
+
+        let keypair = libp2p::identity::secp256k1::Keypair::generate();
+        let enr_key = discv5::enr::CombinedKey::from_secp256k1(&keypair);
+        let enr = discv5::enr::Enr::builder().build(&enr_key).unwrap();
+
+        let record = PeerRecord {
+            enr: enr.to_base64(),
+            multiaddrs: vec![],
+            client: ClientKind::Unknown,
+            score,
+            custody_subnets: vec![],
+            last_connected,
+        };
+
+        (enr.peer_id(), record)
+    }
+
+    #[tokio::test]
+    async fn test_dial_snapshot_peers_by_score() {
+        let mut peer_manager = build_peer_manager(2).await;
+
+        let (recent, recent_record) = snapshot_record(5.0, 2);
+        let (best, best_record) = snapshot_record(10.0, 1);
+        let (old, old_record) = snapshot_record(5.0, 1);
+        let (bad, bad_record) = snapshot_record(-1.0, 3);
+
+        let snapshot = PeerDBSnapshot {
+            peers: vec![bad_record, old_record, recent_record, best_record],
+        };
+
+        assert_eq!(peer_manager.dial_snapshot_peers(snapshot), 2);
+
+        let dialed = peer_manager
+            .peers_to_dial
+            .iter()
+            .map(Enr::peer_id)
+            .collect::<Vec<_>>();
+
+        assert_eq!(dialed, vec![best, recent]);
+        assert!(!dialed.contains(&old));
+        assert!(!dialed.contains(&bad));
+    }
+
+    #[tokio::test]
+    async fn test_dial_snapshot_peers_without_enr_address() {
+        let mut peer_manager = build_peer_manager(2).await;
+        let multiaddr: Multiaddr = "/ip4/10.0.0.1/tcp/9000".parse().unwrap();
+
+        let (peer_id, mut record) = snapshot_record(5.0, 1);
+        record.multiaddrs = vec![multiaddr.clone()];
+
+        let snapshot = PeerDBSnapshot {
+            peers: vec![record],
+        };
+
+        assert_eq!(peer_manager.dial_snapshot_peers(snapshot), 1);
+        assert_eq!(
+            peer_manager.snapshot_dial_addresses.get(&peer_id),
+            Some(&vec![multiaddr]),
+        );
+    }
+
     #[tokio::test]
     async fn test_peer_manager_disconnects_correctly_during_heartbeat() {
         // Create 6 peers to connect to with a target of 3.
@@ -2778,6 +3113,49 @@ mod tests {
         assert!(subnet_1_count > 0, "Least dense subnet should be protected");
     }
 
+    #[tokio::test]
+    async fn test_peer_manager_protect_attestation_subnet_peers_when_subscribed_to_all() {
+        let build = |subscribe_all_subnets| {
+            let network_config = Arc::new(NetworkConfig {
+                subscribe_all_subnets,
+                ..Default::default()
+            });
+            let globals = NetworkGlobals::new_test_globals::<Mainnet>(
+                Arc::new(ChainConfig::mainnet()),
+                vec![],
+                network_config,
+            );
+            PeerManager::new::<Mainnet>(config::Config::default(), Arc::new(globals)).unwrap()
+        };
+
+        let subnet_info = |subnet_id: SubnetId| PeerSubnetInfo {
+            info: PeerInfo::default(),
+            attestation_subnets: HashSet::from([subnet_id]),
+            sync_committees: HashSet::new(),
+            custody_subnets: HashSet::new(),
+        };
+
+        // Subnet 1 is the least dense one, while subnet 5 is at MIN_ATTESTATION_SUBNET_PEERS.
+        let peer_subnet_info = [1, 5, 5]
+            .into_iter()
+            .map(|subnet_id| (PeerId::random(), subnet_info(subnet_id)))
+            .collect::<HashMap<_, _>>();
+
+        let should_protect = |peer_manager: &PeerManager| {
+            peer_manager.should_protect_peer(
+                &subnet_info(5),
+                &HashSet::new(),
+                &HashMap::new(),
+                &peer_subnet_info,
+                0,
+                0,
+            )
+        };
+
+        assert!(!should_protect(&build(false)));
+        assert!(should_protect(&build(true)));
+    }
+
     /// Test the pruning logic prioritizes synced and advanced peers over behind/unknown peers.
     ///
     /// Create 6 peers with different sync statuses:
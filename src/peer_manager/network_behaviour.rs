@@ -102,9 +102,7 @@ impl NetworkBehaviour for PeerManager {
             self.events.shrink_to_fit();
         }
 
-        if let Some(enr) = self.peers_to_dial.pop() {
-            self.inject_peer_connection(&enr.peer_id(), ConnectingType::Dialing, Some(enr.clone()));
-
+        while let Some(enr) = self.peers_to_dial.pop() {
             let multiaddr_quic = if self.quic_enabled {
                 enr.multiaddr_quic()
             } else {
@@ -112,7 +110,26 @@ impl NetworkBehaviour for PeerManager {
             };
 
             // Prioritize Quic connections over Tcp ones.
-            let multiaddrs = [multiaddr_quic, enr.multiaddr_tcp()].concat();
+            let mut multiaddrs = [multiaddr_quic, enr.multiaddr_tcp()].concat();
+
+            let snapshot_addresses = self.snapshot_dial_addresses.remove(&enr.peer_id());
+            if multiaddrs.is_empty() {
+                multiaddrs = snapshot_addresses.unwrap_or_default();
+            }
+
+            let network_config = &self.network_globals.network_config;
+            if network_config.has_ip_filter() {
+                multiaddrs.retain(|multiaddr| {
+                    multiaddr_ip(multiaddr).is_some_and(|ip| network_config.is_ip_allowed(&ip))
+                });
+
+                if multiaddrs.is_empty() {
+                    debug!(peer_id = %enr.peer_id(), "Not dialing peer without allowed addresses");
+                    continue;
+                }
+            }
+
+            self.inject_peer_connection(&enr.peer_id(), ConnectingType::Dialing, Some(enr.clone()));
 
             debug!(peer_id = %enr.peer_id(), ?multiaddrs, "Dialing peer");
             return Poll::Ready(ToSwarm::Dial {
@@ -165,14 +182,10 @@ impl NetworkBehaviour for PeerManager {
         remote_addr: &libp2p::Multiaddr,
     ) -> Result<(), ConnectionDenied> {
         // get the IP address to verify it's not banned.
-        let ip = match remote_addr.iter().next() {
-            Some(Protocol::Ip6(ip)) => IpAddr::V6(ip),
-            Some(Protocol::Ip4(ip)) => IpAddr::V4(ip),
-            _ => {
-                return Err(ConnectionDenied::new(format!(
-                    "Connection to peer rejected: invalid multiaddr: {remote_addr}"
-                )));
-            }
+        let Some(ip) = multiaddr_ip(remote_addr) else {
+            return Err(ConnectionDenied::new(format!(
+                "Connection to peer rejected: invalid multiaddr: {remote_addr}"
+            )));
         };
 
         if self.network_globals.peers.read().is_ip_banned(&ip) {
@@ -181,6 +194,12 @@ impl NetworkBehaviour for PeerManager {
             )));
         }
 
+        if !self.network_globals.network_config.is_ip_allowed(&ip) {
+            return Err(ConnectionDenied::new(format!(
+                "Connection to peer rejected: peer {ip} is outside of allowed IP ranges"
+            )));
+        }
+
         Ok(())
     }
 
@@ -238,6 +257,25 @@ impl NetworkBehaviour for PeerManager {
             return Err(ConnectionDenied::new(cause));
         }
 
+        // The address may fall within an IP range banned by the operator.
+        if let Some(ip) =
+            multiaddr_ip(addr).filter(|ip| self.network_globals.peers.read().is_ip_banned(ip))
+        {
+            debug!(%peer_id, %ip, "Connected a peer on a banned IP. Rejecting connection");
+            return Err(ConnectionDenied::new(format!(
+                "Connection to peer rejected: peer {ip} is banned"
+            )));
+        }
+
+        if let Some(ip) =
+            multiaddr_ip(addr).filter(|ip| !self.network_globals.network_config.is_ip_allowed(ip))
+        {
+            debug!(%peer_id, %ip, "Connected a peer outside of allowed IP ranges. Rejecting connection");
+            return Err(ConnectionDenied::new(format!(
+                "Connection to peer rejected: peer {ip} is outside of allowed IP ranges"
+            )));
+        }
+
         // Check the connection limits
         if self.network_globals.connected_peers() >= self.max_outbound_dialing_peers()
             && self
@@ -256,6 +294,15 @@ impl NetworkBehaviour for PeerManager {
     }
 }
 
+/// Returns the IP address a multiaddr starts with, if any.
+fn multiaddr_ip(multiaddr: &libp2p::Multiaddr) -> Option<IpAddr> {
+    match multiaddr.iter().next() {
+        Some(Protocol::Ip6(ip)) => Some(IpAddr::V6(ip)),
+        Some(Protocol::Ip4(ip)) => Some(IpAddr::V4(ip)),
+        _ => None,
+    }
+}
+
 impl PeerManager {
     fn on_connection_established(
         &mut self,
@@ -5,13 +5,16 @@ use crate::{
 };
 use eip_7594::compute_subnets_for_node;
 use helper_functions::misc;
+use ipnet::IpNet;
 use itertools::Itertools as _;
 use logging::exception;
+use manual_bans::{Ban, BanSource, BanTarget, ManualBans};
 use peer_info::{ConnectionDirection, PeerConnectionStatus, PeerInfo};
-use score::{PeerAction, ReportSource, Score, ScoreState};
+use score::{DEFAULT_SCORE, PeerAction, ReportSource, Score, ScoreState};
+use snapshot::{BanList, BannedPeerRecord, ManualBanRecord, PeerDBSnapshot, PeerRecord};
 use ssz::H256;
 use std::net::IpAddr;
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{cmp::Ordering, fmt::Display};
 use std::{
     collections::{HashMap, HashSet, hash_map::Entry},
@@ -25,8 +28,10 @@ use types::phase0::primitives::{Epoch, SubnetId};
 use types::preset::Preset;
 
 pub mod client;
+pub mod manual_bans;
 pub mod peer_info;
 pub mod score;
+pub mod snapshot;
 pub mod sync_status;
 
 /// Max number of disconnected nodes to remember.
@@ -35,6 +40,9 @@ const MAX_DC_PEERS: usize = 500;
 pub const MAX_BANNED_PEERS: usize = 1000;
 /// We ban an IP if there are more than `BANNED_PEERS_PER_IP_THRESHOLD` banned peers with this IP.
 const BANNED_PEERS_PER_IP_THRESHOLD: usize = 5;
+/// IP ranges with at most this many host bits have every address banned in discovery. For larger
+/// ranges only the addresses of known peers are banned there.
+const MAX_DISCOVERY_BANNED_RANGE_BITS: u8 = 8;
 /// Relative factor of peers that are allowed to have a negative gossipsub score without penalizing.
 const ALLOWED_NEGATIVE_GOSSIPSUB_FACTOR: f32 = 0.1;
 /// The time we allow peers to be in the dialing state in our PeerDb before we revert them to a
@@ -50,6 +58,8 @@ pub struct PeerDB {
     disconnected_peers: usize,
     /// Counts banned peers in total and per ip
     banned_peers_count: BannedPeersCount,
+    /// Peers and IP ranges banned by the operator.
+    manual_bans: ManualBans,
     /// Specifies if peer scoring is disabled.
     disable_peer_scoring: bool,
 }
@@ -69,6 +79,7 @@ impl PeerDB {
             chain_config,
             disconnected_peers: 0,
             banned_peers_count: BannedPeersCount::default(),
+            manual_bans: ManualBans::default(),
             disable_peer_scoring,
             peers,
         }
@@ -185,6 +196,10 @@ impl PeerDB {
     ///
     /// This is used to determine if we should accept incoming connections or not.
     pub fn ban_status(&self, peer_id: &PeerId) -> Option<BanResult> {
+        if self.manual_bans.is_peer_banned(peer_id) {
+            return Some(BanResult::ManuallyBanned);
+        }
+
         self.peers
             .get(peer_id)
             .and_then(|peer| match peer.score_state() {
@@ -195,17 +210,20 @@ impl PeerDB {
 
     /// Checks if the peer's known addresses are currently banned.
     fn ip_is_banned(&self, peer: &PeerInfo) -> Option<IpAddr> {
-        peer.seen_ip_addresses()
-            .find(|ip| self.banned_peers_count.ip_is_banned(ip))
+        peer.seen_ip_addresses().find(|ip| self.is_ip_banned(ip))
     }
 
     /// Returns true if the IP is banned.
     pub fn is_ip_banned(&self, ip: &IpAddr) -> bool {
-        self.banned_peers_count.ip_is_banned(ip)
+        self.banned_peers_count.ip_is_banned(ip) || self.manual_bans.is_ip_banned(ip)
     }
 
     /// Returns true if the Peer is either banned or in the disconnected state.
     fn score_state_banned_or_disconnected(&self, peer_id: &PeerId) -> bool {
+        if self.manual_bans.is_peer_banned(peer_id) {
+            return true;
+        }
+
         if let Some(peer) = self.peers.get(peer_id) {
             match peer.score_state() {
                 ScoreState::Banned | ScoreState::ForcedDisconnect => true,
@@ -404,6 +422,95 @@ impl PeerDB {
             .map(|(peer_id, _)| peer_id)
     }
 
+    /// Returns a snapshot of at most `max_peers` good peers that can be dialed after a restart,
+    /// sorted by score beginning with the best scores.
+    pub fn snapshot(&self, max_peers: usize) -> PeerDBSnapshot {
+        let mut peers = self
+            .peers
+            .values()
+            .filter(|info| !info.is_banned() && info.score().score() >= DEFAULT_SCORE)
+            .filter_map(PeerRecord::from_peer_info)
+            .collect::<Vec<_>>();
+
+        peers.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| b.last_connected.cmp(&a.last_connected))
+        });
+        peers.truncate(max_peers);
+
+        PeerDBSnapshot { peers }
+    }
+
+    /// Returns the peers banned by score together with their unban deadline, latest deadline
+    /// first and bounded by `MAX_BANNED_PEERS`, and the bans placed by the operator.
+    pub fn ban_list(&self) -> BanList {
+        let now = Instant::now();
+        let system_now = SystemTime::now();
+        let unix_timestamp = |deadline: Instant| {
+            (system_now + deadline.saturating_duration_since(now))
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        };
+
+        let mut peers = self
+            .peers
+            .iter()
+            .filter(|(_, info)| info.is_banned())
+            .filter_map(|(peer_id, info)| {
+                Some(BannedPeerRecord {
+                    peer_id: peer_id.to_string(),
+                    ip_addresses: info.seen_ip_addresses().collect(),
+                    unban_deadline: unix_timestamp(info.score().unban_deadline()?),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        peers.sort_by(|a, b| b.unban_deadline.cmp(&a.unban_deadline));
+        peers.truncate(MAX_BANNED_PEERS);
+
+        let mut manual_peers = Vec::new();
+        let mut manual_ip_ranges = Vec::new();
+
+        for ban in self.manual_bans.bans() {
+            let (records, target) = match ban.target {
+                BanTarget::Peer(peer_id) => (&mut manual_peers, peer_id.to_string()),
+                BanTarget::IpRange(range) => (&mut manual_ip_ranges, range.to_string()),
+            };
+
+            records.push(ManualBanRecord {
+                target,
+                unban_deadline: unix_timestamp(ban.until),
+            });
+        }
+
+        BanList {
+            peers,
+            manual_peers,
+            manual_ip_ranges,
+        }
+    }
+
+    /// Returns all active bans placed by the operator or caused by a bad score, beginning with the
+    /// ban that is lifted first.
+    pub fn bans(&self) -> Vec<Ban> {
+        let score_bans = self.peers.iter().filter_map(|(peer_id, info)| {
+            Some(Ban {
+                target: BanTarget::Peer(*peer_id),
+                source: BanSource::Score,
+                until: info.score().unban_deadline()?,
+            })
+        });
+
+        self.manual_bans
+            .bans()
+            .chain(score_bans)
+            .sorted_by_key(|ban| ban.until)
+            .collect()
+    }
+
     /// Returns a vector of all connected peers sorted by score beginning with the worst scores.
     /// Ties get broken randomly.
     pub fn worst_connected_peers(&self) -> Vec<(&PeerId, &PeerInfo)> {
@@ -1167,6 +1274,183 @@ impl PeerDB {
         self.update_connection_state(peer_id, NewConnectionState::Disconnecting { to_ban });
     }
 
+    /// Bans `peer_id` on request of the operator until `until`, regardless of its score.
+    pub(super) fn ban_peer_manually(&mut self, peer_id: PeerId, until: Instant) {
+        self.manual_bans.ban_peer(peer_id, until);
+    }
+
+    /// Lifts a ban placed by the operator. Returns `false` if the peer was not manually banned.
+    pub(super) fn unban_peer_manually(&mut self, peer_id: &PeerId) -> bool {
+        self.manual_bans.unban_peer(peer_id)
+    }
+
+    /// Bans all addresses within `range` on request of the operator until `until`.
+    ///
+    /// Returns the addresses to ban in discovery and the connected peers seen within the range.
+    pub(super) fn ban_ip_range_manually(
+        &mut self,
+        range: IpNet,
+        until: Instant,
+    ) -> (Vec<IpAddr>, Vec<PeerId>) {
+        let range = range.trunc();
+
+        let discovery_ips =
+            if range.max_prefix_len() - range.prefix_len() <= MAX_DISCOVERY_BANNED_RANGE_BITS {
+                range.hosts().collect::<Vec<_>>()
+            } else {
+                self.peers
+                    .values()
+                    .flat_map(PeerInfo::seen_ip_addresses)
+                    .filter(|ip| range.contains(ip))
+                    .unique()
+                    .collect()
+            };
+
+        let connected_peers = self
+            .peers
+            .iter()
+            .filter(|(_, info)| {
+                info.is_connected() && info.seen_ip_addresses().any(|ip| range.contains(&ip))
+            })
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+
+        self.manual_bans
+            .ban_ip_range(range, until, discovery_ips.clone());
+
+        (discovery_ips, connected_peers)
+    }
+
+    /// Lifts a ban of an IP range placed by the operator. Returns the addresses to unban in
+    /// discovery, or `None` if the range was not manually banned.
+    pub(super) fn unban_ip_range_manually(&mut self, range: &IpNet) -> Option<Vec<IpAddr>> {
+        let discovery_ips = self.manual_bans.unban_ip_range(range)?;
+
+        Some(
+            discovery_ips
+                .into_iter()
+                .filter(|ip| !self.is_ip_banned(ip))
+                .collect(),
+        )
+    }
+
+    /// Removes expired bans placed by the operator. Returns the peers that are no longer banned
+    /// and the addresses to unban in discovery.
+    pub(super) fn remove_expired_manual_bans(&mut self) -> (Vec<PeerId>, Vec<IpAddr>) {
+        let (peers, ips) = self.manual_bans.remove_expired();
+
+        let peers = peers
+            .into_iter()
+            .filter(|peer_id| self.ban_status(peer_id).is_none())
+            .collect();
+        let ips = ips
+            .into_iter()
+            .filter(|ip| !self.is_ip_banned(ip))
+            .collect();
+
+        (peers, ips)
+    }
+
+    /// Returns true if the peer has been banned by the operator.
+    pub fn is_manually_banned(&self, peer_id: &PeerId) -> bool {
+        self.manual_bans.is_peer_banned(peer_id)
+    }
+
+    /// Bans the peers of a ban list persisted by a previous run until their unban deadline and
+    /// restores the bans placed by the operator.
+    ///
+    /// Expired entries and peers banned by score that are already known are skipped, and at most
+    /// `MAX_BANNED_PEERS` peers banned by score are restored. Returns the restored peers together
+    /// with the IPs that are now banned, and the IPs of restored IP ranges to ban in discovery.
+    #[must_use = "Banned peers need to be reported to discovery."]
+    pub(super) fn restore_ban_list(
+        &mut self,
+        ban_list: BanList,
+    ) -> (Vec<(PeerId, Vec<IpAddr>)>, Vec<IpAddr>) {
+        let now = Instant::now();
+        let mut restored = Vec::new();
+        let mut manually_banned_peers = Vec::new();
+        let mut manually_banned_ips = Vec::new();
+
+        for record in ban_list.manual_peers {
+            let Some(remaining) = record.remaining() else {
+                continue;
+            };
+
+            let Some(peer_id) = record.peer_id() else {
+                warn!(peer_id = %record.target, "Invalid peer id in ban list");
+                continue;
+            };
+
+            self.ban_peer_manually(peer_id, ManualBans::deadline(remaining));
+            manually_banned_peers.push((peer_id, vec![]));
+        }
+
+        for record in ban_list.manual_ip_ranges {
+            let Some(remaining) = record.remaining() else {
+                continue;
+            };
+
+            let Some(range) = record.ip_range() else {
+                warn!(range = %record.target, "Invalid IP range in ban list");
+                continue;
+            };
+
+            let (discovery_ips, _) =
+                self.ban_ip_range_manually(range, ManualBans::deadline(remaining));
+
+            manually_banned_ips.extend(discovery_ips);
+        }
+
+        for record in ban_list.peers {
+            if self.banned_peers_count.banned_peers() >= MAX_BANNED_PEERS {
+                break;
+            }
+
+            let Some(remaining) = record.remaining() else {
+                continue;
+            };
+
+            let Some(peer_id) = record.peer_id() else {
+                warn!(peer_id = %record.peer_id, "Invalid peer id in ban list");
+                continue;
+            };
+
+            let Entry::Vacant(entry) = self.peers.entry(peer_id) else {
+                continue;
+            };
+
+            let info = entry.insert(PeerInfo::banned_peer_info(
+                Score::banned_until(now + remaining),
+                &record.ip_addresses,
+            ));
+
+            self.banned_peers_count
+                .add_banned_peer(info.seen_ip_addresses());
+
+            restored.push(peer_id);
+        }
+
+        let known_banned_ips = self.banned_peers_count.banned_ips();
+
+        let restored = restored
+            .into_iter()
+            .map(|peer_id| {
+                let banned_ips = self
+                    .peers
+                    .get(&peer_id)
+                    .into_iter()
+                    .flat_map(PeerInfo::seen_ip_addresses)
+                    .filter(|ip| known_banned_ips.contains(ip))
+                    .collect::<Vec<_>>();
+                (peer_id, banned_ips)
+            })
+            .chain(manually_banned_peers)
+            .collect();
+
+        (restored, manually_banned_ips)
+    }
+
     /// Removes banned and disconnected peers from the DB if we have reached any of our limits.
     /// Drops the peers with the lowest reputation so that the number of disconnected peers is less
     /// than MAX_DC_PEERS
@@ -1383,6 +1667,8 @@ pub enum BanOperation {
 pub enum BanResult {
     /// The peer's score is too low causing it to be banned.
     BadScore,
+    /// The peer has been banned by the operator.
+    ManuallyBanned,
     /// The peer should be banned because it is connecting from a banned IP address.
     BannedIp(IpAddr),
 }
@@ -1391,6 +1677,7 @@ impl Display for BanResult {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             BanResult::BadScore => write!(f, "Peer has a bad score"),
+            BanResult::ManuallyBanned => write!(f, "Peer is banned by the operator"),
             BanResult::BannedIp(addr) => write!(f, "Peer address: {} is banned", addr),
         }
     }
@@ -1451,6 +1738,7 @@ mod tests {
     use super::*;
     use libp2p::core::multiaddr::Protocol;
     use std::net::{Ipv4Addr, Ipv6Addr};
+    use types::preset::Mainnet;
 
     fn add_score(db: &mut PeerDB, peer_id: &PeerId, score: f64) {
         if let Some(info) = db.peer_info_mut(peer_id) {
@@ -1655,6 +1943,137 @@ mod tests {
         assert_eq!(vec![&p1, &p0, &p2], best_peers);
     }
 
+    #[test]
+    fn test_snapshot_contains_good_peers_with_enr() {
+        let mut pdb = get_db();
+
+        let p0 = pdb.__add_connected_peer_testing_only::<Mainnet>(false);
+        let p1 = pdb.__add_connected_peer_testing_only::<Mainnet>(false);
+        let p2 = pdb.__add_connected_peer_testing_only::<Mainnet>(false);
+        // A peer without an ENR cannot be dialed and is left out of the snapshot.
+        let p3 = PeerId::random();
+        pdb.connect_ingoing(&p3, "/ip4/0.0.0.0".parse().unwrap(), None);
+        add_score(&mut pdb, &p0, 10.0);
+        add_score(&mut pdb, &p1, 20.0);
+        add_score(&mut pdb, &p2, -10.0);
+        add_score(&mut pdb, &p3, 30.0);
+
+        let enrs = pdb
+            .snapshot(10)
+            .peers
+            .iter()
+            .map(|record| record.enr().expect("ENR is valid").peer_id())
+            .collect::<Vec<_>>();
+        assert_eq!(enrs, vec![p1, p0]);
+
+        assert_eq!(pdb.snapshot(1).peers.len(), 1);
+    }
+
+    #[test]
+    fn test_ban_list_survives_restart() {
+        let mut pdb = get_db();
+
+        let ip = Ipv4Addr::new(1, 2, 3, 4).into();
+        let banned = connect_peer_with_ips(&mut pdb, vec![ip]);
+        let healthy = connect_peer_with_ips(&mut pdb, vec![ip]);
+        let _ = pdb.report_peer(&banned, PeerAction::Fatal, ReportSource::PeerManager, "");
+        pdb.inject_disconnect(&banned);
+
+        let ban_list = pdb.ban_list();
+        assert_eq!(ban_list.peers.len(), 1);
+        assert_eq!(ban_list.peers[0].peer_id(), Some(banned));
+        assert_eq!(ban_list.peers[0].ip_addresses, vec![ip]);
+
+        // A new `PeerDB` knows nothing about the ban until the ban list is restored.
+        let mut pdb = get_db();
+        let restored = pdb.restore_ban_list(ban_list.clone());
+
+        assert_eq!(restored, (vec![(banned, vec![])], vec![]));
+        assert!(pdb.peer_info(&banned).is_some_and(PeerInfo::is_banned));
+        assert!(pdb.peer_info(&healthy).is_none());
+        assert!(pdb.ban_status(&banned).is_some());
+
+        let restored_deadline = pdb.ban_list().peers[0].unban_deadline;
+        assert!(restored_deadline.abs_diff(ban_list.peers[0].unban_deadline) <= 1);
+
+        // Known peers and expired entries are not restored.
+        let expired = BannedPeerRecord {
+            peer_id: PeerId::random().to_string(),
+            ip_addresses: vec![],
+            unban_deadline: 0,
+        };
+        let restored = pdb.restore_ban_list(BanList {
+            peers: vec![ban_list.peers[0].clone(), expired],
+            ..BanList::default()
+        });
+        assert_eq!(restored, (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_manual_bans_survive_restart() {
+        let mut pdb = get_db();
+
+        let peer_id = PeerId::random();
+        let range = "10.0.0.0/30".parse::<IpNet>().unwrap();
+        let until = Instant::now() + std::time::Duration::from_secs(60);
+
+        pdb.ban_peer_manually(peer_id, until);
+        let _ = pdb.ban_ip_range_manually(range, until);
+
+        let ban_list = pdb.ban_list();
+        assert!(ban_list.peers.is_empty());
+        assert_eq!(ban_list.manual_peers.len(), 1);
+        assert_eq!(ban_list.manual_ip_ranges.len(), 1);
+
+        let mut pdb = get_db();
+        let (restored, banned_ips) = pdb.restore_ban_list(ban_list);
+
+        assert_eq!(restored, vec![(peer_id, vec![])]);
+        assert_eq!(banned_ips.len(), 2);
+        assert!(pdb.is_manually_banned(&peer_id));
+        assert!(pdb.is_ip_banned(&"10.0.0.1".parse().unwrap()));
+        assert_eq!(pdb.bans().len(), 2);
+    }
+
+    #[test]
+    fn test_manual_bans() {
+        let mut pdb = get_db();
+
+        let ip = Ipv4Addr::new(10, 0, 0, 1).into();
+        let peer_in_range = connect_peer_with_ips(&mut pdb, vec![ip]);
+        let unknown_peer = PeerId::random();
+        let until = Instant::now() + std::time::Duration::from_secs(60);
+
+        pdb.ban_peer_manually(unknown_peer, until);
+        assert!(matches!(
+            pdb.ban_status(&unknown_peer),
+            Some(BanResult::ManuallyBanned)
+        ));
+        assert!(!pdb.should_dial(&unknown_peer));
+
+        let (discovery_ips, connected_peers) =
+            pdb.ban_ip_range_manually("10.0.0.0/24".parse().unwrap(), until);
+        assert_eq!(discovery_ips.len(), 254);
+        assert_eq!(connected_peers, vec![peer_in_range]);
+        assert!(pdb.is_ip_banned(&ip));
+        assert!(matches!(
+            pdb.ban_status(&peer_in_range),
+            Some(BanResult::BannedIp(banned_ip)) if banned_ip == ip
+        ));
+        assert_eq!(pdb.bans().len(), 2);
+
+        assert!(pdb.unban_peer_manually(&unknown_peer));
+        assert!(!pdb.unban_peer_manually(&unknown_peer));
+        assert!(pdb.ban_status(&unknown_peer).is_none());
+
+        let unbanned_ips = pdb
+            .unban_ip_range_manually(&"10.0.0.0/24".parse().unwrap())
+            .expect("range is banned");
+        assert_eq!(unbanned_ips, discovery_ips);
+        assert!(pdb.ban_status(&peer_in_range).is_none());
+        assert!(pdb.bans().is_empty());
+    }
+
     #[test]
     fn test_the_best_peer() {
         let mut pdb = get_db();
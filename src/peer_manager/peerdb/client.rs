@@ -3,7 +3,7 @@
 //! Currently using identify to fingerprint.
 
 use libp2p::identify::Info as IdentifyInfo;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use strum::{AsRefStr, EnumIter, IntoStaticStr};
 
 /// Various client and protocol information related to a node.
@@ -21,7 +21,18 @@ pub struct Client {
     pub agent_string: Option<String>,
 }
 
-#[derive(Clone, Copy, Debug, Serialize, PartialEq, AsRefStr, IntoStaticStr, EnumIter)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    AsRefStr,
+    IntoStaticStr,
+    EnumIter,
+)]
 pub enum ClientKind {
     /// A Grandine node.
     Grandine,
@@ -38,6 +49,7 @@ pub enum ClientKind {
     /// A Caplin node.
     Caplin,
     /// An unknown client.
+    #[default]
     Unknown,
 }
 
@@ -0,0 +1,195 @@
+//! Bans placed by the node operator, independent of peer scores.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use ipnet::IpNet;
+use libp2p::PeerId;
+
+/// Upper bound of a manual ban (10 years), so that the deadline can always be represented.
+const MAX_MANUAL_BAN_DURATION: Duration = Duration::from_secs(10 * 365 * 24 * 60 * 60);
+
+/// What a ban applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BanTarget {
+    Peer(PeerId),
+    IpRange(IpNet),
+}
+
+/// Why a ban was placed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BanSource {
+    /// The ban was placed by the operator.
+    Manual,
+    /// The score of the peer dropped below the ban threshold.
+    Score,
+}
+
+/// An active ban.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ban {
+    pub target: BanTarget,
+    pub source: BanSource,
+    /// When the ban is lifted. This is an estimate for bans by score.
+    pub until: Instant,
+}
+
+struct IpRangeBan {
+    until: Instant,
+    /// Addresses within the range that were banned in discovery.
+    discovery_ips: Vec<IpAddr>,
+}
+
+/// Peers and IP ranges banned by the operator.
+#[derive(Default)]
+pub struct ManualBans {
+    peers: HashMap<PeerId, Instant>,
+    ip_ranges: HashMap<IpNet, IpRangeBan>,
+}
+
+impl ManualBans {
+    /// Returns the deadline of a ban lasting `duration` from now.
+    pub fn deadline(duration: Duration) -> Instant {
+        Instant::now() + duration.min(MAX_MANUAL_BAN_DURATION)
+    }
+
+    /// Bans `peer_id` until `until`, replacing any previous manual ban of the peer.
+    pub fn ban_peer(&mut self, peer_id: PeerId, until: Instant) {
+        self.peers.insert(peer_id, until);
+    }
+
+    /// Lifts the ban of `peer_id`. Returns `false` if the peer was not banned.
+    pub fn unban_peer(&mut self, peer_id: &PeerId) -> bool {
+        self.peers.remove(peer_id).is_some()
+    }
+
+    /// Bans `range` until `until`, replacing the deadline of any previous ban of the range.
+    /// `discovery_ips` are the addresses within the range that were banned in discovery.
+    pub fn ban_ip_range(&mut self, range: IpNet, until: Instant, discovery_ips: Vec<IpAddr>) {
+        let ban = self
+            .ip_ranges
+            .entry(range.trunc())
+            .or_insert_with(|| IpRangeBan {
+                until,
+                discovery_ips: Vec::new(),
+            });
+
+        ban.until = until;
+
+        for ip in discovery_ips {
+            if !ban.discovery_ips.contains(&ip) {
+                ban.discovery_ips.push(ip);
+            }
+        }
+    }
+
+    /// Lifts the ban of `range`. Returns the addresses that were banned in discovery, or `None`
+    /// if the range was not banned.
+    pub fn unban_ip_range(&mut self, range: &IpNet) -> Option<Vec<IpAddr>> {
+        self.ip_ranges
+            .remove(&range.trunc())
+            .map(|ban| ban.discovery_ips)
+    }
+
+    /// Returns `true` if `peer_id` is banned.
+    pub fn is_peer_banned(&self, peer_id: &PeerId) -> bool {
+        self.peers
+            .get(peer_id)
+            .is_some_and(|until| *until > Instant::now())
+    }
+
+    /// Returns `true` if `ip` is within a banned range.
+    pub fn is_ip_banned(&self, ip: &IpAddr) -> bool {
+        let now = Instant::now();
+
+        self.ip_ranges
+            .iter()
+            .any(|(range, ban)| ban.until > now && range.contains(ip))
+    }
+
+    /// Removes expired bans. Returns the unbanned peers and the addresses that were banned in
+    /// discovery for the unbanned ranges.
+    pub fn remove_expired(&mut self) -> (Vec<PeerId>, Vec<IpAddr>) {
+        let now = Instant::now();
+        let mut unbanned_peers = Vec::new();
+        let mut unbanned_ips = Vec::new();
+
+        self.peers.retain(|peer_id, until| {
+            let expired = *until <= now;
+            if expired {
+                unbanned_peers.push(*peer_id);
+            }
+            !expired
+        });
+
+        self.ip_ranges.retain(|_, ban| {
+            let expired = ban.until <= now;
+            if expired {
+                unbanned_ips.append(&mut ban.discovery_ips);
+            }
+            !expired
+        });
+
+        (unbanned_peers, unbanned_ips)
+    }
+
+    /// Returns the active manual bans.
+    pub fn bans(&self) -> impl Iterator<Item = Ban> + '_ {
+        let peers = self.peers.iter().map(|(peer_id, until)| Ban {
+            target: BanTarget::Peer(*peer_id),
+            source: BanSource::Manual,
+            until: *until,
+        });
+
+        let ip_ranges = self.ip_ranges.iter().map(|(range, ban)| Ban {
+            target: BanTarget::IpRange(*range),
+            source: BanSource::Manual,
+            until: ban.until,
+        });
+
+        let now = Instant::now();
+
+        peers.chain(ip_ranges).filter(move |ban| ban.until > now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ip_range_bans_cover_contained_addresses() {
+        let mut bans = ManualBans::default();
+        let range = "10.1.2.3/16".parse::<IpNet>().unwrap();
+
+        bans.ban_ip_range(range, ManualBans::deadline(Duration::from_secs(60)), vec![]);
+
+        assert!(bans.is_ip_banned(&"10.1.200.1".parse().unwrap()));
+        assert!(!bans.is_ip_banned(&"10.2.0.1".parse().unwrap()));
+
+        // The range is normalized, so any address within the network lifts the ban.
+        let unbanned = "10.1.0.0/16".parse::<IpNet>().unwrap();
+        assert_eq!(bans.unban_ip_range(&unbanned), Some(vec![]));
+        assert!(!bans.is_ip_banned(&"10.1.200.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn expired_bans_are_removed() {
+        let mut bans = ManualBans::default();
+        let expired_peer = PeerId::random();
+        let active_peer = PeerId::random();
+        let ip = "192.168.0.1".parse::<IpAddr>().unwrap();
+
+        bans.ban_peer(expired_peer, Instant::now());
+        bans.ban_peer(active_peer, ManualBans::deadline(Duration::from_secs(60)));
+        bans.ban_ip_range("192.168.0.0/24".parse().unwrap(), Instant::now(), vec![ip]);
+
+        assert!(!bans.is_peer_banned(&expired_peer));
+        assert!(bans.is_peer_banned(&active_peer));
+        assert_eq!(bans.bans().count(), 1);
+
+        assert_eq!(bans.remove_expired(), (vec![expired_peer], vec![ip]));
+        assert!(bans.is_peer_banned(&active_peer));
+    }
+}
@@ -28,6 +28,8 @@ pub struct PeerInfo {
     /// The known listening addresses of this peer. This is given by identify and can be arbitrary
     /// (including local IPs).
     listening_addresses: Vec<Multiaddr>,
+    /// The protocols the peer supports. This is given by identify.
+    supported_protocols: HashSet<String>,
     /// These are the multiaddrs we have physically seen and is what we use for banning/un-banning
     /// peers.
     seen_multiaddrs: HashSet<Multiaddr>,
@@ -64,6 +66,7 @@ impl Default for PeerInfo {
             client: Client::default(),
             connection_status: Default::default(),
             listening_addresses: Vec::new(),
+            supported_protocols: HashSet::new(),
             seen_multiaddrs: HashSet::new(),
             subnets: HashSet::new(),
             custody_subnets: HashSet::new(),
@@ -87,6 +90,18 @@ impl PeerInfo {
         }
     }
 
+    /// Return a PeerInfo struct for a peer restored from the persisted ban list.
+    pub(super) fn banned_peer_info(score: Score, ip_addresses: &[IpAddr]) -> Self {
+        PeerInfo {
+            score,
+            connection_status: Banned {
+                since: Instant::now(),
+            },
+            seen_multiaddrs: ip_addresses.iter().copied().map(Multiaddr::from).collect(),
+            ..Default::default()
+        }
+    }
+
     /// Returns if the peer is subscribed to a given `Subnet` from the metadata attnets/syncnets field.
     /// Also returns true if the peer is assigned to custody a given data column `Subnet` computed from the metadata `custody_group_count` field or ENR `cgc` field.
     pub fn on_subnet_metadata(&self, subnet: &Subnet) -> bool {
@@ -118,6 +133,11 @@ impl PeerInfo {
         &self.listening_addresses
     }
 
+    /// Returns true if the peer advertised support for `protocol` via identify.
+    pub fn supports_protocol(&self, protocol: &str) -> bool {
+        self.supported_protocols.contains(protocol)
+    }
+
     /// Returns the connection direction for the peer.
     pub fn connection_direction(&self) -> Option<&ConnectionDirection> {
         self.connection_direction.as_ref()
@@ -397,6 +417,15 @@ impl PeerInfo {
         std::mem::replace(&mut self.listening_addresses, listening_addresses)
     }
 
+    /// Replaces the protocols the peer supports.
+    // VISIBILITY: The peer manager is able to set the supported protocols
+    pub(in crate::peer_manager) fn set_supported_protocols(
+        &mut self,
+        supported_protocols: impl IntoIterator<Item = String>,
+    ) {
+        self.supported_protocols = supported_protocols.into_iter().collect();
+    }
+
     /// Sets an explicit value for the meta data.
     // VISIBILITY: The peer manager is able to adjust the meta_data
     pub(in crate::peer_manager) fn set_meta_data(&mut self, meta_data: MetaData) {
@@ -680,4 +709,16 @@ mod tests {
         // Peer is subscribed to any custody subnets - return true
         assert!(peer_info.has_long_lived_subnet());
     }
+
+    #[test]
+    fn test_supports_protocols_given_by_identify() {
+        let mut peer_info = create_test_peer_info();
+        assert!(!peer_info.supports_protocol("/eth2/beacon_chain/req/status/1/ssz_snappy"));
+
+        peer_info
+            .set_supported_protocols(["/eth2/beacon_chain/req/status/1/ssz_snappy".to_owned()]);
+
+        assert!(peer_info.supports_protocol("/eth2/beacon_chain/req/status/1/ssz_snappy"));
+        assert!(!peer_info.supports_protocol("/eth2/beacon_chain/req/status/2/ssz_snappy"));
+    }
 }
@@ -258,6 +258,46 @@ impl RealScore {
     pub fn is_good_gossipsub_peer(&self) -> bool {
         self.gossipsub_score >= 0.0
     }
+
+    /// Returns the estimated time at which the score decays above the ban threshold, or `None` if
+    /// the peer is not banned.
+    fn unban_deadline(&self) -> Option<Instant> {
+        if self.score > MIN_SCORE_BEFORE_BAN {
+            return None;
+        }
+
+        // Decay starts at `last_updated`, which is in the future for recently banned peers.
+        let decay_start = self.last_updated.max(Instant::now());
+        let halflives = (self.score / MIN_SCORE_BEFORE_BAN).log2().max(0.0);
+
+        Some(decay_start + Duration::from_secs_f64(halflives * SCORE_HALFLIFE))
+    }
+
+    /// Builds a banned score that decays above the ban threshold at `unban_deadline`.
+    fn banned_until(unban_deadline: Instant) -> Self {
+        let now = Instant::now();
+        let remaining = unban_deadline.saturating_duration_since(now).as_secs_f64();
+        // The time it takes for the minimum score to decay to the ban threshold.
+        let decay_from_min_score = (MIN_SCORE / MIN_SCORE_BEFORE_BAN).log2() * SCORE_HALFLIFE;
+
+        let (peer_score, last_updated) = if remaining >= decay_from_min_score {
+            let decay_start = now + Duration::from_secs_f64(remaining - decay_from_min_score);
+            (MIN_SCORE, decay_start)
+        } else {
+            let peer_score = MIN_SCORE_BEFORE_BAN * 2.0f64.powf(remaining / SCORE_HALFLIFE);
+            (peer_score, now)
+        };
+
+        let mut score = RealScore {
+            peer_score,
+            last_updated,
+            ..Default::default()
+        };
+
+        // `update_state` would extend the ban by `BANNED_BEFORE_DECAY`.
+        score.recompute_score();
+        score
+    }
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -308,6 +348,20 @@ impl Score {
         Self::Max
     }
 
+    /// Returns a banned score that decays above the ban threshold at `unban_deadline`.
+    pub(crate) fn banned_until(unban_deadline: Instant) -> Self {
+        Self::Real(RealScore::banned_until(unban_deadline))
+    }
+
+    /// Returns the estimated time at which a banned peer is unbanned, or `None` if the peer is not
+    /// banned.
+    pub(crate) fn unban_deadline(&self) -> Option<Instant> {
+        match self {
+            Self::Max => None,
+            Self::Real(score) => score.unban_deadline(),
+        }
+    }
+
     /// Returns the expected state of the peer given it's score.
     pub(crate) fn state(&self) -> ScoreState {
         match self.score() {
@@ -398,6 +452,29 @@ mod tests {
         assert!(score.score() > MIN_SCORE_BEFORE_BAN);
     }
 
+    #[test]
+    fn test_banned_until_restores_unban_deadline() {
+        for remaining in [
+            Duration::from_secs(60),
+            Duration::from_secs(3600),
+            BANNED_BEFORE_DECAY,
+        ] {
+            let deadline = Instant::now() + remaining;
+            let score = Score::banned_until(deadline);
+
+            assert_eq!(score.state(), ScoreState::Banned);
+
+            let restored = score.unban_deadline().expect("score is banned");
+            let difference = restored
+                .checked_duration_since(deadline)
+                .unwrap_or_else(|| deadline.duration_since(restored));
+            assert!(difference < Duration::from_secs(1));
+        }
+
+        assert_eq!(Score::default().unban_deadline(), None);
+        assert_eq!(Score::max_score().unban_deadline(), None);
+    }
+
     #[test]
     fn test_very_negative_gossipsub_score() {
         let mut score = Score::default();
@@ -0,0 +1,428 @@
+//! Persisted snapshots of the `PeerDB`.
+//!
+//! Good peers are used to warm-start connections after a restart, while the ban list keeps
+//! misbehaving peers banned across restarts.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr as _;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use discv5::Enr;
+use ipnet::IpNet;
+use libp2p::{Multiaddr, PeerId};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
+use types::phase0::primitives::SubnetId;
+
+use super::client::ClientKind;
+use super::peer_info::{PeerConnectionStatus, PeerInfo};
+use crate::task_executor::TaskExecutor;
+
+/// File name of the peer snapshot within the network directory.
+pub const PEERDB_SNAPSHOT_FILENAME: &str = "peers.json";
+/// File name of the ban list within the network directory.
+pub const BAN_LIST_FILENAME: &str = "banned_peers.json";
+
+/// A known good peer as recorded in a snapshot.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    /// The base64 encoded ENR of the peer.
+    pub enr: String,
+    /// The addresses the peer listened on or was last seen at, for peers whose ENR has none.
+    #[serde(default)]
+    pub multiaddrs: Vec<Multiaddr>,
+    /// The client the peer was running.
+    #[serde(default)]
+    pub client: ClientKind,
+    /// The score of the peer at the time of the snapshot.
+    pub score: f64,
+    /// The data column subnets the peer custodies.
+    pub custody_subnets: Vec<SubnetId>,
+    /// Unix timestamp in seconds of the last time the peer was connected.
+    pub last_connected: u64,
+}
+
+impl PeerRecord {
+    /// Builds a record from `info`. Returns `None` if the peer cannot be dialed later, i.e. it has
+    /// no ENR or we have never been connected to it.
+    pub(crate) fn from_peer_info(info: &PeerInfo) -> Option<Self> {
+        let enr = info.enr()?;
+
+        info.connection_direction()?;
+
+        let now = SystemTime::now();
+        let last_connected = match info.connection_status() {
+            PeerConnectionStatus::Disconnected { since } => {
+                now.checked_sub(since.elapsed()).unwrap_or(UNIX_EPOCH)
+            }
+            _ => now,
+        };
+
+        let mut custody_subnets = info.custody_subnets_iter().copied().collect::<Vec<_>>();
+        custody_subnets.sort_unstable();
+
+        let mut multiaddrs = info.listening_addresses().clone();
+
+        for multiaddr in info.seen_multiaddrs() {
+            if !multiaddrs.contains(multiaddr) {
+                multiaddrs.push(multiaddr.clone());
+            }
+        }
+
+        Some(Self {
+            enr: enr.to_base64(),
+            multiaddrs,
+            client: info.client().kind,
+            // Trusted peers have an infinite score, which cannot be represented in JSON.
+            score: info.score().score().clamp(f64::MIN, f64::MAX),
+            custody_subnets,
+            last_connected: last_connected
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })
+    }
+
+    /// Returns the decoded ENR of the peer, if valid.
+    pub fn enr(&self) -> Option<Enr> {
+        Enr::from_str(&self.enr).ok()
+    }
+}
+
+/// The good peers of the `PeerDB` at a point in time, best peers first.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PeerDBSnapshot {
+    pub peers: Vec<PeerRecord>,
+}
+
+impl PeerDBSnapshot {
+    /// Loads a snapshot from `dir`, if one exists.
+    pub fn load_from_disk(dir: &Path) -> Option<Self> {
+        let path = dir.join(PEERDB_SNAPSHOT_FILENAME);
+
+        match load_json::<Self>(&path) {
+            Ok(snapshot) => {
+                if let Some(snapshot) = &snapshot {
+                    debug!(
+                        peers = snapshot.peers.len(),
+                        "Loaded peer snapshot from disk"
+                    );
+                }
+                snapshot
+            }
+            Err(e) => {
+                warn!(file = ?path, error = %e, "Could not load peer snapshot from disk");
+                None
+            }
+        }
+    }
+
+    /// Writes the snapshot to `dir`, replacing any previous snapshot.
+    pub fn save_to_disk(&self, dir: &Path) {
+        let path = dir.join(PEERDB_SNAPSHOT_FILENAME);
+
+        match save_json(dir, &path, self) {
+            Ok(_) => {
+                debug!(peers = self.peers.len(), "Peer snapshot written to disk");
+            }
+            Err(e) => {
+                warn!(file = ?path, error = %e, "Could not write peer snapshot to disk");
+            }
+        }
+    }
+}
+
+/// A banned peer as recorded in the ban list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BannedPeerRecord {
+    /// The base58 encoded peer id.
+    pub peer_id: String,
+    /// The IP addresses the peer was seen on.
+    pub ip_addresses: Vec<IpAddr>,
+    /// Unix timestamp in seconds after which the peer is no longer banned.
+    pub unban_deadline: u64,
+}
+
+impl BannedPeerRecord {
+    /// Returns the decoded peer id, if valid.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        PeerId::from_str(&self.peer_id).ok()
+    }
+
+    /// Returns the time until the peer is unbanned, or `None` if the deadline has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        remaining_until(self.unban_deadline)
+    }
+}
+
+/// A ban placed by the operator as recorded in the ban list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ManualBanRecord {
+    /// The base58 encoded peer id or the IP range in CIDR notation.
+    pub target: String,
+    /// Unix timestamp in seconds after which the ban is lifted.
+    pub unban_deadline: u64,
+}
+
+impl ManualBanRecord {
+    /// Returns the decoded peer id, if valid.
+    pub fn peer_id(&self) -> Option<PeerId> {
+        PeerId::from_str(&self.target).ok()
+    }
+
+    /// Returns the decoded IP range, if valid.
+    pub fn ip_range(&self) -> Option<IpNet> {
+        IpNet::from_str(&self.target).ok()
+    }
+
+    /// Returns the time until the ban is lifted, or `None` if the deadline has passed.
+    pub fn remaining(&self) -> Option<Duration> {
+        remaining_until(self.unban_deadline)
+    }
+}
+
+/// The banned peers of the `PeerDB`, latest unban deadline first, and the bans placed by the
+/// operator.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BanList {
+    pub peers: Vec<BannedPeerRecord>,
+    /// Peers banned by the operator.
+    #[serde(default)]
+    pub manual_peers: Vec<ManualBanRecord>,
+    /// IP ranges banned by the operator.
+    #[serde(default)]
+    pub manual_ip_ranges: Vec<ManualBanRecord>,
+}
+
+impl BanList {
+    /// Loads the ban list from `dir`, if one exists.
+    pub fn load_from_disk(dir: &Path) -> Option<Self> {
+        let path = dir.join(BAN_LIST_FILENAME);
+
+        match load_json::<Self>(&path) {
+            Ok(ban_list) => {
+                if let Some(ban_list) = &ban_list {
+                    debug!(peers = ban_list.peers.len(), "Loaded ban list from disk");
+                }
+                ban_list
+            }
+            Err(e) => {
+                warn!(file = ?path, error = %e, "Could not load ban list from disk");
+                None
+            }
+        }
+    }
+
+    /// Writes the ban list to `dir`, replacing any previous ban list.
+    pub fn save_to_disk(&self, dir: &Path) {
+        let path = dir.join(BAN_LIST_FILENAME);
+
+        match save_json(dir, &path, self) {
+            Ok(_) => {
+                debug!(peers = self.peers.len(), "Ban list written to disk");
+            }
+            Err(e) => {
+                warn!(file = ?path, error = %e, "Could not write ban list to disk");
+            }
+        }
+    }
+}
+
+/// A write performed by the `PeerDBWriter`.
+enum PeerDBWrite {
+    BanList(BanList),
+    Snapshot(PeerDBSnapshot),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the ban list and peer snapshots to a directory on a blocking task, so that disk I/O
+/// does not block the network. Only the latest of the ban lists and of the snapshots waiting to be
+/// written are written.
+pub struct PeerDBWriter {
+    sender: mpsc::UnboundedSender<PeerDBWrite>,
+}
+
+impl PeerDBWriter {
+    /// Spawns the writer task, which writes to `dir`.
+    pub fn new(executor: &TaskExecutor, dir: PathBuf) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        executor.spawn_blocking(
+            move || {
+                while let Some(write) = receiver.blocking_recv() {
+                    let mut ban_list = None;
+                    let mut snapshot = None;
+                    let mut flushes = Vec::new();
+
+                    let queued = std::iter::from_fn(|| receiver.try_recv().ok());
+
+                    for write in std::iter::once(write).chain(queued) {
+                        match write {
+                            PeerDBWrite::BanList(latest) => ban_list = Some(latest),
+                            PeerDBWrite::Snapshot(latest) => snapshot = Some(latest),
+                            PeerDBWrite::Flush(sender) => flushes.push(sender),
+                        }
+                    }
+
+                    if let Some(ban_list) = ban_list {
+                        ban_list.save_to_disk(&dir);
+                    }
+
+                    if let Some(snapshot) = snapshot {
+                        snapshot.save_to_disk(&dir);
+                    }
+
+                    for sender in flushes {
+                        let _ = sender.send(());
+                    }
+                }
+            },
+            "peer_db_writer",
+        );
+
+        Self { sender }
+    }
+
+    /// Queues `ban_list` to be written, replacing any ban list not written yet.
+    pub fn save_ban_list(&self, ban_list: BanList) {
+        let _ = self.sender.send(PeerDBWrite::BanList(ban_list));
+    }
+
+    /// Queues `snapshot` to be written, replacing any snapshot not written yet.
+    pub fn save_snapshot(&self, snapshot: PeerDBSnapshot) {
+        let _ = self.sender.send(PeerDBWrite::Snapshot(snapshot));
+    }
+
+    /// Waits until everything queued before has been written.
+    pub async fn flush(&self) {
+        let (sender, receiver) = oneshot::channel();
+
+        if self.sender.send(PeerDBWrite::Flush(sender)).is_ok() {
+            let _ = receiver.await;
+        }
+    }
+}
+
+/// Returns the time until `unban_deadline`, a Unix timestamp in seconds, or `None` if it has passed.
+fn remaining_until(unban_deadline: u64) -> Option<Duration> {
+    let unban_deadline = UNIX_EPOCH.checked_add(Duration::from_secs(unban_deadline))?;
+    unban_deadline
+        .duration_since(SystemTime::now())
+        .ok()
+        .filter(|remaining| !remaining.is_zero())
+}
+
+fn load_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let bytes = std::fs::read(path)?;
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+fn save_json(dir: &Path, path: &Path, value: &impl Serialize) -> Result<()> {
+    let bytes = serde_json::to_vec(value)?;
+    std::fs::create_dir_all(dir)?;
+    // Write to a temporary file first so that a crash does not leave a truncated file behind.
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, bytes)?;
+    std::fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_round_trips_through_disk() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let snapshot = PeerDBSnapshot {
+            peers: vec![PeerRecord {
+                enr: "enr:-invalid".to_owned(),
+                multiaddrs: vec!["/ip4/10.0.0.1/tcp/9000".parse().expect("valid multiaddr")],
+                client: ClientKind::Lighthouse,
+                score: 12.5,
+                custody_subnets: vec![1, 5],
+                last_connected: 1_700_000_000,
+            }],
+        };
+
+        assert_eq!(PeerDBSnapshot::load_from_disk(dir.path()), None);
+
+        snapshot.save_to_disk(dir.path());
+
+        let loaded = PeerDBSnapshot::load_from_disk(dir.path()).expect("snapshot is written");
+
+        assert_eq!(loaded, snapshot);
+        assert_eq!(loaded.peers[0].enr(), None);
+    }
+
+    #[test]
+    fn ban_list_round_trips_through_disk() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("time is after the epoch")
+            .as_secs();
+        let ban_list = BanList {
+            peers: vec![
+                BannedPeerRecord {
+                    peer_id: PeerId::random().to_string(),
+                    ip_addresses: vec!["10.0.0.1".parse().unwrap()],
+                    unban_deadline: now + 3600,
+                },
+                BannedPeerRecord {
+                    peer_id: PeerId::random().to_string(),
+                    ip_addresses: vec![],
+                    unban_deadline: now - 1,
+                },
+            ],
+            manual_peers: vec![ManualBanRecord {
+                target: PeerId::random().to_string(),
+                unban_deadline: now + 60,
+            }],
+            manual_ip_ranges: vec![ManualBanRecord {
+                target: "10.0.0.0/24".to_owned(),
+                unban_deadline: now + 60,
+            }],
+        };
+
+        ban_list.save_to_disk(dir.path());
+
+        let loaded = BanList::load_from_disk(dir.path()).expect("ban list is written");
+
+        assert_eq!(loaded, ban_list);
+        assert!(loaded.peers[0].remaining().is_some());
+        assert_eq!(loaded.peers[1].remaining(), None);
+        assert!(loaded.manual_peers[0].peer_id().is_some());
+        assert_eq!(loaded.manual_peers[0].ip_range(), None);
+        assert!(loaded.manual_ip_ranges[0].ip_range().is_some());
+    }
+
+    #[tokio::test]
+    async fn writer_writes_the_latest_ban_list() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let (signal_tx, _signal_rx) = futures::channel::mpsc::channel(1);
+        let writer = PeerDBWriter::new(&TaskExecutor::new(signal_tx), dir.path().to_owned());
+        let ban_list = |peer_id: PeerId| BanList {
+            peers: vec![BannedPeerRecord {
+                peer_id: peer_id.to_string(),
+                ip_addresses: vec![],
+                unban_deadline: u64::MAX,
+            }],
+            ..BanList::default()
+        };
+        let latest = ban_list(PeerId::random());
+
+        writer.save_ban_list(ban_list(PeerId::random()));
+        writer.save_ban_list(latest.clone());
+        writer.flush().await;
+
+        assert_eq!(BanList::load_from_disk(dir.path()), Some(latest));
+    }
+}
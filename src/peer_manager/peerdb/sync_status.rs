@@ -3,6 +3,8 @@
 use serde::Serialize;
 use types::phase0::primitives::{Epoch, H256, Slot};
 
+use crate::rpc::StatusMessage;
+
 #[derive(Clone, Debug, Serialize)]
 /// The current sync status of the peer.
 pub enum SyncStatus {
@@ -41,6 +43,18 @@ impl SyncInfo {
     }
 }
 
+impl From<StatusMessage> for SyncInfo {
+    fn from(status: StatusMessage) -> Self {
+        Self {
+            head_slot: status.head_slot(),
+            head_root: status.head_root(),
+            finalized_epoch: status.finalized_epoch(),
+            finalized_root: status.finalized_root(),
+            earliest_available_slot: status.earliest_available_slot(),
+        }
+    }
+}
+
 impl std::cmp::PartialEq for SyncStatus {
     fn eq(&self, other: &Self) -> bool {
         matches!(
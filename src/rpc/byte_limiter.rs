@@ -0,0 +1,208 @@
+//! Byte quotas of the responses we send.
+//!
+//! The size of a response is only known once the inbound codec has encoded it, so its bytes are
+//! charged after it is written to the substream. Responses of a protocol are held back while the
+//! peer, or all peers together, are over the byte quota of the protocol.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::num::NonZeroU64;
+use std::sync::Arc;
+use std::task::Context;
+use std::time::{Duration, Instant};
+
+use libp2p::PeerId;
+use parking_lot::{Mutex, RwLock};
+use tokio::time::Interval;
+
+use super::Protocol;
+use super::config::ByteQuotaConfig;
+use super::rate_limiter::{Limiter, RateLimitedErr};
+
+/// Per peer and global byte limits of the responses of each protocol.
+pub(super) struct ByteLimiter {
+    /// Limiters of the bytes sent to each peer.
+    peer_limiters: HashMap<Protocol, Limiter<PeerId>>,
+    /// Limiters of the bytes sent to all peers together.
+    global_limiters: HashMap<Protocol, Limiter<()>>,
+    /// Multipliers of the per peer quotas of individual peers.
+    peer_multipliers: HashMap<PeerId, NonZeroU64>,
+    /// Interval to prune peers whose buckets are full.
+    prune_interval: Interval,
+    /// Creation time of the limiter.
+    init_time: Instant,
+}
+
+impl ByteLimiter {
+    pub fn new(config: ByteQuotaConfig) -> Result<Self, &'static str> {
+        let ByteQuotaConfig {
+            peer_quotas,
+            global_quotas,
+        } = config;
+
+        let peer_limiters = peer_quotas
+            .0
+            .into_iter()
+            .map(|(protocol, quota)| Ok((protocol, Limiter::from_quota(quota)?)))
+            .collect::<Result<_, &'static str>>()?;
+
+        let global_limiters = global_quotas
+            .0
+            .into_iter()
+            .map(|(protocol, quota)| Ok((protocol, Limiter::from_quota(quota)?)))
+            .collect::<Result<_, &'static str>>()?;
+
+        // check for peers to prune every 30 seconds, starting in 30 seconds
+        let prune_every = tokio::time::Duration::from_secs(30);
+        let prune_start = tokio::time::Instant::now()
+            .checked_add(prune_every)
+            .ok_or("prune time overflow")?;
+
+        Ok(Self {
+            peer_limiters,
+            global_limiters,
+            peer_multipliers: HashMap::new(),
+            prune_interval: tokio::time::interval_at(prune_start, prune_every),
+            init_time: Instant::now(),
+        })
+    }
+
+    /// Checks whether a response of `protocol` may be sent to `peer_id`. If the peer or all peers
+    /// together are over their quota, the time until the excess is replenished is returned.
+    pub fn check(&mut self, peer_id: &PeerId, protocol: Protocol) -> Result<(), Duration> {
+        let time_since_start = self.init_time.elapsed();
+        let mut wait_time = Duration::ZERO;
+
+        if let Some(limiter) = self.peer_limiters.get_mut(&protocol) {
+            if let Err(RateLimitedErr::TooSoon(wait)) = limiter.allows(time_since_start, peer_id, 0)
+            {
+                wait_time = wait_time.max(wait);
+            }
+        }
+
+        if let Some(limiter) = self.global_limiters.get_mut(&protocol) {
+            if let Err(RateLimitedErr::TooSoon(wait)) = limiter.allows(time_since_start, &(), 0) {
+                wait_time = wait_time.max(wait);
+            }
+        }
+
+        if wait_time.is_zero() {
+            Ok(())
+        } else {
+            Err(wait_time)
+        }
+    }
+
+    /// Charges `bytes` of a response of `protocol` sent to `peer_id`.
+    pub fn charge(&mut self, peer_id: &PeerId, protocol: Protocol, bytes: u64) {
+        let time_since_start = self.init_time.elapsed();
+
+        if let Some(limiter) = self.peer_limiters.get_mut(&protocol) {
+            let multiplier = self
+                .peer_multipliers
+                .get(peer_id)
+                .copied()
+                .unwrap_or(NonZeroU64::MIN);
+
+            limiter.charge(time_since_start, peer_id, bytes, multiplier);
+        }
+
+        if let Some(limiter) = self.global_limiters.get_mut(&protocol) {
+            limiter.charge(time_since_start, &(), bytes, NonZeroU64::MIN);
+        }
+    }
+
+    /// Multiplies the per peer quotas of `peer_id` by `multiplier`, or restores them if it is
+    /// `None`.
+    pub fn set_peer_multiplier(&mut self, peer_id: PeerId, multiplier: Option<NonZeroU64>) {
+        match multiplier {
+            Some(multiplier) if multiplier > NonZeroU64::MIN => {
+                self.peer_multipliers.insert(peer_id, multiplier);
+            }
+            _ => {
+                self.peer_multipliers.remove(&peer_id);
+            }
+        }
+    }
+
+    /// Prunes peers whose buckets are full, if it is time to do so.
+    pub fn poll_prune(&mut self, cx: &mut Context<'_>) {
+        while self.prune_interval.poll_tick(cx).is_ready() {
+            let time_since_start = self.init_time.elapsed();
+
+            for limiter in self.peer_limiters.values_mut() {
+                limiter.prune(time_since_start);
+            }
+
+            for limiter in self.global_limiters.values_mut() {
+                limiter.prune(time_since_start);
+            }
+        }
+    }
+}
+
+/// The byte limiter of our responses, if enabled. It is shared by the meters of all connections,
+/// so that a limiter enabled at runtime also charges the responses of existing connections.
+pub(super) type SharedByteLimiter = Arc<RwLock<Option<Arc<Mutex<ByteLimiter>>>>>;
+
+/// Charges the bytes of the responses encoded on the inbound substreams of a peer.
+#[derive(Clone)]
+pub struct ResponseByteMeter {
+    peer_id: PeerId,
+    limiter: SharedByteLimiter,
+}
+
+impl Debug for ResponseByteMeter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseByteMeter")
+            .field("peer_id", &self.peer_id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResponseByteMeter {
+    pub(super) fn new(peer_id: PeerId, limiter: SharedByteLimiter) -> Self {
+        Self { peer_id, limiter }
+    }
+
+    /// Charges `bytes` of an encoded response of `protocol`, if byte quotas are enabled.
+    pub fn record(&self, protocol: Protocol, bytes: u64) {
+        if let Some(limiter) = self.limiter.read().as_ref() {
+            limiter.lock().charge(&self.peer_id, protocol, bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::config::ByteQuotas;
+    use crate::rpc::rate_limiter::Quota;
+
+    #[tokio::test]
+    async fn peers_over_their_byte_quota_are_held_back() {
+        let quota = Quota::n_every(NonZeroU64::new(1000).unwrap(), 10);
+        let mut limiter = ByteLimiter::new(ByteQuotaConfig {
+            peer_quotas: ByteQuotas(HashMap::from([(Protocol::BlocksByRange, quota.clone())])),
+            global_quotas: ByteQuotas(HashMap::from([(Protocol::BlocksByRoot, quota)])),
+        })
+        .unwrap();
+
+        let peer_a = PeerId::random();
+        let peer_b = PeerId::random();
+
+        // A response larger than the quota is sent, but holds back the following ones.
+        assert_eq!(limiter.check(&peer_a, Protocol::BlocksByRange), Ok(()));
+        limiter.charge(&peer_a, Protocol::BlocksByRange, 5000);
+        assert!(limiter.check(&peer_a, Protocol::BlocksByRange).is_err());
+        assert_eq!(limiter.check(&peer_b, Protocol::BlocksByRange), Ok(()));
+
+        // Protocols without a quota are not limited.
+        limiter.charge(&peer_a, Protocol::Status, 5000);
+        assert_eq!(limiter.check(&peer_a, Protocol::Status), Ok(()));
+
+        // The global quota is shared by all peers.
+        limiter.charge(&peer_a, Protocol::BlocksByRoot, 5000);
+        assert!(limiter.check(&peer_b, Protocol::BlocksByRoot).is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::rpc::RequestType;
+use crate::rpc::byte_limiter::ResponseByteMeter;
 use crate::rpc::methods::*;
 use crate::rpc::protocol::{
     ERROR_TYPE_MAX, ERROR_TYPE_MIN, Encoding, ProtocolId, RPCError, SupportedProtocol,
@@ -52,6 +53,8 @@ pub struct SSZSnappyInboundCodec<P: Preset> {
     /// Maximum bytes that can be sent in one req/resp chunked responses.
     max_packet_size: usize,
     fork_context: Arc<ForkContext>,
+    /// Charges the bytes of encoded responses to the byte quotas of the peer.
+    byte_meter: Option<ResponseByteMeter>,
     phantom: PhantomData<P>,
 }
 
@@ -61,6 +64,7 @@ impl<P: Preset> SSZSnappyInboundCodec<P> {
         protocol: ProtocolId,
         max_packet_size: usize,
         fork_context: Arc<ForkContext>,
+        byte_meter: Option<ResponseByteMeter>,
     ) -> Self {
         let uvi_codec = Uvi::default();
         // this encoding only applies to ssz_snappy.
@@ -74,6 +78,7 @@ impl<P: Preset> SSZSnappyInboundCodec<P> {
             phantom: PhantomData,
             fork_context,
             max_packet_size,
+            byte_meter,
         }
     }
 
@@ -173,6 +178,10 @@ impl<P: Preset> Encoder<RpcResponse<P>> for SSZSnappyInboundCodec<P> {
         crate::common::metrics::inc_counter_by(&crate::metrics::RPC_SENT_BYTES, count);
         crate::common::metrics::inc_counter_by(&crate::metrics::RPC_TOTAL_BYTES, count);
 
+        if let Some(byte_meter) = self.byte_meter.as_ref() {
+            byte_meter.record(self.protocol.versioned_protocol.protocol(), count);
+        }
+
         result
     }
 }
@@ -1287,6 +1296,13 @@ mod tests {
         }
     }
 
+    fn lcurange_request() -> LightClientUpdatesByRangeRequest {
+        LightClientUpdatesByRangeRequest {
+            start_period: 0,
+            count: 10,
+        }
+    }
+
     fn bbroot_request_v1(config: &Config, phase: Phase) -> BlocksByRootRequest {
         BlocksByRootRequest::new_v1(config, phase, core::iter::once(H256::zero()))
     }
@@ -1351,6 +1367,7 @@ mod tests {
             snappy_protocol_id,
             config.max_payload_size,
             fork_context,
+            None,
         );
 
         snappy_inbound_codec.encode_response(message, &mut buf)?;
@@ -1443,6 +1460,7 @@ mod tests {
             protocol.clone(),
             config.max_payload_size,
             fork_context.clone(),
+            None,
         );
 
         let decoded = inbound_codec.decode(&mut buf).unwrap().unwrap_or_else(|| {
@@ -2219,6 +2237,7 @@ mod tests {
             RequestType::DataColumnsByRoot(dcbroot_request::<Mainnet>(&config)),
             RequestType::MetaData(MetadataRequest::new_v2()),
             RequestType::MetaData(MetadataRequest::new_v3()),
+            RequestType::LightClientUpdatesByRange(lcurange_request()),
         ];
         for req in requests.iter() {
             for fork_name in enum_iterator::all::<Phase>() {
@@ -1,12 +1,15 @@
 use super::{Protocol, rate_limiter::Quota};
+use libp2p::PeerId;
+use std::collections::HashMap;
 use std::num::NonZeroU64;
 use std::{
     fmt::{Debug, Display},
     str::FromStr,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use serde::{Deserialize, Serialize};
+use itertools::Itertools as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
 /// Auxiliary struct to aid on configuration parsing.
 ///
@@ -269,6 +272,274 @@ impl FromStr for RateLimiterConfig {
     }
 }
 
+/// Byte quotas of the responses we send, on top of the response count quotas of the inbound rate
+/// limiter. Response sizes are measured after encoding. Protocols without a quota are not limited
+/// by size.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteQuotaConfig {
+    /// Quotas of the bytes sent to each peer.
+    pub peer_quotas: ByteQuotas,
+    /// Quotas of the bytes sent to all peers together.
+    #[serde(default)]
+    pub global_quotas: ByteQuotas,
+}
+
+/// Parse byte quota configurations.
+///
+/// The expected format is the per peer [`ByteQuotas`], optionally followed by '|' and the global
+/// [`ByteQuotas`].
+impl FromStr for ByteQuotaConfig {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (peer_quotas, global_quotas) = s.split_once('|').unwrap_or((s, ""));
+
+        Ok(Self {
+            peer_quotas: peer_quotas.parse()?,
+            global_quotas: global_quotas.parse()?,
+        })
+    }
+}
+
+/// Byte quotas of individual protocols.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ByteQuotas(pub HashMap<Protocol, Quota>);
+
+/// Parse byte quotas. Protocol specified more than once use only the first given Quota.
+///
+/// The expected format is a ';' separated list of `protocol_name:bytes/time_in_seconds`.
+impl FromStr for ByteQuotas {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quotas = HashMap::new();
+
+        for proto_def in s.split(';').filter(|proto_def| !proto_def.is_empty()) {
+            let ProtocolQuota { protocol, quota } = proto_def.parse()?;
+            quotas.entry(protocol).or_insert(quota);
+        }
+
+        Ok(Self(quotas))
+    }
+}
+
+impl Display for ByteQuotas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quotas = self
+            .0
+            .iter()
+            .map(|(protocol, quota)| ProtocolQuota {
+                protocol: *protocol,
+                quota: quota.clone(),
+            })
+            .sorted_by_key(|quota| quota.protocol.as_ref().to_owned())
+            .join(";");
+
+        f.write_str(&quotas)
+    }
+}
+
+impl Serialize for ByteQuotas {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteQuotas {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Multipliers of the inbound and outbound rate limiter quotas of peer classes.
+///
+/// A peer belonging to several classes gets the largest of their multipliers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerQuotaMultipliers {
+    /// Multiplier of trusted peers.
+    pub trusted_peers: Option<NonZeroU64>,
+    /// Multiplier of peers whose metadata advertises custody of all custody groups.
+    pub full_custody_peers: Option<NonZeroU64>,
+    /// Multipliers of individual peers, keyed by their base58 encoded peer id.
+    #[serde(
+        serialize_with = "serialize_peer_multipliers",
+        deserialize_with = "deserialize_peer_multipliers",
+        default
+    )]
+    pub peers: HashMap<PeerId, NonZeroU64>,
+}
+
+fn serialize_peer_multipliers<S>(
+    peers: &HashMap<PeerId, NonZeroU64>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(
+        peers
+            .iter()
+            .map(|(peer_id, multiplier)| (peer_id.to_string(), multiplier)),
+    )
+}
+
+fn deserialize_peer_multipliers<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<PeerId, NonZeroU64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    HashMap::<String, NonZeroU64>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(peer_id, multiplier)| {
+            let peer_id = PeerId::from_str(&peer_id).map_err(|e| {
+                de::Error::custom(format!("Failed to deserialise peer id: {:?}", e))
+            })?;
+            Ok((peer_id, multiplier))
+        })
+        .collect()
+}
+
+impl PeerQuotaMultipliers {
+    /// Returns the multiplier of `peer_id`, or `None` if its quotas are not multiplied.
+    pub fn multiplier(
+        &self,
+        peer_id: &PeerId,
+        is_trusted: bool,
+        has_full_custody: bool,
+    ) -> Option<NonZeroU64> {
+        [
+            self.trusted_peers.filter(|_| is_trusted),
+            self.full_custody_peers.filter(|_| has_full_custody),
+            self.peers.get(peer_id).copied(),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+    }
+}
+
+/// Global limits of the inbound requests handed to the application and not answered yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InboundSchedulerConfig {
+    /// Maximum number of requests served at the same time across all peers.
+    pub max_concurrent_requests: usize,
+    /// Maximum number of `*ByRange` requests served at the same time across all peers.
+    pub max_concurrent_range_requests: usize,
+    /// Maximum time a request waits for a free slot before it is answered with `RateLimited`.
+    /// It should be below the first chunk timeout, after which the peer gives up on the request.
+    #[serde(default = "InboundSchedulerConfig::default_max_queue_time")]
+    pub max_queue_time: Duration,
+}
+
+impl InboundSchedulerConfig {
+    pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 256;
+    pub const DEFAULT_MAX_CONCURRENT_RANGE_REQUESTS: usize = 32;
+    pub const DEFAULT_MAX_QUEUE_TIME: Duration = Duration::from_secs(5);
+
+    const fn default_max_queue_time() -> Duration {
+        Self::DEFAULT_MAX_QUEUE_TIME
+    }
+}
+
+impl Default for InboundSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: Self::DEFAULT_MAX_CONCURRENT_REQUESTS,
+            max_concurrent_range_requests: Self::DEFAULT_MAX_CONCURRENT_RANGE_REQUESTS,
+            max_queue_time: Self::DEFAULT_MAX_QUEUE_TIME,
+        }
+    }
+}
+
+/// Time limits for receiving or sending the responses to a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseTimeouts {
+    /// Time allowed until the first response chunk.
+    pub first_chunk: Duration,
+    /// Time allowed between subsequent response chunks.
+    pub next_chunk: Duration,
+    /// Time allowed for the whole response stream, if limited.
+    pub stream: Option<Duration>,
+}
+
+impl ResponseTimeouts {
+    /// The same timeout for the first and every subsequent chunk, without a stream deadline.
+    pub const fn per_chunk(timeout: Duration) -> Self {
+        Self {
+            first_chunk: timeout,
+            next_chunk: timeout,
+            stream: None,
+        }
+    }
+
+    /// Returns the timeout of the next chunk, bounded by the stream deadline if there is one.
+    pub(super) fn next_chunk_timeout(&self, stream_started: Instant) -> Duration {
+        match self.stream {
+            Some(stream) => {
+                let remaining = stream.saturating_sub(stream_started.elapsed());
+                self.next_chunk.min(remaining)
+            }
+            None => self.next_chunk,
+        }
+    }
+
+    /// Returns the timeout of the first chunk, bounded by the stream deadline if there is one.
+    pub(super) fn first_chunk_timeout(&self) -> Duration {
+        self.stream
+            .map_or(self.first_chunk, |stream| self.first_chunk.min(stream))
+    }
+}
+
+/// Timeouts and limits of the RPC connection handler.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcHandlerConfig {
+    /// Response timeouts of protocols missing from `protocol_response_timeouts`.
+    pub response_timeouts: ResponseTimeouts,
+    /// Response timeouts of individual protocols.
+    pub protocol_response_timeouts: HashMap<Protocol, ResponseTimeouts>,
+    /// Maximum time given to the handler to perform shutdown operations.
+    pub shutdown_timeout: Duration,
+    /// Maximum number of simultaneous inbound substreams we keep for a peer.
+    pub max_inbound_substreams: usize,
+    /// Check that `BlocksByRange`, `BlobsByRoot` and `DataColumnsByRoot` responses match the
+    /// request. Mismatching responses are reported as `RPCError::InvalidData`.
+    pub validate_responses: bool,
+}
+
+impl RpcHandlerConfig {
+    pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(15);
+    pub const DEFAULT_MAX_INBOUND_SUBSTREAMS: usize = 32;
+
+    /// Returns the response timeouts of `protocol`.
+    pub fn response_timeouts(&self, protocol: Protocol) -> ResponseTimeouts {
+        self.protocol_response_timeouts
+            .get(&protocol)
+            .copied()
+            .unwrap_or(self.response_timeouts)
+    }
+}
+
+impl Default for RpcHandlerConfig {
+    fn default() -> Self {
+        Self {
+            response_timeouts: ResponseTimeouts::per_chunk(Self::DEFAULT_RESPONSE_TIMEOUT),
+            protocol_response_timeouts: HashMap::new(),
+            shutdown_timeout: Self::DEFAULT_SHUTDOWN_TIMEOUT,
+            max_inbound_substreams: Self::DEFAULT_MAX_INBOUND_SUBSTREAMS,
+            validate_responses: false,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,4 +555,98 @@ mod tests {
         };
         assert_eq!(quota.to_string().parse(), Ok(quota))
     }
+
+    #[test]
+    fn test_byte_quotas_keep_first_quota_of_protocol() {
+        let quotas: ByteQuotas = "beacon_blocks_by_range:10000000/10;beacon_blocks_by_range:1/1"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            quotas.0.get(&Protocol::BlocksByRange),
+            Some(&Quota::n_every(NonZeroU64::new(10_000_000).unwrap(), 10)),
+        );
+        assert_eq!(quotas.0.len(), 1);
+    }
+
+    #[test]
+    fn test_byte_quota_config_inverse() {
+        let config: ByteQuotaConfig =
+            "beacon_blocks_by_range:10000000/10;blob_sidecars_by_root:500000/1|data_column_sidecars_by_range:80000000/10"
+                .parse()
+                .unwrap();
+
+        assert_eq!(config.peer_quotas.0.len(), 2);
+        assert_eq!(config.global_quotas.0.len(), 1);
+
+        let peer_only: ByteQuotaConfig = "beacon_blocks_by_range:10000000/10".parse().unwrap();
+        assert!(peer_only.global_quotas.0.is_empty());
+
+        let json = serde_json::to_string(&config).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ByteQuotaConfig>(&json).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_peer_quota_multipliers_round_trip_through_json() {
+        let multipliers = PeerQuotaMultipliers {
+            trusted_peers: NonZeroU64::new(4),
+            full_custody_peers: None,
+            peers: HashMap::from([(PeerId::random(), NonZeroU64::new(2).unwrap())]),
+        };
+
+        let json = serde_json::to_string(&multipliers).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<PeerQuotaMultipliers>(&json).unwrap(),
+            multipliers,
+        );
+    }
+
+    #[test]
+    fn test_response_timeouts_are_bounded_by_stream_deadline() {
+        let mut config = RpcHandlerConfig::default();
+        let timeouts = ResponseTimeouts {
+            first_chunk: Duration::from_secs(30),
+            next_chunk: Duration::from_secs(10),
+            stream: Some(Duration::from_secs(20)),
+        };
+        config
+            .protocol_response_timeouts
+            .insert(Protocol::DataColumnsByRange, timeouts);
+
+        assert_eq!(
+            config.response_timeouts(Protocol::Status),
+            ResponseTimeouts::per_chunk(RpcHandlerConfig::DEFAULT_RESPONSE_TIMEOUT),
+        );
+
+        let timeouts = config.response_timeouts(Protocol::DataColumnsByRange);
+        let stream_started = Instant::now() - Duration::from_secs(15);
+
+        assert_eq!(timeouts.first_chunk_timeout(), Duration::from_secs(20));
+        assert!(timeouts.next_chunk_timeout(stream_started) <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_rpc_handler_config_round_trips_through_json() {
+        let mut config = RpcHandlerConfig::default();
+        config.protocol_response_timeouts.insert(
+            Protocol::DataColumnsByRange,
+            ResponseTimeouts {
+                first_chunk: Duration::from_secs(30),
+                next_chunk: Duration::from_secs(10),
+                stream: Some(Duration::from_secs(60)),
+            },
+        );
+
+        let json = serde_json::to_string(&config).unwrap();
+
+        assert!(json.contains("data_column_sidecars_by_range"));
+        assert_eq!(
+            serde_json::from_str::<RpcHandlerConfig>(&json).unwrap(),
+            config
+        );
+    }
 }
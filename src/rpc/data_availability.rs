@@ -0,0 +1,243 @@
+//! Checks inbound range requests against the data this node is able to serve.
+//!
+//! Requests that end before the locally registered `earliest_available_slot` or before the
+//! retention windows of blob and data column sidecars are answered with `ResourceUnavailable`
+//! without reaching the application. So are sidecar requests touching the part of the retention
+//! window the node is still backfilling, as it must serve the whole window. Other requests
+//! overlapping the available range are left to the application, which serves the part of the
+//! range it has.
+
+use helper_functions::misc;
+use types::{
+    config::Config as ChainConfig,
+    nonstandard::Phase,
+    phase0::{
+        consts::FAR_FUTURE_EPOCH,
+        primitives::{Epoch, Slot},
+    },
+    preset::Preset,
+};
+
+use super::RequestType;
+
+/// The range of slots this node can serve, as registered by the application.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DataAvailability {
+    /// The earliest slot for which the node has blocks and sidecars.
+    pub earliest_available_slot: Slot,
+    /// The current wall clock slot.
+    pub current_slot: Slot,
+}
+
+impl DataAvailability {
+    /// Returns the reason `request` cannot be served, or `None` if it can be served or is not a
+    /// range request.
+    pub fn unavailable_reason<P: Preset>(
+        &self,
+        config: &ChainConfig,
+        request: &RequestType<P>,
+    ) -> Option<&'static str> {
+        let (start_slot, count, retention) = match request {
+            RequestType::BlocksByRange(request) => (request.start_slot(), request.count(), None),
+            RequestType::BlobsByRange(request) => (
+                request.start_slot,
+                request.count,
+                Some((
+                    config.fork_epoch(Phase::Deneb),
+                    config.min_epochs_for_blob_sidecars_requests,
+                )),
+            ),
+            RequestType::DataColumnsByRange(request) => (
+                request.start_slot,
+                request.count,
+                Some((
+                    config.fork_epoch(Phase::Fulu),
+                    config.min_epochs_for_data_column_sidecars_requests,
+                )),
+            ),
+            _ => return None,
+        };
+
+        let end_slot = start_slot.saturating_add(count);
+
+        if let Some(window_start) = retention.and_then(|(fork_epoch, min_epochs)| {
+            self.retention_window_start::<P>(fork_epoch, min_epochs)
+        }) {
+            if end_slot <= window_start {
+                return Some("Requested range is outside the retention window");
+            }
+
+            if window_start < self.earliest_available_slot
+                && start_slot < self.earliest_available_slot
+            {
+                return Some("Requested range is in the retention window still being backfilled");
+            }
+        }
+
+        (end_slot <= self.earliest_available_slot)
+            .then_some("Requested range is before the earliest available slot")
+    }
+
+    /// Returns the first slot of the retention window of sidecars introduced at `fork_epoch` that
+    /// must be served for `min_epochs` epochs, or `None` if the fork is not scheduled.
+    fn retention_window_start<P: Preset>(
+        &self,
+        fork_epoch: Epoch,
+        min_epochs: u64,
+    ) -> Option<Slot> {
+        if fork_epoch == FAR_FUTURE_EPOCH {
+            return None;
+        }
+
+        let current_epoch = misc::compute_epoch_at_slot::<P>(self.current_slot);
+        let window_start_epoch = current_epoch.saturating_sub(min_epochs).max(fork_epoch);
+
+        Some(misc::compute_start_slot_at_epoch::<P>(window_start_epoch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::methods::{
+        BlobsByRangeRequest, DataColumnsByRangeRequest, OldBlocksByRangeRequest,
+    };
+    use ssz::ContiguousList;
+    use std::sync::Arc;
+    use types::preset::Mainnet;
+
+    #[test]
+    fn rejects_requests_outside_the_available_range() {
+        let config = ChainConfig::mainnet();
+        let deneb_slot = config
+            .fork_slot::<Mainnet>(Phase::Deneb)
+            .expect("Deneb is enabled on mainnet");
+        let retention_slots = config.min_epochs_for_blob_sidecars_requests * 32;
+        let current_slot = deneb_slot + retention_slots + 1024;
+        let availability = DataAvailability {
+            earliest_available_slot: deneb_slot + 500,
+            current_slot,
+        };
+
+        let blocks = |start_slot| {
+            RequestType::<Mainnet>::BlocksByRange(OldBlocksByRangeRequest::new(start_slot, 64, 1))
+        };
+        let blobs = |start_slot| {
+            RequestType::<Mainnet>::BlobsByRange(BlobsByRangeRequest {
+                start_slot,
+                count: 64,
+            })
+        };
+
+        assert!(
+            availability
+                .unavailable_reason(&config, &blocks(deneb_slot))
+                .is_some()
+        );
+        assert_eq!(
+            availability.unavailable_reason(&config, &blocks(deneb_slot + 500)),
+            None,
+        );
+        // Ranges overlapping the earliest available slot are served in part.
+        assert_eq!(
+            availability.unavailable_reason(&config, &blocks(deneb_slot + 450)),
+            None,
+        );
+
+        // The retention window starts 1024 slots after the Deneb fork slot, after the earliest
+        // available slot.
+        assert!(
+            availability
+                .unavailable_reason(&config, &blobs(deneb_slot + 1024 - 64))
+                .is_some()
+        );
+        assert_eq!(
+            availability.unavailable_reason(&config, &blobs(deneb_slot + 1023)),
+            None,
+        );
+        assert_eq!(
+            availability.unavailable_reason(&config, &blobs(current_slot - 64)),
+            None,
+        );
+        assert_eq!(
+            availability.unavailable_reason(&config, &RequestType::LightClientFinalityUpdate),
+            None,
+        );
+    }
+
+    #[test]
+    fn sidecars_of_unscheduled_forks_have_no_retention_window() {
+        let mut config = ChainConfig::mainnet();
+        config.fulu_fork_epoch = FAR_FUTURE_EPOCH;
+
+        let availability = DataAvailability {
+            earliest_available_slot: 0,
+            current_slot: 1_000_000,
+        };
+
+        let request = RequestType::<Mainnet>::DataColumnsByRange(DataColumnsByRangeRequest {
+            start_slot: 0,
+            count: 64,
+            columns: Arc::new(ContiguousList::default()),
+        });
+
+        assert_eq!(availability.unavailable_reason(&config, &request), None);
+
+        // Without a retention window, the earliest available slot still applies.
+        let availability = DataAvailability {
+            earliest_available_slot: 500,
+            current_slot: 1_000_000,
+        };
+
+        assert!(availability.unavailable_reason(&config, &request).is_some());
+    }
+
+    #[test]
+    fn rejects_sidecar_requests_in_the_window_being_backfilled() {
+        let config = ChainConfig::mainnet();
+        let deneb_slot = config
+            .fork_slot::<Mainnet>(Phase::Deneb)
+            .expect("Deneb is enabled on mainnet");
+        let retention_slots = config.min_epochs_for_blob_sidecars_requests * 32;
+        let window_start = deneb_slot + 1024;
+        let availability = DataAvailability {
+            earliest_available_slot: window_start + 512,
+            current_slot: window_start + retention_slots,
+        };
+
+        let blobs = |start_slot| {
+            RequestType::<Mainnet>::BlobsByRange(BlobsByRangeRequest {
+                start_slot,
+                count: 64,
+            })
+        };
+
+        assert!(
+            availability
+                .unavailable_reason(&config, &blobs(window_start))
+                .is_some()
+        );
+        // Ranges overlapping the earliest available slot cannot be served in full either.
+        assert!(
+            availability
+                .unavailable_reason(&config, &blobs(window_start + 480))
+                .is_some()
+        );
+        assert_eq!(
+            availability.unavailable_reason(&config, &blobs(window_start + 512)),
+            None,
+        );
+        // Blocks have no retention window and are served in part.
+        assert_eq!(
+            availability.unavailable_reason(
+                &config,
+                &RequestType::BlocksByRange(OldBlocksByRangeRequest::new(
+                    window_start + 480,
+                    64,
+                    1
+                )),
+            ),
+            None,
+        );
+    }
+}
@@ -1,9 +1,13 @@
 #![allow(clippy::type_complexity)]
 #![allow(clippy::cognitive_complexity)]
 
-use super::methods::{GoodbyeReason, RpcErrorResponse, RpcResponse};
+use super::config::{ResponseTimeouts, RpcHandlerConfig};
+use super::methods::{
+    GoodbyeReason, MAX_REQUEST_LIGHT_CLIENT_UPDATES, RpcErrorResponse, RpcResponse,
+};
 use super::outbound::OutboundRequestContainer;
 use super::protocol::{InboundOutput, Protocol, RPCError, RPCProtocol, RequestType};
+use super::response_validator::ResponseValidator;
 use super::{RPCReceived, RPCSend, ReqId};
 use crate::rpc::outbound::OutboundFramed;
 use crate::rpc::protocol::InboundFramed;
@@ -25,7 +29,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::{Duration, Instant},
+    time::Instant,
 };
 use tokio::time::{Sleep, sleep};
 use tokio_util::time::{DelayQueue, delay_queue};
@@ -35,15 +39,6 @@ use types::preset::Preset;
 /// The number of times to retry an outbound upgrade in the case of IO errors.
 const IO_ERROR_RETRIES: u8 = 3;
 
-/// Maximum time given to the handler to perform shutdown operations.
-const SHUTDOWN_TIMEOUT_SECS: u64 = 15;
-
-/// Maximum number of simultaneous inbound substreams we keep for this peer.
-const MAX_INBOUND_SUBSTREAMS: usize = 32;
-
-/// Timeout that will be used for inbound and outbound responses.
-const RESP_TIMEOUT: Duration = Duration::from_secs(10);
-
 /// Identifier of inbound and outbound substreams from the handler's perspective.
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct SubstreamId(usize);
@@ -112,6 +107,13 @@ where
     /// Current number of concurrent outbound substreams being opened.
     dial_negotiated: u32,
 
+    /// Requests whose outbound substream is being opened.
+    negotiating_requests: SmallVec<[(Id, Protocol); 4]>,
+
+    /// Requests cancelled while their outbound substream was being opened. The substream is
+    /// dropped once it is negotiated.
+    cancelled_negotiations: SmallVec<[Id; 4]>,
+
     /// Current inbound substreams awaiting processing.
     inbound_substreams: FnvHashMap<SubstreamId, InboundInfo<P>>,
 
@@ -143,6 +145,9 @@ where
     /// Fork specific info.
     fork_context: Arc<ForkContext>,
 
+    /// Timeouts and limits of the handler.
+    config: Arc<RpcHandlerConfig>,
+
     /// Waker, to be sure the handler gets polled when needed.
     waker: Option<std::task::Waker>,
 }
@@ -189,6 +194,12 @@ struct OutboundInfo<Id, P: Preset> {
     max_remaining_chunks: Option<u64>,
     /// `Id` as given by the application that sent the request.
     req_id: Id,
+    /// Time limits for the responses of the peer.
+    timeouts: ResponseTimeouts,
+    /// When the substream was established. Used to enforce the stream deadline.
+    stream_started: Instant,
+    /// Checks the responses against the request, if response validation is enabled.
+    validator: Option<ResponseValidator>,
 }
 
 /// State of an inbound substream connection.
@@ -226,6 +237,7 @@ where
     pub fn new(
         listen_protocol: SubstreamProtocol<RPCProtocol<P>, ()>,
         fork_context: Arc<ForkContext>,
+        config: Arc<RpcHandlerConfig>,
         peer_id: PeerId,
         connection_id: ConnectionId,
     ) -> Self {
@@ -236,6 +248,8 @@ where
             events_out: SmallVec::new(),
             dial_queue: SmallVec::new(),
             dial_negotiated: 0,
+            negotiating_requests: SmallVec::new(),
+            cancelled_negotiations: SmallVec::new(),
             inbound_substreams: FnvHashMap::default(),
             outbound_substreams: FnvHashMap::default(),
             inbound_substreams_delay: DelayQueue::new(),
@@ -246,6 +260,7 @@ where
             max_dial_negotiated: 8,
             outbound_io_error_retries: 0,
             fork_context,
+            config,
             waker: None,
         }
     }
@@ -277,9 +292,7 @@ where
                 self.dial_queue.push((id, RequestType::Goodbye(reason)));
             }
 
-            self.state = HandlerState::ShuttingDown(Box::pin(sleep(Duration::from_secs(
-                SHUTDOWN_TIMEOUT_SECS,
-            ))));
+            self.state = HandlerState::ShuttingDown(Box::pin(sleep(self.config.shutdown_timeout)));
         }
     }
 
@@ -359,6 +372,7 @@ where
             RPCSend::Request(id, req) => self.send_request(id, req),
             RPCSend::Response(inbound_id, response) => self.send_response(inbound_id, response),
             RPCSend::Shutdown(id, reason) => self.shutdown(Some((id, reason))),
+            RPCSend::CancelRequest(id) => self.cancel_request(id),
         }
         // In any case, we need the handler to process the event.
         if let Some(waker) = &self.waker {
@@ -540,9 +554,13 @@ where
                                     info.max_remaining_chunks.saturating_sub(1);
 
                                 // If this substream has not ended, we reset the timer.
-                                // Each chunk is allowed RESPONSE_TIMEOUT to be sent.
+                                // Each chunk is allowed the protocol's chunk timeout to be sent.
                                 if let Some(ref delay_key) = info.delay_key {
-                                    self.inbound_substreams_delay.reset(delay_key, RESP_TIMEOUT);
+                                    let timeout = self
+                                        .config
+                                        .response_timeouts(info.protocol)
+                                        .next_chunk_timeout(info.request_start_time);
+                                    self.inbound_substreams_delay.reset(delay_key, timeout);
                                 }
 
                                 // The stream may be currently idle. Attempt to process more
@@ -685,6 +703,31 @@ where
                     request,
                 } => match substream.poll_next_unpin(cx) {
                     Poll::Ready(Some(Ok(response))) => {
+                        if let (RpcResponse::Success(success), Some(validator)) =
+                            (&response, entry.get_mut().validator.as_mut())
+                        {
+                            if let Err(reason) = validator.validate(success) {
+                                debug!(
+                                    peer_id = %self.peer_id,
+                                    connection_id = %self.connection_id,
+                                    %reason,
+                                    "Received a response that does not match the request"
+                                );
+                                // drop the stream
+                                let delay_key = &entry.get().delay_key;
+                                self.outbound_substreams_delay.remove(delay_key);
+                                let outbound_err = HandlerErr::Outbound {
+                                    id: entry.get().req_id,
+                                    proto: entry.get().proto,
+                                    error: RPCError::InvalidData(reason),
+                                };
+                                entry.remove_entry();
+                                return Poll::Ready(ConnectionHandlerEvent::NotifyBehaviour(
+                                    HandlerEvent::Err(outbound_err),
+                                ));
+                            }
+                        }
+
                         if request.expect_exactly_one_response() || response.close_after() {
                             // either this is a single response request or this response closes the
                             // stream
@@ -708,8 +751,10 @@ where
                                         request,
                                     };
                                 substream_entry.max_remaining_chunks = Some(max_remaining_chunks);
-                                self.outbound_substreams_delay
-                                    .reset(delay_key, RESP_TIMEOUT);
+                                let timeout = substream_entry
+                                    .timeouts
+                                    .next_chunk_timeout(substream_entry.stream_started);
+                                self.outbound_substreams_delay.reset(delay_key, timeout);
                             }
                         }
 
@@ -831,6 +876,8 @@ where
             self.dial_negotiated += 1;
             let (id, req) = self.dial_queue.remove(0);
             self.dial_queue.shrink_to_fit();
+            self.negotiating_requests
+                .push((id, req.versioned_protocol().protocol()));
             return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                 protocol: SubstreamProtocol::new(
                     OutboundRequestContainer {
@@ -903,6 +950,51 @@ where
     Id: ReqId,
     P: Preset,
 {
+    /// Cancels the outbound request `id` if it is queued, being negotiated or awaiting responses
+    /// on this connection. Its substream is dropped and the request is reported as failed.
+    fn cancel_request(&mut self, id: Id) {
+        let proto = if let Some(position) = self
+            .dial_queue
+            .iter()
+            .position(|(queued_id, _)| *queued_id == id)
+        {
+            let (_, req) = self.dial_queue.remove(position);
+            req.versioned_protocol().protocol()
+        } else if let Some((_, proto)) = self
+            .negotiating_requests
+            .iter()
+            .find(|(negotiating_id, _)| *negotiating_id == id)
+            .filter(|_| !self.cancelled_negotiations.contains(&id))
+        {
+            let proto = *proto;
+            self.cancelled_negotiations.push(id);
+            proto
+        } else {
+            let Some(substream_id) = self
+                .outbound_substreams
+                .iter()
+                .find(|(_, info)| info.req_id == id)
+                .map(|(substream_id, _)| *substream_id)
+            else {
+                return;
+            };
+
+            let Some(info) = self.outbound_substreams.remove(&substream_id) else {
+                return;
+            };
+
+            self.outbound_substreams_delay.remove(&info.delay_key);
+            info.proto
+        };
+
+        self.events_out
+            .push(HandlerEvent::Err(HandlerErr::Outbound {
+                id,
+                proto,
+                error: RPCError::InternalError("Request was cancelled"),
+            }));
+    }
+
     fn on_fully_negotiated_inbound(&mut self, substream: InboundOutput<Stream, P>) {
         // only accept new peer requests when active
         if !matches!(self.state, HandlerState::Active) {
@@ -945,6 +1037,19 @@ where
                     return self.shutdown(None);
                 }
             }
+            RequestType::LightClientUpdatesByRange(request) => {
+                if request.count > MAX_REQUEST_LIGHT_CLIENT_UPDATES {
+                    self.events_out.push(HandlerEvent::Err(HandlerErr::Inbound {
+                        id: self.current_inbound_substream_id,
+                        proto: Protocol::LightClientUpdatesByRange,
+                        error: RPCError::InvalidData(format!(
+                            "requested exceeded limit. allowed: {}, requested: {}",
+                            MAX_REQUEST_LIGHT_CLIENT_UPDATES, request.count
+                        )),
+                    }));
+                    return self.shutdown(None);
+                }
+            }
             _ => {}
         };
 
@@ -953,11 +1058,15 @@ where
 
         // store requests that expect responses
         if max_responses > 0 {
-            if self.inbound_substreams.len() < MAX_INBOUND_SUBSTREAMS {
+            if self.inbound_substreams.len() < self.config.max_inbound_substreams {
+                let timeout = self
+                    .config
+                    .response_timeouts(req.versioned_protocol().protocol())
+                    .first_chunk_timeout();
                 // Store the stream and tag the output.
                 let delay_key = self
                     .inbound_substreams_delay
-                    .insert(self.current_inbound_substream_id, RESP_TIMEOUT);
+                    .insert(self.current_inbound_substream_id, timeout);
                 let awaiting_stream = InboundState::Idle(substream);
                 self.inbound_substreams.insert(
                     self.current_inbound_substream_id,
@@ -997,6 +1106,29 @@ where
         self.current_inbound_substream_id.0 += 1;
     }
 
+    /// Removes `id` from the requests being negotiated. Returns `true` if it was cancelled in the
+    /// meantime, in which case its failure has already been reported.
+    fn finish_negotiation(&mut self, id: Id) -> bool {
+        if let Some(position) = self
+            .negotiating_requests
+            .iter()
+            .position(|(negotiating_id, _)| *negotiating_id == id)
+        {
+            self.negotiating_requests.remove(position);
+        }
+
+        let Some(position) = self
+            .cancelled_negotiations
+            .iter()
+            .position(|cancelled_id| *cancelled_id == id)
+        else {
+            return false;
+        };
+
+        self.cancelled_negotiations.remove(position);
+        true
+    }
+
     fn on_fully_negotiated_outbound(
         &mut self,
         substream: OutboundFramed<Stream, P>,
@@ -1006,6 +1138,11 @@ where
         // Reset any io-retries counter.
         self.outbound_io_error_retries = 0;
 
+        if self.finish_negotiation(id) {
+            // The request was cancelled, dropping the substream closes it.
+            return;
+        }
+
         let proto = request.versioned_protocol().protocol();
 
         // accept outbound connections only if the handler is not deactivated
@@ -1030,10 +1167,17 @@ where
             } else {
                 Some(max_responses)
             };
+            let timeouts = self.config.response_timeouts(proto);
+            let validator = self
+                .config
+                .validate_responses
+                .then(|| ResponseValidator::new(&request))
+                .flatten();
             // new outbound request. Store the stream and tag the output.
-            let delay_key = self
-                .outbound_substreams_delay
-                .insert(self.current_outbound_substream_id, RESP_TIMEOUT);
+            let delay_key = self.outbound_substreams_delay.insert(
+                self.current_outbound_substream_id,
+                timeouts.first_chunk_timeout(),
+            );
             let awaiting_stream = OutboundSubstreamState::RequestPendingResponse {
                 substream: Box::new(substream),
                 request,
@@ -1048,6 +1192,9 @@ where
                         proto,
                         max_remaining_chunks,
                         req_id: id,
+                        timeouts,
+                        stream_started: Instant::now(),
+                        validator,
                     },
                 )
                 .is_some()
@@ -1070,6 +1217,10 @@ where
 
         let (id, req) = request_info;
 
+        if self.finish_negotiation(id) {
+            return;
+        }
+
         // map the error
         let error = match error {
             StreamUpgradeError::Timeout => RPCError::NegotiationTimeout,
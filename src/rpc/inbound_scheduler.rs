@@ -0,0 +1,508 @@
+//! Global scheduling of the inbound requests handed to the application.
+//!
+//! Every `(peer, protocol)` pair is a flow. Requests are released in weighted fair queuing order:
+//! a request costs the number of responses it may produce and flows of peers with a larger weight
+//! are served proportionally more often. Requests wait in their flow while the global limits on
+//! concurrently served requests are reached, so a single peer cannot occupy every slot by itself.
+//!
+//! `Goodbye`, `Ping`, `Status` and `MetaData` requests are cheap and keep connections alive, so they
+//! are handed to the application as soon as they arrive and do not count against the limits.
+//!
+//! Requests still waiting after `max_queue_time` are returned by
+//! [`InboundScheduler::poll_expired`], so that they are answered before the peer times out.
+
+use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU64;
+use std::task::{Context, Poll};
+
+use libp2p::PeerId;
+use libp2p::swarm::ConnectionId;
+use tokio_util::time::{DelayQueue, delay_queue};
+use types::preset::Preset;
+
+use super::config::InboundSchedulerConfig;
+use super::{InboundRequestId, Protocol, RequestType};
+
+/// Scale of the virtual time, to keep precision when dividing costs by peer weights.
+const COST_SCALE: u64 = 1024;
+
+/// A request waiting to be handed to the application.
+pub(super) struct ScheduledRequest<P: Preset> {
+    pub peer_id: PeerId,
+    pub connection_id: ConnectionId,
+    pub request_id: InboundRequestId,
+    pub request_type: RequestType<P>,
+    /// Virtual time at which the request is done being served.
+    finish_tag: u64,
+    /// Position of the request in arrival order, serving requests with equal tags first come first.
+    sequence: u64,
+    /// Key of the deadline of the request in `queue_deadlines`, if it waits in a flow.
+    deadline_key: Option<delay_queue::Key>,
+}
+
+struct Flow<P: Preset> {
+    queue: VecDeque<ScheduledRequest<P>>,
+    /// Finish tag of the last request enqueued in the flow.
+    last_finish_tag: u64,
+}
+
+pub(super) struct InboundScheduler<P: Preset> {
+    config: InboundSchedulerConfig,
+    flows: HashMap<(PeerId, Protocol), Flow<P>>,
+    /// Requests of protocols that are not scheduled, in arrival order.
+    unscheduled: VecDeque<ScheduledRequest<P>>,
+    /// Deadlines of the requests waiting in flows.
+    queue_deadlines: DelayQueue<(PeerId, Protocol)>,
+    /// Weights of peers served more often than others.
+    peer_weights: HashMap<PeerId, NonZeroU64>,
+    /// Finish tag of the last request handed to the application.
+    virtual_time: u64,
+    /// Number of requests enqueued so far.
+    sequence: u64,
+    /// Requests handed to the application that have not been answered yet.
+    active_requests: HashMap<InboundRequestId, (PeerId, Protocol)>,
+    /// Number of `active_requests` of range protocols.
+    active_range_requests: usize,
+}
+
+impl<P: Preset> InboundScheduler<P> {
+    pub fn new(config: InboundSchedulerConfig) -> Self {
+        Self {
+            config,
+            flows: HashMap::new(),
+            unscheduled: VecDeque::new(),
+            queue_deadlines: DelayQueue::new(),
+            peer_weights: HashMap::new(),
+            virtual_time: 0,
+            sequence: 0,
+            active_requests: HashMap::new(),
+            active_range_requests: 0,
+        }
+    }
+
+    /// Queues a request costing `cost` until it can be handed to the application.
+    pub fn enqueue(
+        &mut self,
+        peer_id: PeerId,
+        connection_id: ConnectionId,
+        request_id: InboundRequestId,
+        request_type: RequestType<P>,
+        cost: u64,
+    ) {
+        if !is_scheduled_protocol(request_type.protocol()) {
+            self.unscheduled.push_back(ScheduledRequest {
+                peer_id,
+                connection_id,
+                request_id,
+                request_type,
+                finish_tag: self.virtual_time,
+                sequence: self.sequence,
+                deadline_key: None,
+            });
+
+            self.sequence += 1;
+            return;
+        }
+
+        let weight = self
+            .peer_weights
+            .get(&peer_id)
+            .copied()
+            .unwrap_or(NonZeroU64::MIN);
+
+        let key = (peer_id, request_type.protocol());
+        let deadline_key = self.queue_deadlines.insert(key, self.config.max_queue_time);
+
+        let flow = self.flows.entry(key).or_insert_with(|| Flow {
+            queue: VecDeque::new(),
+            last_finish_tag: 0,
+        });
+
+        let start_tag = flow.last_finish_tag.max(self.virtual_time);
+        let finish_tag = start_tag.saturating_add(cost.saturating_mul(COST_SCALE) / weight);
+
+        flow.last_finish_tag = finish_tag;
+        flow.queue.push_back(ScheduledRequest {
+            peer_id,
+            connection_id,
+            request_id,
+            request_type,
+            finish_tag,
+            sequence: self.sequence,
+            deadline_key: Some(deadline_key),
+        });
+
+        self.sequence += 1;
+    }
+
+    /// Returns the next request to hand to the application, if any may be served.
+    pub fn next_request(&mut self) -> Option<ScheduledRequest<P>> {
+        if let Some(request) = self.unscheduled.pop_front() {
+            return Some(request);
+        }
+
+        if self.active_requests.len() >= self.config.max_concurrent_requests {
+            return None;
+        }
+
+        let range_requests_allowed =
+            self.active_range_requests < self.config.max_concurrent_range_requests;
+
+        let key = self
+            .flows
+            .iter()
+            .filter(|((_, protocol), _)| range_requests_allowed || !is_range_protocol(*protocol))
+            .filter_map(|(key, flow)| {
+                let request = flow.queue.front()?;
+                Some(((request.finish_tag, request.sequence), *key))
+            })
+            .min_by_key(|(tags, _)| *tags)
+            .map(|(_, key)| key)?;
+
+        let flow = self.flows.get_mut(&key)?;
+        let request = flow.queue.pop_front()?;
+
+        if flow.queue.is_empty() {
+            self.flows.remove(&key);
+        }
+
+        if let Some(deadline_key) = request.deadline_key {
+            self.queue_deadlines.remove(&deadline_key);
+        }
+
+        self.virtual_time = self.virtual_time.max(request.finish_tag);
+        self.active_requests.insert(request.request_id, key);
+
+        if is_range_protocol(key.1) {
+            self.active_range_requests += 1;
+        }
+
+        Some(request)
+    }
+
+    /// Informs the scheduler that a request was answered or failed. Requests that are still
+    /// queued are dropped.
+    pub fn request_completed(&mut self, request_id: &InboundRequestId) {
+        if let Some((_, protocol)) = self.active_requests.remove(request_id) {
+            if is_range_protocol(protocol) {
+                self.active_range_requests = self.active_range_requests.saturating_sub(1);
+            }
+            return;
+        }
+
+        self.flows.retain(|_, flow| {
+            flow.queue.retain(|request| {
+                if request.request_id != *request_id {
+                    return true;
+                }
+                if let Some(deadline_key) = request.deadline_key {
+                    self.queue_deadlines.remove(&deadline_key);
+                }
+                false
+            });
+            !flow.queue.is_empty()
+        });
+        self.unscheduled
+            .retain(|request| request.request_id != *request_id);
+    }
+
+    /// Removes and returns all requests that have not been handed to the application yet.
+    pub fn drain_queued(&mut self) -> Vec<ScheduledRequest<P>> {
+        let mut requests = self.unscheduled.drain(..).collect::<Vec<_>>();
+
+        requests.extend(self.flows.drain().flat_map(|(_, flow)| flow.queue));
+        self.queue_deadlines.clear();
+
+        requests
+    }
+
+    /// Removes and returns the next request that waited longer than `max_queue_time`.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<ScheduledRequest<P>> {
+        while let Poll::Ready(Some(expired)) = self.queue_deadlines.poll_expired(cx) {
+            let deadline_key = expired.key();
+            let key = expired.into_inner();
+
+            let Some(flow) = self.flows.get_mut(&key) else {
+                continue;
+            };
+
+            let Some(position) = flow
+                .queue
+                .iter()
+                .position(|request| request.deadline_key == Some(deadline_key))
+            else {
+                continue;
+            };
+
+            let request = flow.queue.remove(position);
+
+            if flow.queue.is_empty() {
+                self.flows.remove(&key);
+            }
+
+            if let Some(request) = request {
+                return Poll::Ready(request);
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Returns the number of queued requests of `peer_id` for `protocol`.
+    pub fn queued_requests(&self, peer_id: PeerId, protocol: Protocol) -> usize {
+        self.flows
+            .get(&(peer_id, protocol))
+            .map_or(0, |flow| flow.queue.len())
+    }
+
+    /// Serves `peer_id` `weight` times as often as other peers, or as often if it is `None`.
+    pub fn set_peer_weight(&mut self, peer_id: PeerId, weight: Option<NonZeroU64>) {
+        match weight {
+            Some(weight) if weight > NonZeroU64::MIN => {
+                self.peer_weights.insert(peer_id, weight);
+            }
+            _ => {
+                self.peer_weights.remove(&peer_id);
+            }
+        }
+    }
+
+    /// Drops the queued requests of a disconnected peer and frees the slots of its requests
+    /// handed to the application, as they can no longer be answered.
+    pub fn peer_disconnected(&mut self, peer_id: PeerId) {
+        self.flows.retain(|(flow_peer_id, _), flow| {
+            if *flow_peer_id != peer_id {
+                return true;
+            }
+            for deadline_key in flow.queue.iter().filter_map(|request| request.deadline_key) {
+                self.queue_deadlines.remove(&deadline_key);
+            }
+            false
+        });
+        self.unscheduled
+            .retain(|request| request.peer_id != peer_id);
+        self.peer_weights.remove(&peer_id);
+
+        let mut freed_range_requests = 0;
+
+        self.active_requests
+            .retain(|_, (request_peer_id, protocol)| {
+                let freed = *request_peer_id == peer_id;
+                if freed && is_range_protocol(*protocol) {
+                    freed_range_requests += 1;
+                }
+                !freed
+            });
+
+        self.active_range_requests = self
+            .active_range_requests
+            .saturating_sub(freed_range_requests);
+    }
+}
+
+/// Returns `false` for protocols whose requests are handed to the application immediately.
+///
+/// `Goodbye` is never answered, so it would hold a slot until the peer disconnects.
+fn is_scheduled_protocol(protocol: Protocol) -> bool {
+    !matches!(
+        protocol,
+        Protocol::Goodbye | Protocol::Ping | Protocol::Status | Protocol::MetaData
+    )
+}
+
+fn is_range_protocol(protocol: Protocol) -> bool {
+    matches!(
+        protocol,
+        Protocol::BlocksByRange
+            | Protocol::BlobsByRange
+            | Protocol::DataColumnsByRange
+            | Protocol::LightClientUpdatesByRange
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::Ping;
+    use crate::rpc::methods::{GoodbyeReason, OldBlocksByRangeRequest};
+    use std::time::Duration;
+    use types::preset::Mainnet;
+
+    fn request_id(substream_id: usize) -> InboundRequestId {
+        InboundRequestId::new_unchecked(0, substream_id)
+    }
+
+    fn blocks_by_range() -> RequestType<Mainnet> {
+        RequestType::BlocksByRange(OldBlocksByRangeRequest::new(0, 64, 1))
+    }
+
+    #[tokio::test]
+    async fn range_requests_are_capped_and_shared_between_peers() {
+        let mut scheduler = InboundScheduler::<Mainnet>::new(InboundSchedulerConfig {
+            max_concurrent_requests: 8,
+            max_concurrent_range_requests: 2,
+            ..Default::default()
+        });
+
+        let aggressive_peer = PeerId::random();
+        let other_peer = PeerId::random();
+        let connection_id = ConnectionId::new_unchecked(0);
+
+        for substream_id in 0..4 {
+            scheduler.enqueue(
+                aggressive_peer,
+                connection_id,
+                request_id(substream_id),
+                blocks_by_range(),
+                64,
+            );
+        }
+
+        scheduler.enqueue(
+            other_peer,
+            connection_id,
+            request_id(4),
+            blocks_by_range(),
+            64,
+        );
+        scheduler.enqueue(
+            other_peer,
+            connection_id,
+            request_id(5),
+            RequestType::Ping(Ping { data: 0 }),
+            1,
+        );
+
+        let first = scheduler.next_request().expect("a request is ready");
+        let second = scheduler.next_request().expect("a request is ready");
+        let third = scheduler.next_request().expect("a request is ready");
+
+        // The cheap request goes first, then both peers get a range request before the
+        // aggressive peer gets a second one.
+        assert_eq!(first.request_id, request_id(5));
+        assert_eq!(
+            [second.peer_id, third.peer_id],
+            [aggressive_peer, other_peer]
+        );
+        // No more range requests are served once the cap is reached.
+        assert!(scheduler.next_request().is_none());
+        assert_eq!(
+            scheduler.queued_requests(aggressive_peer, Protocol::BlocksByRange),
+            3
+        );
+
+        scheduler.request_completed(&second.request_id);
+
+        let fourth = scheduler.next_request().expect("a range slot is free");
+        assert_eq!(fourth.peer_id, aggressive_peer);
+    }
+
+    #[tokio::test]
+    async fn unanswered_goodbyes_do_not_hold_slots() {
+        let mut scheduler = InboundScheduler::<Mainnet>::new(InboundSchedulerConfig {
+            max_concurrent_requests: 1,
+            max_concurrent_range_requests: 1,
+            ..Default::default()
+        });
+
+        let peer_id = PeerId::random();
+        let connection_id = ConnectionId::new_unchecked(0);
+
+        scheduler.enqueue(
+            peer_id,
+            connection_id,
+            request_id(0),
+            RequestType::Goodbye(GoodbyeReason::ClientShutdown),
+            1,
+        );
+
+        let goodbye = scheduler.next_request().expect("goodbye is not scheduled");
+        assert_eq!(goodbye.request_id, request_id(0));
+
+        // The goodbye is never answered, yet the only slot is still free.
+        scheduler.enqueue(peer_id, connection_id, request_id(1), blocks_by_range(), 64);
+
+        let range_request = scheduler.next_request().expect("a slot is free");
+        assert_eq!(range_request.request_id, request_id(1));
+
+        // Pings are answered even while every slot is taken.
+        scheduler.enqueue(
+            peer_id,
+            connection_id,
+            request_id(2),
+            RequestType::Ping(Ping { data: 0 }),
+            1,
+        );
+
+        let ping = scheduler.next_request().expect("pings are not scheduled");
+        assert_eq!(ping.request_id, request_id(2));
+        assert!(scheduler.next_request().is_none());
+    }
+
+    #[tokio::test]
+    async fn disconnected_peers_free_their_slots() {
+        let mut scheduler = InboundScheduler::<Mainnet>::new(InboundSchedulerConfig {
+            max_concurrent_requests: 1,
+            max_concurrent_range_requests: 1,
+            ..Default::default()
+        });
+
+        let disconnected_peer = PeerId::random();
+        let other_peer = PeerId::random();
+        let connection_id = ConnectionId::new_unchecked(0);
+
+        scheduler.enqueue(
+            disconnected_peer,
+            connection_id,
+            request_id(0),
+            blocks_by_range(),
+            64,
+        );
+        scheduler.enqueue(
+            other_peer,
+            connection_id,
+            request_id(1),
+            blocks_by_range(),
+            64,
+        );
+
+        assert!(scheduler.next_request().is_some());
+        assert!(scheduler.next_request().is_none());
+
+        scheduler.peer_disconnected(disconnected_peer);
+
+        let request = scheduler.next_request().expect("the slot was freed");
+        assert_eq!(request.peer_id, other_peer);
+    }
+
+    #[tokio::test]
+    async fn requests_queued_too_long_expire() {
+        let mut scheduler = InboundScheduler::<Mainnet>::new(InboundSchedulerConfig {
+            max_concurrent_requests: 1,
+            max_concurrent_range_requests: 1,
+            max_queue_time: Duration::from_millis(10),
+        });
+
+        let peer_id = PeerId::random();
+        let connection_id = ConnectionId::new_unchecked(0);
+
+        scheduler.enqueue(peer_id, connection_id, request_id(0), blocks_by_range(), 64);
+        scheduler.enqueue(peer_id, connection_id, request_id(1), blocks_by_range(), 64);
+
+        assert!(scheduler.next_request().is_some());
+        assert!(scheduler.next_request().is_none());
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // Only the request still waiting for a slot expires.
+        let expired = futures::future::poll_fn(|cx| scheduler.poll_expired(cx)).await;
+        assert_eq!(expired.request_id, request_id(1));
+        assert_eq!(
+            scheduler.queued_requests(peer_id, Protocol::BlocksByRange),
+            0
+        );
+
+        let still_pending = futures::future::poll_fn(|cx| Poll::Ready(scheduler.poll_expired(cx)));
+        assert!(still_pending.await.is_pending());
+    }
+}
@@ -28,7 +28,8 @@ use types::{
     traits::SignedBeaconBlock as _,
 };
 
-const MAX_REQUEST_LIGHT_CLIENT_UPDATES: u64 = 128;
+/// Maximum number of light client updates in a single `LightClientUpdatesByRange` request.
+pub const MAX_REQUEST_LIGHT_CLIENT_UPDATES: u64 = 128;
 
 /// Maximum length of error message.
 pub type MaxErrorLen = U256;
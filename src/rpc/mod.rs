@@ -12,12 +12,15 @@ use libp2p::swarm::{
     handler::ConnectionHandler,
 };
 use libp2p::swarm::{ConnectionClosed, FromSwarm, SubstreamProtocol, THandlerInEvent};
+use parking_lot::RwLock;
 use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::num::NonZeroU64;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use std_ext::ArcExt as _;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 use types::{config::Config as ChainConfig, preset::Preset};
 
 use crate::types::ForkContext;
@@ -28,11 +31,18 @@ pub(crate) use methods::{
 };
 pub use protocol::RequestType;
 
-use self::config::{InboundRateLimiterConfig, OutboundRateLimiterConfig};
+use self::byte_limiter::{ResponseByteMeter, SharedByteLimiter};
+use self::config::{
+    ByteQuotaConfig, InboundRateLimiterConfig, InboundSchedulerConfig, OutboundRateLimiterConfig,
+    RpcHandlerConfig,
+};
 use self::protocol::RPCProtocol;
 use self::self_limiter::SelfRateLimiter;
+use crate::rpc::inbound_scheduler::{InboundScheduler, ScheduledRequest};
+use crate::rpc::rate_limiter::RPCRateLimiter;
 use crate::rpc::rate_limiter::RateLimiterItem;
 use crate::rpc::response_limiter::ResponseLimiter;
+pub use data_availability::DataAvailability;
 pub use handler::SubstreamId;
 pub use methods::{
     BlobsByRangeRequest, BlobsByRootRequest, BlocksByRangeRequest, BlocksByRootRequest,
@@ -40,23 +50,28 @@ pub use methods::{
     StatusMessage, StatusMessageV1, StatusMessageV2,
 };
 pub use protocol::{Protocol, RPCError};
+pub use self_limiter::RequestPriority;
 
+mod byte_limiter;
 pub(crate) mod codec;
 pub mod config;
+mod data_availability;
 mod handler;
+mod inbound_scheduler;
 pub mod methods;
 mod outbound;
 mod protocol;
 mod rate_limiter;
 mod response_limiter;
+mod response_validator;
 mod self_limiter;
 
 // Maximum number of concurrent requests per protocol ID that a client may issue.
 const MAX_CONCURRENT_REQUESTS: usize = 2;
 
 /// Composite trait for a request id.
-pub trait ReqId: Send + 'static + std::fmt::Debug + Copy + Clone {}
-impl<T> ReqId for T where T: Send + 'static + std::fmt::Debug + Copy + Clone {}
+pub trait ReqId: Send + 'static + std::fmt::Debug + Copy + Clone + PartialEq {}
+impl<T> ReqId for T where T: Send + 'static + std::fmt::Debug + Copy + Clone + PartialEq {}
 
 /// RPC events sent from the application.
 #[derive(Debug, Clone)]
@@ -74,6 +89,8 @@ pub enum RPCSend<Id, P: Preset> {
     Response(SubstreamId, RpcResponse<P>),
     /// Application has requested to terminate the connection with a goodbye message.
     Shutdown(Id, GoodbyeReason),
+    /// Application has cancelled a request. Its substream is dropped if it was already opened.
+    CancelRequest(Id),
 }
 
 /// RPC events received from outside the application.
@@ -131,6 +148,7 @@ impl<P: Preset, Id: std::fmt::Debug> std::fmt::Display for RPCSend<Id, P> {
             RPCSend::Request(id, req) => write!(f, "RPC Request(id: {:?}, {})", id, req),
             RPCSend::Response(id, res) => write!(f, "RPC Response(id: {:?}, {})", id, res),
             RPCSend::Shutdown(_id, reason) => write!(f, "Sending Goodbye: {}", reason),
+            RPCSend::CancelRequest(id) => write!(f, "Cancelling RPC Request(id: {:?})", id),
         }
     }
 }
@@ -154,16 +172,33 @@ pub struct RPC<Id: ReqId, P: Preset> {
     chain_config: Arc<ChainConfig>,
     /// Rate limiter for our responses.
     response_limiter: Option<ResponseLimiter<P>>,
+    /// Byte limiter of the response limiter, shared with the inbound codecs of all connections.
+    response_byte_limiter: SharedByteLimiter,
+    /// Byte quotas of our responses, applied whenever the response limiter is enabled.
+    inbound_byte_quota_config: Option<ByteQuotaConfig>,
+    /// Quota multipliers of connected peers, applied whenever the response limiter is enabled.
+    peer_quota_multipliers: HashMap<PeerId, NonZeroU64>,
     /// Rate limiter for our own requests.
     outbound_request_limiter: SelfRateLimiter<Id, P>,
     /// Active inbound requests that are awaiting a response.
     active_inbound_requests: HashMap<InboundRequestId, ActiveInboundRequest<P>>,
+    /// Inbound requests waiting to be handed to the application.
+    inbound_scheduler: InboundScheduler<P>,
+    /// The established connections of each connected peer, latest last. Failures of requests that
+    /// never reached a connection handler are reported on the latest one.
+    connections: HashMap<PeerId, Vec<ConnectionId>>,
     /// Queue of events to be processed.
     events: Vec<BehaviourAction<Id, P>>,
     fork_context: Arc<ForkContext>,
     enable_light_client_server: bool,
+    /// Timeouts and limits shared by the handlers of all connections.
+    handler_config: Arc<RpcHandlerConfig>,
+    /// The range of slots we can serve. Range requests are not checked until it is registered.
+    data_availability: Option<DataAvailability>,
     /// A sequential counter indicating when data gets modified.
     seq_number: u64,
+    /// Whether new inbound requests are rejected because the node is shutting down.
+    shutting_down: bool,
 }
 
 impl<Id: ReqId, P: Preset> RPC<Id, P> {
@@ -172,15 +207,36 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
         fork_context: Arc<ForkContext>,
         enable_light_client_server: bool,
         inbound_rate_limiter_config: Option<InboundRateLimiterConfig>,
+        inbound_byte_quota_config: Option<ByteQuotaConfig>,
         outbound_rate_limiter_config: Option<OutboundRateLimiterConfig>,
+        handler_config: RpcHandlerConfig,
+        inbound_scheduler_config: InboundSchedulerConfig,
         seq_number: u64,
     ) -> Self {
+        if inbound_rate_limiter_config.is_none() && inbound_byte_quota_config.is_some() {
+            warn!("Inbound byte quotas are ignored until the inbound rate limiter is enabled");
+        }
+
         let response_limiter = inbound_rate_limiter_config.map(|config| {
-            debug!(?config, "Using response rate limiting params");
-            ResponseLimiter::new(config, fork_context.clone())
-                .expect("Inbound limiter configuration parameters are valid")
+            debug!(
+                ?config,
+                ?inbound_byte_quota_config,
+                "Using response rate limiting params"
+            );
+            ResponseLimiter::new(
+                config,
+                inbound_byte_quota_config.clone(),
+                fork_context.clone(),
+            )
+            .expect("Inbound limiter configuration parameters are valid")
         });
 
+        let response_byte_limiter = Arc::new(RwLock::new(
+            response_limiter
+                .as_ref()
+                .and_then(ResponseLimiter::byte_limiter),
+        ));
+
         let outbound_request_limiter: SelfRateLimiter<Id, P> =
             SelfRateLimiter::new(outbound_rate_limiter_config, fork_context.clone())
                 .expect("Outbound limiter configuration parameters are valid");
@@ -188,12 +244,20 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
         RPC {
             chain_config,
             response_limiter,
+            response_byte_limiter,
+            inbound_byte_quota_config,
+            peer_quota_multipliers: HashMap::new(),
             outbound_request_limiter,
             active_inbound_requests: HashMap::new(),
+            inbound_scheduler: InboundScheduler::new(inbound_scheduler_config),
+            connections: HashMap::new(),
             events: Vec::new(),
             fork_context,
             enable_light_client_server,
+            handler_config: Arc::new(handler_config),
+            data_availability: None,
             seq_number,
+            shutting_down: false,
         }
     }
 
@@ -226,6 +290,8 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
                     peer_disconnected,
                 },
             );
+        } else {
+            self.inbound_scheduler.request_completed(&request_id);
         }
 
         if peer_disconnected {
@@ -274,10 +340,29 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
     ///
     /// The peer must be connected for this to succeed.
     pub fn send_request(&mut self, peer_id: PeerId, request_id: Id, req: RequestType<P>) {
-        match self
-            .outbound_request_limiter
-            .allows(peer_id, request_id, req)
-        {
+        self.send_request_with_priority(peer_id, request_id, req, RequestPriority::Normal, None)
+    }
+
+    /// Submits an RPC request with a priority. If the request is self rate limited, it is sent
+    /// ahead of queued requests of lower priority. If it is still queued after `max_queue_time`,
+    /// it fails with `RPCError::InternalError`.
+    ///
+    /// The peer must be connected for this to succeed.
+    pub fn send_request_with_priority(
+        &mut self,
+        peer_id: PeerId,
+        request_id: Id,
+        req: RequestType<P>,
+        priority: RequestPriority,
+        max_queue_time: Option<Duration>,
+    ) {
+        match self.outbound_request_limiter.allows(
+            peer_id,
+            request_id,
+            req,
+            priority,
+            max_queue_time,
+        ) {
             Ok(event) => self.events.push(BehaviourAction::NotifyHandler {
                 peer_id,
                 handler: NotifyHandler::Any,
@@ -289,6 +374,51 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
         }
     }
 
+    /// Cancels a request. Requests that have not been handed to a connection handler yet are
+    /// dropped without a failure being reported. Requests already sent to the peer have their
+    /// substream dropped and are reported as failed with `RPCError::InternalError`, which releases
+    /// their outbound rate limiter slot.
+    pub fn cancel_request(&mut self, peer_id: PeerId, request_id: Id) {
+        if self
+            .outbound_request_limiter
+            .cancel_request(peer_id, request_id)
+        {
+            return;
+        }
+
+        let position = self.events.iter().position(|event| {
+            matches!(
+                event,
+                ToSwarm::NotifyHandler {
+                    peer_id: p,
+                    event: RPCSend::Request(id, _),
+                    ..
+                } if *p == peer_id && *id == request_id
+            )
+        });
+
+        if let Some(position) = position {
+            if let ToSwarm::NotifyHandler {
+                event: RPCSend::Request(_, req),
+                ..
+            } = self.events.remove(position)
+            {
+                self.outbound_request_limiter
+                    .request_completed(&peer_id, req.protocol());
+            }
+            return;
+        }
+
+        // The request was handed to one of the connections of the peer.
+        for connection_id in self.connections.get(&peer_id).into_iter().flatten() {
+            self.events.push(ToSwarm::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(*connection_id),
+                event: RPCSend::CancelRequest(request_id),
+            });
+        }
+    }
+
     /// Application wishes to disconnect from this peer by sending a Goodbye message. This
     /// gracefully terminates the RPC behaviour with a goodbye message.
     pub fn shutdown(&mut self, peer_id: PeerId, id: Id, reason: GoodbyeReason) {
@@ -299,10 +429,91 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
         });
     }
 
+    /// Replaces the quotas of the inbound and outbound rate limiters. A `None` configuration
+    /// leaves its limiter unchanged. Per-peer state is kept for protocols whose quotas are
+    /// replenished over the same period and reset for the others.
+    ///
+    /// When the inbound limiter is first enabled, the configured byte quotas and the quota
+    /// multipliers of connected peers are applied to it.
+    ///
+    /// If a configuration is invalid, an error is returned and neither limiter is changed.
+    pub fn update_rate_limits(
+        &mut self,
+        inbound_rate_limiter_config: Option<InboundRateLimiterConfig>,
+        outbound_rate_limiter_config: Option<OutboundRateLimiterConfig>,
+    ) -> Result<(), &'static str> {
+        debug!(
+            ?inbound_rate_limiter_config,
+            ?outbound_rate_limiter_config,
+            "Updating rate limiting params"
+        );
+
+        // Build both limiters before applying either of them.
+        let mut inbound_limiter = inbound_rate_limiter_config
+            .map(|config| RPCRateLimiter::new_with_config(config.0, self.fork_context.clone()))
+            .transpose()?;
+
+        let outbound_limiter = outbound_rate_limiter_config
+            .map(|config| RPCRateLimiter::new_with_config(config.0, self.fork_context.clone()))
+            .transpose()?;
+
+        let new_response_limiter = inbound_limiter
+            .take_if(|_| self.response_limiter.is_none())
+            .map(|limiter| {
+                ResponseLimiter::with_limiter(limiter, self.inbound_byte_quota_config.clone())
+            })
+            .transpose()?;
+
+        if let Some(mut response_limiter) = new_response_limiter {
+            for (peer_id, multiplier) in &self.peer_quota_multipliers {
+                response_limiter.set_peer_multiplier(*peer_id, Some(*multiplier));
+            }
+
+            *self.response_byte_limiter.write() = response_limiter.byte_limiter();
+            self.response_limiter = Some(response_limiter);
+        }
+
+        if let (Some(limiter), Some(response_limiter)) =
+            (inbound_limiter, self.response_limiter.as_mut())
+        {
+            response_limiter.update_limiter(limiter);
+        }
+
+        if let Some(limiter) = outbound_limiter {
+            self.outbound_request_limiter.update_limiter(limiter);
+        }
+
+        Ok(())
+    }
+
+    /// Multiplies the inbound and outbound rate limiter quotas of `peer_id` by `multiplier`, or
+    /// restores them if it is `None`. The multiplier is reset when the peer disconnects.
+    pub fn set_peer_quota_multiplier(&mut self, peer_id: PeerId, multiplier: Option<NonZeroU64>) {
+        match multiplier {
+            Some(multiplier) => self.peer_quota_multipliers.insert(peer_id, multiplier),
+            None => self.peer_quota_multipliers.remove(&peer_id),
+        };
+
+        if let Some(limiter) = self.response_limiter.as_mut() {
+            limiter.set_peer_multiplier(peer_id, multiplier);
+        }
+
+        self.outbound_request_limiter
+            .set_peer_multiplier(peer_id, multiplier);
+
+        self.inbound_scheduler.set_peer_weight(peer_id, multiplier);
+    }
+
     pub fn update_seq_number(&mut self, seq_number: u64) {
         self.seq_number = seq_number
     }
 
+    /// Registers the range of slots we can serve. Inbound range requests outside of it are
+    /// answered with `ResourceUnavailable` without reaching the application.
+    pub fn update_data_availability(&mut self, data_availability: DataAvailability) {
+        self.data_availability = Some(data_availability);
+    }
+
     /// Send a Ping request to the destination `PeerId` via `ConnectionId`.
     pub fn ping(&mut self, peer_id: PeerId, id: Id) {
         let ping = Ping {
@@ -311,6 +522,118 @@ impl<Id: ReqId, P: Preset> RPC<Id, P> {
         trace!(%peer_id, "Sending Ping");
         self.send_request(peer_id, id, RequestType::Ping(ping));
     }
+
+    /// Hands the inbound requests released by the scheduler to the application.
+    fn dispatch_inbound_requests(&mut self) {
+        while let Some(ScheduledRequest {
+            peer_id,
+            connection_id,
+            request_id,
+            request_type,
+            ..
+        }) = self.inbound_scheduler.next_request()
+        {
+            // Goodbye requests are never answered.
+            if !matches!(request_type, RequestType::Goodbye(_)) {
+                self.active_inbound_requests.insert(
+                    request_id,
+                    ActiveInboundRequest {
+                        peer_id,
+                        request_type: request_type.clone(),
+                        peer_disconnected: false,
+                    },
+                );
+            }
+
+            // If we received a Ping, we queue a Pong response.
+            if let RequestType::Ping(_) = request_type {
+                trace!(connection_id = %connection_id, %peer_id, "Received Ping, queueing Pong");
+
+                self.send_response(
+                    request_id,
+                    RpcResponse::Success(RpcSuccessResponse::Pong(Ping {
+                        data: self.seq_number,
+                    })),
+                )
+                .expect("Request to exist");
+            }
+
+            self.events.push(ToSwarm::GenerateEvent(RPCMessage {
+                peer_id,
+                connection_id,
+                message: Ok(RPCReceived::Request(request_id, request_type)),
+            }));
+        }
+    }
+
+    /// Rejects new inbound requests and the requests that have not been handed to the application
+    /// yet with a `ServerError`. Requests already handed to the application can still be answered.
+    ///
+    /// Returns the number of rejected queued requests.
+    pub fn stop_accepting_inbound_requests(&mut self) -> usize {
+        self.shutting_down = true;
+
+        let queued_requests = self.inbound_scheduler.drain_queued();
+        let rejected_requests = queued_requests.len();
+
+        for ScheduledRequest {
+            peer_id,
+            request_id,
+            request_type,
+            ..
+        } in queued_requests
+        {
+            self.reject_shutting_down(peer_id, request_id, &request_type);
+        }
+
+        rejected_requests
+    }
+
+    fn reject_shutting_down(
+        &mut self,
+        peer_id: PeerId,
+        request_id: InboundRequestId,
+        request_type: &RequestType<P>,
+    ) {
+        // Goodbye requests are never answered.
+        if matches!(request_type, RequestType::Goodbye(_)) {
+            return;
+        }
+
+        self.send_response_inner(
+            peer_id,
+            request_type.protocol(),
+            request_id,
+            RpcResponse::Error(
+                RpcErrorResponse::ServerError,
+                "Node is shutting down".into(),
+            ),
+        );
+    }
+
+    /// Terminates all inbound requests that are still awaiting a response with a `ServerError`.
+    ///
+    /// Returns the number of terminated requests.
+    pub fn cancel_active_inbound_requests(&mut self) -> usize {
+        let request_ids = self
+            .active_inbound_requests
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        for request_id in &request_ids {
+            let response = RpcResponse::Error(
+                RpcErrorResponse::ServerError,
+                "Node is shutting down".into(),
+            );
+
+            if self.send_response(*request_id, response).is_err() {
+                trace!(?request_id, "Inbound request already completed");
+            }
+        }
+
+        request_ids.len()
+    }
 }
 
 impl<Id, P> NetworkBehaviour for RPC<Id, P>
@@ -334,12 +657,27 @@ where
                 fork_context: self.fork_context.clone(),
                 max_rpc_size: self.chain_config.max_payload_size,
                 enable_light_client_server: self.enable_light_client_server,
+                response_byte_meter: Some(ResponseByteMeter::new(
+                    peer_id,
+                    self.response_byte_limiter.clone_arc(),
+                )),
                 phantom: PhantomData,
             },
             (),
         );
 
-        let handler = RPCHandler::new(protocol, self.fork_context.clone(), peer_id, connection_id);
+        let handler = RPCHandler::new(
+            protocol,
+            self.fork_context.clone(),
+            self.handler_config.clone_arc(),
+            peer_id,
+            connection_id,
+        );
+
+        self.connections
+            .entry(peer_id)
+            .or_default()
+            .push(connection_id);
 
         Ok(handler)
     }
@@ -358,12 +696,27 @@ where
                 fork_context: self.fork_context.clone(),
                 max_rpc_size: self.chain_config.max_payload_size,
                 enable_light_client_server: self.enable_light_client_server,
+                response_byte_meter: Some(ResponseByteMeter::new(
+                    peer_id,
+                    self.response_byte_limiter.clone_arc(),
+                )),
                 phantom: PhantomData,
             },
             (),
         );
 
-        let handler = RPCHandler::new(protocol, self.fork_context.clone(), peer_id, connection_id);
+        let handler = RPCHandler::new(
+            protocol,
+            self.fork_context.clone(),
+            self.handler_config.clone_arc(),
+            peer_id,
+            connection_id,
+        );
+
+        self.connections
+            .entry(peer_id)
+            .or_default()
+            .push(connection_id);
 
         Ok(handler)
     }
@@ -382,11 +735,17 @@ where
             ..
         }) = event
         {
+            if let Some(connections) = self.connections.get_mut(&peer_id) {
+                connections.retain(|id| *id != connection_id);
+            }
+
             // If there are still connections remaining, do nothing.
             if remaining_established > 0 {
                 return;
             }
 
+            self.connections.remove(&peer_id);
+
             // Get a list of pending requests from the self rate limiter
             for (id, proto) in self.outbound_request_limiter.peer_disconnected(peer_id) {
                 let error_msg = ToSwarm::GenerateEvent(RPCMessage {
@@ -406,10 +765,14 @@ where
                 .filter(|request| request.peer_id == peer_id)
                 .for_each(|request| request.peer_disconnected = true);
 
+            self.peer_quota_multipliers.remove(&peer_id);
+
             if let Some(limiter) = self.response_limiter.as_mut() {
                 limiter.peer_disconnected(peer_id);
             }
 
+            self.inbound_scheduler.peer_disconnected(peer_id);
+
             // Replace the pending Requests to the disconnected peer
             // with reports of failed requests.
             self.events.iter_mut().for_each(|event| match &event {
@@ -441,6 +804,17 @@ where
     ) {
         match event {
             HandlerEvent::Ok(RPCReceived::Request(request_id, request_type)) => {
+                if self.shutting_down {
+                    debug!(
+                        request = %request_type,
+                        %peer_id,
+                        "Rejecting inbound request, node is shutting down"
+                    );
+
+                    self.reject_shutting_down(peer_id, request_id, &request_type);
+                    return;
+                }
+
                 let is_concurrent_request_limit_exceeded = self
                     .active_inbound_requests
                     .iter()
@@ -459,6 +833,9 @@ where
                         },
                     )
                     .count()
+                    + self
+                        .inbound_scheduler
+                        .queued_requests(peer_id, request_type.protocol())
                     >= MAX_CONCURRENT_REQUESTS;
 
                 // Restricts more than MAX_CONCURRENT_REQUESTS inbound requests from running simultaneously on the same protocol per peer.
@@ -484,34 +861,49 @@ where
                     return;
                 }
 
-                // Requests that are below the limit on the number of simultaneous requests are added to the active inbound requests.
-                self.active_inbound_requests.insert(
-                    request_id,
-                    ActiveInboundRequest {
-                        peer_id,
-                        request_type: request_type.clone(),
-                        peer_disconnected: false,
-                    },
-                );
+                if let Some(reason) = self.data_availability.and_then(|data_availability| {
+                    data_availability.unavailable_reason(&self.chain_config, &request_type)
+                }) {
+                    debug!(
+                        request = %request_type,
+                        %peer_id,
+                        reason,
+                        "Requested range is not available"
+                    );
 
-                // If we received a Ping, we queue a Pong response.
-                if let RequestType::Ping(_) = request_type {
-                    trace!(connection_id = %connection_id, %peer_id, "Received Ping, queueing Pong");
+                    crate::common::metrics::inc_counter_vec(
+                        &crate::metrics::RPC_UNAVAILABLE_RANGE_REQUESTS,
+                        &[request_type.protocol().as_ref()],
+                    );
 
-                    self.send_response(
+                    self.send_response_inner(
+                        peer_id,
+                        request_type.protocol(),
                         request_id,
-                        RpcResponse::Success(RpcSuccessResponse::Pong(Ping {
-                            data: self.seq_number,
-                        })),
-                    )
-                    .expect("Request to exist");
+                        RpcResponse::Error(RpcErrorResponse::ResourceUnavailable, reason.into()),
+                    );
+                    return;
                 }
 
-                self.events.push(ToSwarm::GenerateEvent(RPCMessage {
+                // Requests that are below the limit on the number of simultaneous requests are
+                // handed to the application in fair order across peers. Goodbye, Ping, Status and
+                // MetaData requests are handed over right away.
+                let cost = request_type
+                    .max_responses(
+                        self.fork_context.chain_config(),
+                        self.fork_context.current_fork_epoch(),
+                    )
+                    .max(1);
+
+                self.inbound_scheduler.enqueue(
                     peer_id,
                     connection_id,
-                    message: Ok(RPCReceived::Request(request_id, request_type)),
-                }));
+                    request_id,
+                    request_type,
+                    cost,
+                );
+
+                self.dispatch_inbound_requests();
             }
             HandlerEvent::Ok(RPCReceived::Response(id, response)) => {
                 if response.protocol().terminator().is_none() {
@@ -542,6 +934,14 @@ where
                 let protocol = match err {
                     HandlerErr::Inbound { proto, .. } | HandlerErr::Outbound { proto, .. } => proto,
                 };
+
+                if let HandlerErr::Inbound { id, .. } = err {
+                    self.inbound_scheduler.request_completed(&InboundRequestId {
+                        connection_id,
+                        substream_id: id,
+                    });
+                }
+
                 self.outbound_request_limiter
                     .request_completed(&peer_id, protocol);
 
@@ -562,6 +962,34 @@ where
     }
 
     fn poll(&mut self, cx: &mut Context) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        // Requests answered since the last poll may have freed slots for queued ones.
+        self.dispatch_inbound_requests();
+
+        // Requests that waited too long for a slot are answered before the peer times out.
+        while let Poll::Ready(ScheduledRequest {
+            peer_id,
+            request_id,
+            request_type,
+            ..
+        }) = self.inbound_scheduler.poll_expired(cx)
+        {
+            debug!(
+                request = %request_type,
+                %peer_id,
+                "Inbound request was not served before its deadline"
+            );
+
+            self.send_response_inner(
+                peer_id,
+                request_type.protocol(),
+                request_id,
+                RpcResponse::Error(
+                    RpcErrorResponse::RateLimited,
+                    "Rate limited. The request waited too long to be served".into(),
+                ),
+            );
+        }
+
         if let Some(response_limiter) = self.response_limiter.as_mut() {
             if let Poll::Ready(responses) = response_limiter.poll_ready(cx) {
                 for response in responses {
@@ -578,6 +1006,31 @@ where
             self.events.push(event)
         }
 
+        if let Poll::Ready((peer_id, id, proto)) = self.outbound_request_limiter.poll_expired(cx) {
+            // Requests queued for a peer are failed when it disconnects, so an expired request
+            // without a connection was made to a peer that was never connected, like the requests
+            // the swarm drops for such peers.
+            let connection_id = self
+                .connections
+                .get(&peer_id)
+                .and_then(|connections| connections.last())
+                .copied();
+
+            if let Some(connection_id) = connection_id {
+                self.events.push(ToSwarm::GenerateEvent(RPCMessage {
+                    peer_id,
+                    connection_id,
+                    message: Err(HandlerErr::Outbound {
+                        id,
+                        proto,
+                        error: RPCError::InternalError("Request was not sent before its deadline"),
+                    }),
+                }));
+            } else {
+                debug!(%peer_id, %proto, "Dropping expired request to a peer without a connection");
+            }
+        }
+
         if !self.events.is_empty() {
             return Poll::Ready(self.events.remove(0));
         }
@@ -585,3 +1038,134 @@ where
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::config::{ByteQuotas, RateLimiterConfig};
+    use crate::rpc::rate_limiter::Quota;
+    use crate::service::api_types::AppRequestId;
+    use types::nonstandard::Phase;
+    use types::preset::Mainnet;
+
+    fn rpc() -> RPC<AppRequestId, Mainnet> {
+        let chain_config = Arc::new(ChainConfig::mainnet().rapid_upgrade());
+        let fork_context = Arc::new(ForkContext::dummy::<Mainnet>(&chain_config, Phase::Phase0));
+        let byte_quota_config = ByteQuotaConfig {
+            peer_quotas: ByteQuotas(HashMap::from([(Protocol::Ping, Quota::one_every(10))])),
+            global_quotas: ByteQuotas::default(),
+        };
+
+        RPC::new(
+            chain_config,
+            fork_context,
+            false,
+            None,
+            Some(byte_quota_config),
+            None,
+            RpcHandlerConfig::default(),
+            InboundSchedulerConfig::default(),
+            0,
+        )
+    }
+
+    fn pong() -> RpcResponse<Mainnet> {
+        RpcResponse::Success(RpcSuccessResponse::Pong(Ping { data: 0 }))
+    }
+
+    #[tokio::test]
+    async fn enabling_the_inbound_limiter_applies_byte_quotas_and_multipliers() {
+        let mut rpc = rpc();
+        let peer_id = PeerId::random();
+        let ping_quota = "ping:1/10".parse::<RateLimiterConfig>().unwrap();
+
+        rpc.set_peer_quota_multiplier(peer_id, NonZeroU64::new(2));
+        rpc.update_rate_limits(Some(InboundRateLimiterConfig(ping_quota)), None)
+            .expect("configuration is valid");
+
+        let limiter = rpc
+            .response_limiter
+            .as_mut()
+            .expect("inbound limiter is enabled");
+
+        assert!(limiter.byte_limiter().is_some());
+
+        // The multiplier doubles the quota of a single response.
+        for allowed in [true, true, false] {
+            let response_allowed = limiter.allows(
+                peer_id,
+                Protocol::Ping,
+                ConnectionId::new_unchecked(0),
+                SubstreamId::new(0),
+                pong(),
+            );
+
+            assert_eq!(response_allowed, allowed);
+        }
+    }
+
+    #[tokio::test]
+    async fn enabling_the_inbound_limiter_charges_existing_connections() {
+        let mut rpc = rpc();
+        let peer_id = PeerId::random();
+        let addr = "/ip4/127.0.0.1/tcp/9000"
+            .parse::<libp2p::Multiaddr>()
+            .unwrap();
+        let ping_quota = "ping:10/10".parse::<RateLimiterConfig>().unwrap();
+
+        let handler = rpc
+            .handle_established_inbound_connection(
+                ConnectionId::new_unchecked(0),
+                peer_id,
+                &addr,
+                &addr,
+            )
+            .expect("connection is accepted");
+
+        let byte_meter = handler
+            .listen_protocol()
+            .upgrade()
+            .response_byte_meter
+            .clone()
+            .expect("every connection has a byte meter");
+
+        rpc.update_rate_limits(Some(InboundRateLimiterConfig(ping_quota)), None)
+            .expect("configuration is valid");
+
+        let allows = |rpc: &mut RPC<AppRequestId, Mainnet>| {
+            rpc.response_limiter
+                .as_mut()
+                .expect("inbound limiter is enabled")
+                .allows(
+                    peer_id,
+                    Protocol::Ping,
+                    ConnectionId::new_unchecked(0),
+                    SubstreamId::new(0),
+                    pong(),
+                )
+        };
+
+        assert!(allows(&mut rpc));
+
+        // A response encoded on the connection opened before the limiter exceeds the byte quota.
+        byte_meter.record(Protocol::Ping, 100);
+
+        assert!(!allows(&mut rpc));
+    }
+
+    #[test]
+    fn invalid_rate_limits_change_neither_limiter() {
+        let mut rpc = rpc();
+        let valid = "ping:1/10".parse::<RateLimiterConfig>().unwrap();
+        let invalid = "ping:1/0".parse::<RateLimiterConfig>().unwrap();
+
+        assert!(
+            rpc.update_rate_limits(
+                Some(InboundRateLimiterConfig(valid)),
+                Some(OutboundRateLimiterConfig(invalid)),
+            )
+            .is_err()
+        );
+        assert!(rpc.response_limiter.is_none());
+    }
+}
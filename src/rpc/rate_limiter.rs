@@ -116,6 +116,8 @@ pub struct RPCRateLimiter {
     lc_finality_update_rl: Limiter<PeerId>,
     /// LightClientUpdatesByRange rate limiter.
     lc_updates_by_range_rl: Limiter<PeerId>,
+    /// Multipliers of the quotas of individual peers.
+    peer_multipliers: FnvHashMap<PeerId, NonZeroU64>,
     fork_context: Arc<ForkContext>,
 }
 
@@ -262,6 +264,7 @@ impl RPCRateLimiterBuilder {
             lc_optimistic_update_rl,
             lc_finality_update_rl,
             lc_updates_by_range_rl,
+            peer_multipliers: FnvHashMap::default(),
             init_time: Instant::now(),
             fork_context,
         })
@@ -362,8 +365,15 @@ impl RPCRateLimiter {
             )
             .max(1);
 
-        let check =
-            |limiter: &mut Limiter<PeerId>| limiter.allows(time_since_start, peer_id, tokens);
+        let multiplier = self
+            .peer_multipliers
+            .get(peer_id)
+            .copied()
+            .unwrap_or(NonZeroU64::MIN);
+
+        let check = |limiter: &mut Limiter<PeerId>| {
+            limiter.allows_with_multiplier(time_since_start, peer_id, tokens, multiplier)
+        };
         let limiter = match request.protocol() {
             Protocol::Ping => &mut self.ping_rl,
             Protocol::Status => &mut self.status_rl,
@@ -383,6 +393,62 @@ impl RPCRateLimiter {
         check(limiter)
     }
 
+    /// Multiplies the quotas of `peer_id` by `multiplier`, or restores them if it is `None`.
+    pub fn set_peer_multiplier(&mut self, peer_id: PeerId, multiplier: Option<NonZeroU64>) {
+        match multiplier {
+            Some(multiplier) if multiplier > NonZeroU64::MIN => {
+                self.peer_multipliers.insert(peer_id, multiplier);
+            }
+            _ => {
+                self.peer_multipliers.remove(&peer_id);
+            }
+        }
+    }
+
+    /// Replaces the quotas with the ones of `updated`. Per-peer state is kept for protocols whose
+    /// quotas are replenished over the same period and reset for the others.
+    pub fn update_quotas(&mut self, mut updated: Self) {
+        updated.init_time = self.init_time;
+        updated.peer_multipliers = std::mem::take(&mut self.peer_multipliers);
+
+        let Self {
+            prune_interval: _,
+            init_time: _,
+            goodbye_rl,
+            ping_rl,
+            metadata_rl,
+            status_rl,
+            bbrange_rl,
+            bbroots_rl,
+            blbrange_rl,
+            blbroot_rl,
+            dcbroot_rl,
+            dcbrange_rl,
+            lc_bootstrap_rl,
+            lc_optimistic_update_rl,
+            lc_finality_update_rl,
+            lc_updates_by_range_rl,
+            peer_multipliers: _,
+            fork_context: _,
+        } = std::mem::replace(self, updated);
+
+        self.goodbye_rl.inherit(goodbye_rl);
+        self.ping_rl.inherit(ping_rl);
+        self.metadata_rl.inherit(metadata_rl);
+        self.status_rl.inherit(status_rl);
+        self.bbrange_rl.inherit(bbrange_rl);
+        self.bbroots_rl.inherit(bbroots_rl);
+        self.blbrange_rl.inherit(blbrange_rl);
+        self.blbroot_rl.inherit(blbroot_rl);
+        self.dcbroot_rl.inherit(dcbroot_rl);
+        self.dcbrange_rl.inherit(dcbrange_rl);
+        self.lc_bootstrap_rl.inherit(lc_bootstrap_rl);
+        self.lc_optimistic_update_rl
+            .inherit(lc_optimistic_update_rl);
+        self.lc_finality_update_rl.inherit(lc_finality_update_rl);
+        self.lc_updates_by_range_rl.inherit(lc_updates_by_range_rl);
+    }
+
     pub fn prune(&mut self) {
         let time_since_start = self.init_time.elapsed();
 
@@ -403,6 +469,7 @@ impl RPCRateLimiter {
             lc_optimistic_update_rl,
             lc_finality_update_rl,
             lc_updates_by_range_rl,
+            peer_multipliers: _,
             fork_context: _,
         } = self;
 
@@ -472,10 +539,22 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
         time_since_start: Duration,
         key: &Key,
         tokens: u64,
+    ) -> Result<(), RateLimitedErr> {
+        self.allows_with_multiplier(time_since_start, key, tokens, NonZeroU64::MIN)
+    }
+
+    /// Same as [`Self::allows`], but for a key whose quota has `multiplier` times as many tokens.
+    pub fn allows_with_multiplier(
+        &mut self,
+        time_since_start: Duration,
+        key: &Key,
+        tokens: u64,
+        multiplier: NonZeroU64,
     ) -> Result<(), RateLimitedErr> {
         let time_since_start = time_since_start.as_nanos() as u64;
         let tau = self.tau;
-        let t = self.t;
+        // Tokens of a multiplied quota are replenished faster. The bucket still fills in `tau`.
+        let t = self.t.checked_div(multiplier.get()).unwrap_or(self.t);
         // how long does it take to replenish these tokens
         let additional_time = t.saturating_mul(tokens);
         if additional_time > tau {
@@ -504,6 +583,34 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
         }
     }
 
+    /// Charges `tokens` to `key` regardless of whether they fit in its quota. Requests of a key
+    /// that went over its quota are rejected by [`Self::allows`] until the excess is replenished.
+    pub fn charge(
+        &mut self,
+        time_since_start: Duration,
+        key: &Key,
+        tokens: u64,
+        multiplier: NonZeroU64,
+    ) {
+        let time_since_start = time_since_start.as_nanos() as u64;
+        let t = self.t.checked_div(multiplier.get()).unwrap_or(self.t);
+        let tat = self
+            .tat_per_key
+            .entry(key.clone())
+            .or_insert(time_since_start);
+        *tat = time_since_start
+            .max(*tat)
+            .saturating_add(t.saturating_mul(tokens));
+    }
+
+    /// Takes over the per-key state of `previous` if its buckets fill up in the same time. The
+    /// tokens used by each key then keep the same share of the quota.
+    pub fn inherit(&mut self, previous: Self) {
+        if self.tau == previous.tau {
+            self.tat_per_key = previous.tat_per_key;
+        }
+    }
+
     /// Removes keys for which their bucket is full by `time_limit`
     pub fn prune(&mut self, time_limit: Duration) {
         let lim = &mut (time_limit.as_nanos() as u64);
@@ -623,4 +730,70 @@ mod tests {
         let result = limiter.allows(Duration::from_secs_f32(0.0), &10, tokens);
         assert!(matches!(result, Err(RateLimitedErr::TooLarge)));
     }
+
+    #[test]
+    fn multiplied_quota() {
+        let mut limiter = Limiter::from_quota(Quota {
+            replenish_all_every: Duration::from_secs(2),
+            max_tokens: NonZeroU64::new(4).unwrap(),
+        })
+        .unwrap();
+        let multiplier = NonZeroU64::new(2).unwrap();
+        let key = 10;
+
+        // A quota of 4T/2s doubled allows bursts of 8T, replenishing 1T every 0.25s.
+        assert!(
+            limiter
+                .allows_with_multiplier(Duration::from_secs_f32(0.0), &key, 8, multiplier)
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .allows_with_multiplier(Duration::from_secs_f32(0.1), &key, 1, multiplier)
+                .is_err()
+        );
+        assert!(
+            limiter
+                .allows_with_multiplier(Duration::from_secs_f32(0.25), &key, 1, multiplier)
+                .is_ok()
+        );
+        assert!(matches!(
+            limiter.allows(Duration::from_secs_f32(0.3), &key, 8),
+            Err(RateLimitedErr::TooLarge),
+        ));
+    }
+
+    #[test]
+    fn state_is_kept_for_quotas_with_the_same_period() {
+        let quota = |max_tokens| Quota {
+            replenish_all_every: Duration::from_secs(2),
+            max_tokens: NonZeroU64::new(max_tokens).unwrap(),
+        };
+        let key = 10;
+
+        let mut previous = Limiter::from_quota(quota(4)).unwrap();
+        assert!(
+            previous
+                .allows(Duration::from_secs_f32(0.0), &key, 4)
+                .is_ok()
+        );
+
+        // The bucket of the key is still empty after doubling the quota.
+        let mut doubled = Limiter::from_quota(quota(8)).unwrap();
+        doubled.inherit(previous);
+        assert!(
+            doubled
+                .allows(Duration::from_secs_f32(0.1), &key, 1)
+                .is_err()
+        );
+
+        // The bucket of the key is full after changing the period.
+        let mut longer = Limiter::from_quota(Quota {
+            replenish_all_every: Duration::from_secs(4),
+            max_tokens: NonZeroU64::new(8).unwrap(),
+        })
+        .unwrap();
+        longer.inherit(doubled);
+        assert!(longer.allows(Duration::from_secs_f32(0.1), &key, 8).is_ok());
+    }
 }
@@ -1,6 +1,7 @@
 use crate::PeerId;
 use crate::common::metrics;
-use crate::rpc::config::InboundRateLimiterConfig;
+use crate::rpc::byte_limiter::ByteLimiter;
+use crate::rpc::config::{ByteQuotaConfig, InboundRateLimiterConfig};
 use crate::rpc::rate_limiter::{RPCRateLimiter, RateLimitedErr};
 use crate::rpc::self_limiter::timestamp_now;
 use crate::rpc::{Protocol, RpcResponse, SubstreamId};
@@ -8,8 +9,10 @@ use crate::types::ForkContext;
 use futures::FutureExt;
 use libp2p::swarm::ConnectionId;
 use logging::exception;
+use parking_lot::Mutex;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, VecDeque};
+use std::num::NonZeroU64;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
@@ -32,6 +35,8 @@ pub(super) struct QueuedResponse<P: Preset> {
 pub(super) struct ResponseLimiter<P: Preset> {
     /// Rate limiter for our responses.
     limiter: RPCRateLimiter,
+    /// Byte limiter for our responses, charged by the inbound codecs.
+    byte_limiter: Option<Arc<Mutex<ByteLimiter>>>,
     /// Responses queued for sending. These responses are stored when the response limiter rejects them.
     delayed_responses: HashMap<(PeerId, Protocol), VecDeque<QueuedResponse<P>>>,
     /// The delay required to allow a peer's outbound response per protocol.
@@ -42,10 +47,26 @@ impl<P: Preset> ResponseLimiter<P> {
     /// Creates a new [`ResponseLimiter`] based on configuration values.
     pub fn new(
         config: InboundRateLimiterConfig,
+        byte_quota_config: Option<ByteQuotaConfig>,
         fork_context: Arc<ForkContext>,
     ) -> Result<Self, &'static str> {
+        let limiter = RPCRateLimiter::new_with_config(config.0, fork_context)?;
+        Self::with_limiter(limiter, byte_quota_config)
+    }
+
+    /// Creates a new [`ResponseLimiter`] using an already built rate limiter.
+    pub fn with_limiter(
+        limiter: RPCRateLimiter,
+        byte_quota_config: Option<ByteQuotaConfig>,
+    ) -> Result<Self, &'static str> {
+        let byte_limiter = byte_quota_config
+            .map(ByteLimiter::new)
+            .transpose()?
+            .map(|limiter| Arc::new(Mutex::new(limiter)));
+
         Ok(ResponseLimiter {
-            limiter: RPCRateLimiter::new_with_config(config.0, fork_context)?,
+            limiter,
+            byte_limiter,
             delayed_responses: HashMap::new(),
             next_response: DelayQueue::new(),
         })
@@ -75,9 +96,13 @@ impl<P: Preset> ResponseLimiter<P> {
             return false;
         }
 
-        if let Err(wait_time) =
-            Self::try_limiter(&mut self.limiter, peer_id, response.clone(), protocol)
-        {
+        if let Err(wait_time) = Self::try_limiter(
+            &mut self.limiter,
+            self.byte_limiter.as_deref(),
+            peer_id,
+            response.clone(),
+            protocol,
+        ) {
             self.delayed_responses
                 .entry((peer_id, protocol))
                 .or_default()
@@ -96,14 +121,23 @@ impl<P: Preset> ResponseLimiter<P> {
         true
     }
 
-    /// Checks if the limiter allows the response. If the response should be delayed, the duration
+    /// Checks if the limiters allow the response. If the response should be delayed, the duration
     /// to wait is returned.
     fn try_limiter(
         limiter: &mut RPCRateLimiter,
+        byte_limiter: Option<&Mutex<ByteLimiter>>,
         peer_id: PeerId,
         response: RpcResponse<P>,
         protocol: Protocol,
     ) -> Result<(), Duration> {
+        // Check the byte quota first, as it does not consume any of the response quota.
+        if let Some(byte_limiter) = byte_limiter {
+            if let Err(wait_time) = byte_limiter.lock().check(&peer_id, protocol) {
+                debug!(%peer_id, %protocol, wait_time_ms = wait_time.as_millis(), "Response byte rate limiting");
+                return Err(wait_time);
+            }
+        }
+
         match limiter.allows(&peer_id, &(response.clone(), protocol)) {
             Ok(()) => Ok(()),
             Err(e) => match e {
@@ -124,8 +158,31 @@ impl<P: Preset> ResponseLimiter<P> {
         }
     }
 
+    /// Replaces the quotas of our responses, see [`RPCRateLimiter::update_quotas`]. Delayed
+    /// responses are checked against the new quotas once their current delay expires.
+    pub fn update_limiter(&mut self, limiter: RPCRateLimiter) {
+        self.limiter.update_quotas(limiter);
+    }
+
+    /// Multiplies the quotas of our responses to `peer_id` by `multiplier`, or restores them if it
+    /// is `None`.
+    pub fn set_peer_multiplier(&mut self, peer_id: PeerId, multiplier: Option<NonZeroU64>) {
+        self.limiter.set_peer_multiplier(peer_id, multiplier);
+
+        if let Some(byte_limiter) = self.byte_limiter.as_ref() {
+            byte_limiter.lock().set_peer_multiplier(peer_id, multiplier);
+        }
+    }
+
+    /// Returns the limiter to be charged with the bytes of encoded responses, if byte quotas are
+    /// configured.
+    pub fn byte_limiter(&self) -> Option<Arc<Mutex<ByteLimiter>>> {
+        self.byte_limiter.clone()
+    }
+
     /// Informs the limiter that a peer has disconnected. This removes any pending responses.
     pub fn peer_disconnected(&mut self, peer_id: PeerId) {
+        self.set_peer_multiplier(peer_id, None);
         self.delayed_responses
             .retain(|(map_peer_id, _protocol), _queue| map_peer_id != &peer_id);
     }
@@ -143,6 +200,7 @@ impl<P: Preset> ResponseLimiter<P> {
                 while let Some(response) = queue.pop_front() {
                     match Self::try_limiter(
                         &mut self.limiter,
+                        self.byte_limiter.as_deref(),
                         response.peer_id,
                         response.response.clone(),
                         response.protocol,
@@ -168,9 +226,13 @@ impl<P: Preset> ResponseLimiter<P> {
             }
         }
 
-        // Prune the rate limiter.
+        // Prune the rate limiters.
         let _ = self.limiter.poll_unpin(cx);
 
+        if let Some(byte_limiter) = self.byte_limiter.as_ref() {
+            byte_limiter.lock().poll_prune(cx);
+        }
+
         if !responses.is_empty() {
             return Poll::Ready(responses);
         }
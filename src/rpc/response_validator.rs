@@ -0,0 +1,228 @@
+//! Checks that the responses of outbound requests match what was requested.
+//!
+//! The codec only verifies sizes and context bytes. A response that decodes correctly but does not
+//! belong to the request (a block outside the requested range, a sidecar that was never asked for
+//! or one that was already received) is reported as `RPCError::InvalidData`.
+
+use std::collections::HashSet;
+
+use ssz::SszHash as _;
+use types::{
+    combined::DataColumnSidecar,
+    deneb::containers::BlobSidecar,
+    phase0::primitives::{H256, Slot},
+    preset::Preset,
+    traits::SignedBeaconBlock as _,
+};
+
+use super::{RequestType, RpcSuccessResponse};
+
+/// Validation state of a single outbound request.
+#[derive(Debug)]
+pub enum ResponseValidator {
+    /// Blocks must be inside `[start_slot, end_slot)` with strictly ascending slots.
+    BlocksByRange {
+        start_slot: Slot,
+        end_slot: Slot,
+        last_slot: Option<Slot>,
+    },
+    /// Sidecars must match a requested `(block_root, index)` pair and may only be received once.
+    SidecarsByRoot {
+        requested: HashSet<(H256, u64)>,
+        received: HashSet<(H256, u64)>,
+    },
+}
+
+impl ResponseValidator {
+    /// Returns a validator for `request`, or `None` if its responses are not validated.
+    pub fn new<P: Preset>(request: &RequestType<P>) -> Option<Self> {
+        let validator = match request {
+            RequestType::BlocksByRange(request) => Self::BlocksByRange {
+                start_slot: request.start_slot(),
+                end_slot: request.start_slot().saturating_add(request.count()),
+                last_slot: None,
+            },
+            RequestType::BlobsByRoot(request) => Self::SidecarsByRoot {
+                requested: request
+                    .blob_ids
+                    .iter()
+                    .map(|blob_id| (blob_id.block_root, blob_id.index))
+                    .collect(),
+                received: HashSet::new(),
+            },
+            RequestType::DataColumnsByRoot(request) => Self::SidecarsByRoot {
+                requested: request
+                    .data_column_ids
+                    .iter()
+                    .flat_map(|data_column_id| {
+                        data_column_id
+                            .columns
+                            .iter()
+                            .map(|index| (data_column_id.block_root, *index))
+                    })
+                    .collect(),
+                received: HashSet::new(),
+            },
+            _ => return None,
+        };
+
+        Some(validator)
+    }
+
+    /// Checks `response` against the request and records it as received.
+    ///
+    /// Responses of the wrong type are rejected by the codec and are not checked here.
+    pub fn validate<P: Preset>(&mut self, response: &RpcSuccessResponse<P>) -> Result<(), String> {
+        match self {
+            Self::BlocksByRange {
+                start_slot,
+                end_slot,
+                last_slot,
+            } => {
+                let RpcSuccessResponse::BlocksByRange(block) = response else {
+                    return Ok(());
+                };
+
+                let slot = block.message().slot();
+
+                if slot < *start_slot || slot >= *end_slot {
+                    return Err(format!(
+                        "block slot {slot} is outside the requested range [{start_slot}, {end_slot})"
+                    ));
+                }
+
+                if let Some(last_slot) = last_slot.filter(|last_slot| slot <= *last_slot) {
+                    return Err(format!(
+                        "block slot {slot} is not greater than the previous slot {last_slot}"
+                    ));
+                }
+
+                *last_slot = Some(slot);
+            }
+            Self::SidecarsByRoot {
+                requested,
+                received,
+            } => {
+                let identifier = match response {
+                    RpcSuccessResponse::BlobsByRoot(blob_sidecar) => {
+                        blob_sidecar_identifier(blob_sidecar)
+                    }
+                    RpcSuccessResponse::DataColumnsByRoot(data_column_sidecar) => {
+                        data_column_sidecar_identifier(data_column_sidecar)
+                    }
+                    _ => return Ok(()),
+                };
+
+                let (block_root, index) = identifier;
+
+                if !requested.contains(&identifier) {
+                    return Err(format!(
+                        "sidecar with block root {block_root:?} and index {index} was not requested"
+                    ));
+                }
+
+                if !received.insert(identifier) {
+                    return Err(format!(
+                        "sidecar with block root {block_root:?} and index {index} was received more than once"
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn blob_sidecar_identifier<P: Preset>(blob_sidecar: &BlobSidecar<P>) -> (H256, u64) {
+    (
+        blob_sidecar.signed_block_header.message.hash_tree_root(),
+        blob_sidecar.index,
+    )
+}
+
+fn data_column_sidecar_identifier<P: Preset>(
+    data_column_sidecar: &DataColumnSidecar<P>,
+) -> (H256, u64) {
+    let block_root = match data_column_sidecar {
+        DataColumnSidecar::Fulu(sidecar) => sidecar.signed_block_header.message.hash_tree_root(),
+        DataColumnSidecar::Gloas(sidecar) => sidecar.beacon_block_root,
+    };
+
+    (block_root, data_column_sidecar.index())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::factory;
+    use crate::rpc::methods::{BlobsByRootRequest, OldBlocksByRangeRequest};
+    use std::sync::Arc;
+    use std_ext::ArcExt as _;
+    use types::{
+        combined::SignedBeaconBlock, config::Config as ChainConfig,
+        deneb::containers::BlobIdentifier, nonstandard::Phase, preset::Mainnet,
+    };
+
+    fn block_at_slot(slot: Slot) -> RpcSuccessResponse<Mainnet> {
+        let mut block = factory::empty_phase0_signed_beacon_block::<Mainnet>();
+        block.message.slot = slot;
+        RpcSuccessResponse::BlocksByRange(Arc::new(SignedBeaconBlock::from(block)))
+    }
+
+    #[test]
+    fn rejects_blocks_outside_the_range_or_out_of_order() {
+        let request = RequestType::<Mainnet>::BlocksByRange(OldBlocksByRangeRequest::new(10, 5, 1));
+        let mut validator = ResponseValidator::new(&request).expect("request is validated");
+
+        assert!(validator.validate(&block_at_slot(9)).is_err());
+        assert_eq!(validator.validate(&block_at_slot(10)), Ok(()));
+        assert_eq!(validator.validate(&block_at_slot(12)), Ok(()));
+        assert!(validator.validate(&block_at_slot(12)).is_err());
+        assert!(validator.validate(&block_at_slot(11)).is_err());
+        assert_eq!(validator.validate(&block_at_slot(14)), Ok(()));
+        assert!(validator.validate(&block_at_slot(15)).is_err());
+    }
+
+    #[test]
+    fn rejects_unrequested_and_duplicate_blob_sidecars() {
+        let config = ChainConfig::mainnet();
+        let blob_sidecar = Arc::new(BlobSidecar::<Mainnet>::default());
+        let block_root = blob_sidecar.signed_block_header.message.hash_tree_root();
+
+        let request = RequestType::<Mainnet>::BlobsByRoot(BlobsByRootRequest::new(
+            &config,
+            Phase::Deneb,
+            [BlobIdentifier {
+                block_root,
+                index: 0,
+            }]
+            .into_iter(),
+        ));
+        let mut validator = ResponseValidator::new(&request).expect("request is validated");
+
+        let mut unrequested = BlobSidecar::<Mainnet>::default();
+        unrequested.index = 1;
+
+        assert!(
+            validator
+                .validate(&RpcSuccessResponse::BlobsByRoot(Arc::new(unrequested)))
+                .is_err()
+        );
+        assert_eq!(
+            validator.validate(&RpcSuccessResponse::BlobsByRoot(blob_sidecar.clone_arc())),
+            Ok(()),
+        );
+        assert!(
+            validator
+                .validate(&RpcSuccessResponse::BlobsByRoot(blob_sidecar))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn other_requests_are_not_validated() {
+        assert!(
+            ResponseValidator::new(&RequestType::<Mainnet>::LightClientFinalityUpdate).is_none()
+        );
+    }
+}
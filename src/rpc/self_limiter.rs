@@ -4,6 +4,7 @@ use super::{
     rate_limiter::{RPCRateLimiter as RateLimiter, RateLimitedErr},
 };
 use crate::{common::metrics, rpc::rate_limiter::RateLimiterItem};
+use std::num::NonZeroU64;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::{
     collections::{HashMap, VecDeque, hash_map::Entry},
@@ -16,18 +17,33 @@ use futures::FutureExt;
 use libp2p::{PeerId, swarm::NotifyHandler};
 use logging::exception;
 use smallvec::SmallVec;
-use tokio_util::time::DelayQueue;
+use tokio_util::time::{DelayQueue, delay_queue};
 use tracing::debug;
 use types::preset::Preset;
 
 use crate::types::ForkContext;
 
+/// The priority of an outbound request. Requests queued for the same peer and protocol are sent
+/// in order of priority, and in the order they were made within the same priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RequestPriority {
+    /// Requests that can wait, such as backfill.
+    Low,
+    #[default]
+    Normal,
+    /// Latency-critical requests, such as head block lookups.
+    High,
+}
+
 /// A request that was rate limited or waiting on rate limited requests for the same peer and
 /// protocol.
 struct QueuedRequest<Id: ReqId, P: Preset> {
     req: RequestType<P>,
     request_id: Id,
+    priority: RequestPriority,
     queued_at: Duration,
+    /// Key of the deadline of the request in `queue_deadlines`, removed when it leaves the queue.
+    deadline_key: Option<delay_queue::Key>,
 }
 
 /// The number of milliseconds requests delayed due to the concurrent request limit stay in the queue.
@@ -39,10 +55,14 @@ pub(crate) struct SelfRateLimiter<Id: ReqId, P: Preset> {
     active_requests: HashMap<PeerId, HashMap<Protocol, usize>>,
     /// Requests queued for sending per peer. These requests are stored when the self rate
     /// limiter rejects them. Rate limiting is based on a Peer and Protocol basis, therefore
-    /// are stored in the same way.
+    /// are stored in the same way. Each queue is ordered by priority.
     delayed_requests: HashMap<(PeerId, Protocol), VecDeque<QueuedRequest<Id, P>>>,
+    /// Deadlines of queued requests that are dropped if they are not sent in time.
+    queue_deadlines: DelayQueue<(PeerId, Protocol, Id)>,
     /// The delay required to allow a peer's outbound request per protocol.
     next_peer_request: DelayQueue<(PeerId, Protocol)>,
+    /// Keys of the pending `next_peer_request` delays, removed together with their queue.
+    next_peer_request_keys: HashMap<(PeerId, Protocol), delay_queue::Key>,
     /// Rate limiter for our own requests.
     rate_limiter: Option<RateLimiter>,
     /// Requests that are ready to be sent.
@@ -74,7 +94,9 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
         Ok(SelfRateLimiter {
             active_requests: Default::default(),
             delayed_requests: Default::default(),
+            queue_deadlines: Default::default(),
             next_peer_request: Default::default(),
+            next_peer_request_keys: Default::default(),
             rate_limiter,
             ready_requests: Default::default(),
         })
@@ -82,48 +104,88 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
 
     /// Checks if the rate limiter allows the request. If it's allowed, returns the
     /// [`ToSwarm`] that should be emitted. When not allowed, the request is delayed
-    /// until it can be sent.
+    /// until it can be sent, ahead of queued requests of lower `priority`.
+    ///
+    /// A delayed request that is still queued after `max_queue_time` is dropped and returned by
+    /// [`Self::poll_expired`].
     pub fn allows(
         &mut self,
         peer_id: PeerId,
         request_id: Id,
         req: RequestType<P>,
+        priority: RequestPriority,
+        max_queue_time: Option<Duration>,
     ) -> Result<RPCSend<Id, P>, Error> {
         let protocol = req.versioned_protocol().protocol();
+        let key = (peer_id, protocol);
         // First check that there are not already other requests waiting to be sent.
-        if let Some(queued_requests) = self.delayed_requests.get_mut(&(peer_id, protocol)) {
+        let result = if self.delayed_requests.contains_key(&key) {
             debug!(
                 %peer_id,
                 protocol = %req.protocol(),
+                ?priority,
                 "Self rate limiting since there are already other requests waiting to be sent"
             );
 
-            queued_requests.push_back(QueuedRequest {
-                req,
+            let deadline_key = self.insert_deadline(key, request_id, max_queue_time);
+
+            self.queue_request(
+                key,
+                QueuedRequest {
+                    req,
+                    request_id,
+                    priority,
+                    queued_at: timestamp_now(),
+                    deadline_key,
+                },
+            );
+            Error::PendingRequests
+        } else {
+            match Self::try_send_request(
+                &mut self.active_requests,
+                &mut self.rate_limiter,
+                peer_id,
                 request_id,
-                queued_at: timestamp_now(),
-            });
-            return Err(Error::PendingRequests);
-        }
-        match Self::try_send_request(
-            &mut self.active_requests,
-            &mut self.rate_limiter,
-            peer_id,
-            request_id,
-            req,
-        ) {
-            Err((rate_limited_req, wait_time)) => {
-                let key = (peer_id, protocol);
-                self.next_peer_request.insert(key, wait_time);
-                self.delayed_requests
-                    .entry(key)
-                    .or_default()
-                    .push_back(rate_limited_req);
-
-                Err(Error::RateLimited)
+                req,
+                priority,
+            ) {
+                Err((mut rate_limited_req, wait_time)) => {
+                    let delay_key = self.next_peer_request.insert(key, wait_time);
+                    self.next_peer_request_keys.insert(key, delay_key);
+                    rate_limited_req.deadline_key =
+                        self.insert_deadline(key, request_id, max_queue_time);
+                    self.queue_request(key, rate_limited_req);
+                    Error::RateLimited
+                }
+                Ok(event) => return Ok(event),
             }
-            Ok(event) => Ok(event),
-        }
+        };
+
+        Err(result)
+    }
+
+    /// Starts the deadline of a request that is being queued, if it has one.
+    fn insert_deadline(
+        &mut self,
+        (peer_id, protocol): (PeerId, Protocol),
+        request_id: Id,
+        max_queue_time: Option<Duration>,
+    ) -> Option<delay_queue::Key> {
+        max_queue_time.map(|max_queue_time| {
+            self.queue_deadlines
+                .insert((peer_id, protocol, request_id), max_queue_time)
+        })
+    }
+
+    /// Queues a request behind the queued requests of the same or higher priority.
+    fn queue_request(&mut self, key: (PeerId, Protocol), queued_request: QueuedRequest<Id, P>) {
+        let queued_requests = self.delayed_requests.entry(key).or_default();
+        let position = queued_requests
+            .iter()
+            .position(|queued| queued.priority < queued_request.priority)
+            .unwrap_or(queued_requests.len());
+
+        queued_requests.insert(position, queued_request);
     }
 
     /// Auxiliary function to deal with self rate limiting outcomes. If the rate limiter allows the
@@ -136,6 +198,7 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
         peer_id: PeerId,
         request_id: Id,
         req: RequestType<P>,
+        priority: RequestPriority,
     ) -> Result<RPCSend<Id, P>, (QueuedRequest<Id, P>, Duration)> {
         if let Some(active_request) = active_requests.get(&peer_id) {
             if let Some(count) = active_request.get(&req.protocol()) {
@@ -149,7 +212,9 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
                         QueuedRequest {
                             req,
                             request_id,
+                            priority,
                             queued_at: timestamp_now(),
+                            deadline_key: None,
                         },
                         Duration::from_millis(WAIT_TIME_DUE_TO_CONCURRENT_REQUESTS),
                     ));
@@ -183,7 +248,9 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
                                 QueuedRequest {
                                     req,
                                     request_id,
+                                    priority,
                                     queued_at: timestamp_now(),
+                                    deadline_key: None,
                                 },
                                 wait_time,
                             ));
@@ -210,7 +277,9 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
             while let Some(QueuedRequest {
                 req,
                 request_id,
+                priority,
                 queued_at,
+                deadline_key,
             }) = queued_requests.pop_front()
             {
                 match Self::try_send_request(
@@ -219,20 +288,29 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
                     peer_id,
                     request_id,
                     req.clone(),
+                    priority,
                 ) {
                     Err((_rate_limited_req, wait_time)) => {
                         let key = (peer_id, protocol);
-                        self.next_peer_request.insert(key, wait_time);
+                        let delay_key = self.next_peer_request.insert(key, wait_time);
+                        self.next_peer_request_keys.insert(key, delay_key);
                         // Don't push `rate_limited_req` here to prevent `queued_at` from being updated.
                         queued_requests.push_front(QueuedRequest {
                             req,
                             request_id,
+                            priority,
                             queued_at,
+                            deadline_key,
                         });
                         // If one fails just wait for the next window that allows sending requests.
                         return;
                     }
-                    Ok(event) => self.ready_requests.push((peer_id, event, queued_at)),
+                    Ok(event) => {
+                        if let Some(deadline_key) = deadline_key {
+                            self.queue_deadlines.remove(&deadline_key);
+                        }
+                        self.ready_requests.push((peer_id, event, queued_at));
+                    }
                 }
             }
             if queued_requests.is_empty() {
@@ -243,20 +321,42 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
         // ignore these messages here.
     }
 
+    /// Replaces the quotas of our requests, see [`RateLimiter::update_quotas`]. Enables rate
+    /// limiting if it was disabled.
+    pub fn update_limiter(&mut self, limiter: RateLimiter) {
+        match self.rate_limiter.as_mut() {
+            Some(rate_limiter) => rate_limiter.update_quotas(limiter),
+            None => self.rate_limiter = Some(limiter),
+        }
+    }
+
+    /// Multiplies the quotas of our requests to `peer_id` by `multiplier`, or restores them if it
+    /// is `None`.
+    pub fn set_peer_multiplier(&mut self, peer_id: PeerId, multiplier: Option<NonZeroU64>) {
+        if let Some(limiter) = self.rate_limiter.as_mut() {
+            limiter.set_peer_multiplier(peer_id, multiplier);
+        }
+    }
+
     /// Informs the limiter that a peer has disconnected. This removes any pending requests and
     /// returns their IDs.
     pub fn peer_disconnected(&mut self, peer_id: PeerId) -> Vec<(Id, Protocol)> {
         self.active_requests.remove(&peer_id);
 
+        if let Some(limiter) = self.rate_limiter.as_mut() {
+            limiter.set_peer_multiplier(peer_id, None);
+        }
+
         // It's not ideal to iterate this map, but the key is (PeerId, Protocol) and this map
         // should never really be large. So we iterate for simplicity
         let mut failed_requests = Vec::new();
         self.delayed_requests
             .retain(|(map_peer_id, protocol), queue| {
                 if map_peer_id == &peer_id {
-                    // NOTE: Currently cannot remove entries from the DelayQueue, we will just let
-                    // them expire and ignore them.
                     for message in queue {
+                        if let Some(deadline_key) = message.deadline_key {
+                            self.queue_deadlines.remove(&deadline_key);
+                        }
                         failed_requests.push((message.request_id, *protocol))
                     }
                     // Remove the entry
@@ -266,9 +366,110 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
                     true
                 }
             });
+
+        self.next_peer_request_keys
+            .retain(|(map_peer_id, _), delay_key| {
+                if map_peer_id == &peer_id {
+                    self.next_peer_request.remove(delay_key);
+                    false
+                } else {
+                    true
+                }
+            });
+
         failed_requests
     }
 
+    /// Removes a request that has not been sent yet. Requests that are waiting for quota are
+    /// dropped without consuming it, requests that are ready to be sent release their slot in the
+    /// concurrent request limit.
+    ///
+    /// Returns `false` if the request is not held by the limiter.
+    pub fn cancel_request(&mut self, peer_id: PeerId, request_id: Id) -> bool {
+        let protocols = self
+            .delayed_requests
+            .keys()
+            .filter(|(queue_peer_id, _)| *queue_peer_id == peer_id)
+            .map(|(_, protocol)| *protocol)
+            .collect::<Vec<_>>();
+
+        let removed = protocols.into_iter().find_map(|protocol| {
+            self.remove_queued_request(peer_id, protocol, |queued| queued.request_id == request_id)
+        });
+
+        if let Some(removed) = removed {
+            if let Some(deadline_key) = removed.deadline_key {
+                self.queue_deadlines.remove(&deadline_key);
+            }
+            return true;
+        }
+
+        let position = self.ready_requests.iter().position(|(ready_peer_id, event, _)| {
+            matches!(event, RPCSend::Request(id, _) if *ready_peer_id == peer_id && *id == request_id)
+        });
+
+        if let Some(position) = position {
+            if let (_, RPCSend::Request(_, req), _) = self.ready_requests.remove(position) {
+                self.request_completed(&peer_id, req.protocol());
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// Removes the first request matching `predicate` from the queue of `peer_id` and `protocol`
+    /// and returns it. Its deadline is left to the caller.
+    fn remove_queued_request(
+        &mut self,
+        peer_id: PeerId,
+        protocol: Protocol,
+        predicate: impl Fn(&QueuedRequest<Id, P>) -> bool,
+    ) -> Option<QueuedRequest<Id, P>> {
+        let Entry::Occupied(mut entry) = self.delayed_requests.entry((peer_id, protocol)) else {
+            return None;
+        };
+
+        let position = entry.get().iter().position(predicate)?;
+        let removed = entry.get_mut().remove(position);
+
+        if entry.get().is_empty() {
+            entry.remove();
+
+            if let Some(delay_key) = self.next_peer_request_keys.remove(&(peer_id, protocol)) {
+                self.next_peer_request.remove(&delay_key);
+            }
+        }
+
+        removed
+    }
+
+    /// Returns the next request dropped from the queue because it was not sent before its
+    /// deadline.
+    pub fn poll_expired(&mut self, cx: &mut Context<'_>) -> Poll<(PeerId, Id, Protocol)> {
+        while let Poll::Ready(Some(expired)) = self.queue_deadlines.poll_expired(cx) {
+            let deadline_key = expired.key();
+            let (peer_id, protocol, request_id) = expired.into_inner();
+
+            if self
+                .remove_queued_request(peer_id, protocol, |queued| {
+                    queued.deadline_key == Some(deadline_key)
+                })
+                .is_some()
+            {
+                debug!(
+                    %peer_id,
+                    %protocol,
+                    ?request_id,
+                    "Dropping request that was not sent before its deadline"
+                );
+                return Poll::Ready((peer_id, request_id, protocol));
+            }
+        }
+
+        Poll::Pending
+    }
+
     /// Informs the limiter that a response has been received.
     pub fn request_completed(&mut self, peer_id: &PeerId, protocol: Protocol) {
         if let Some(active_requests) = self.active_requests.get_mut(peer_id) {
@@ -288,6 +489,7 @@ impl<Id: ReqId, P: Preset> SelfRateLimiter<Id, P> {
         // immediately adding rate limiting keys.
         if let Poll::Ready(Some(expired)) = self.next_peer_request.poll_expired(cx) {
             let (peer_id, protocol) = expired.into_inner();
+            self.next_peer_request_keys.remove(&(peer_id, protocol));
             self.next_peer_request_ready(peer_id, protocol);
         }
 
@@ -324,13 +526,14 @@ pub fn timestamp_now() -> Duration {
 mod tests {
     use crate::rpc::config::{OutboundRateLimiterConfig, RateLimiterConfig};
     use crate::rpc::rate_limiter::Quota;
-    use crate::rpc::self_limiter::SelfRateLimiter;
+    use crate::rpc::self_limiter::{RequestPriority, SelfRateLimiter};
     use crate::rpc::{Ping, Protocol, RPCSend, RequestType};
     use crate::service::api_types::AppRequestId;
     use crate::types::ForkContext;
     use libp2p::PeerId;
     use std::num::NonZeroU64;
     use std::sync::Arc;
+    use std::task::Poll;
     use std::time::Duration;
     use types::nonstandard::Phase;
     use types::{config::Config, preset::Mainnet};
@@ -352,6 +555,8 @@ mod tests {
                 peer_id,
                 AppRequestId::Application(i),
                 RequestType::Ping(Ping { data: i as u64 }),
+                RequestPriority::default(),
+                None,
             );
         }
 
@@ -412,6 +617,8 @@ mod tests {
                 peer_id,
                 AppRequestId::Application(i),
                 RequestType::Ping(Ping { data: i as u64 }),
+                RequestPriority::default(),
+                None,
             );
 
             // Check that the limiter allows the first two requests.
@@ -463,6 +670,149 @@ mod tests {
         }
     }
 
+    /// Test that queued requests are sent in order of priority and dropped after their deadline.
+    #[tokio::test]
+    async fn test_request_priorities_and_deadlines() {
+        let chain_config = Arc::new(Config::mainnet().rapid_upgrade());
+        let fork_context = Arc::new(ForkContext::dummy::<Mainnet>(&chain_config, Phase::Phase0));
+        let mut limiter: SelfRateLimiter<AppRequestId, Mainnet> =
+            SelfRateLimiter::new(None, fork_context).unwrap();
+        let peer_id = PeerId::random();
+
+        let priorities = [
+            (RequestPriority::Normal, None),
+            (RequestPriority::Normal, None),
+            (RequestPriority::Low, Some(Duration::from_millis(10))),
+            (RequestPriority::Normal, None),
+            (RequestPriority::High, None),
+        ];
+
+        for (i, (priority, max_queue_time)) in priorities.into_iter().enumerate() {
+            let _ = limiter.allows(
+                peer_id,
+                AppRequestId::Application(i),
+                RequestType::Ping(Ping { data: i as u64 }),
+                priority,
+                max_queue_time,
+            );
+        }
+
+        // The first two requests are sent, the rest are queued by priority.
+        let queue = limiter
+            .delayed_requests
+            .get(&(peer_id, Protocol::Ping))
+            .unwrap();
+        let queued_ids = queue
+            .iter()
+            .map(|queued| queued.request_id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            queued_ids,
+            [4, 3, 2].map(AppRequestId::Application).to_vec(),
+        );
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let expired = futures::future::poll_fn(|cx| limiter.poll_expired(cx)).await;
+        assert_eq!(
+            expired,
+            (peer_id, AppRequestId::Application(2), Protocol::Ping),
+        );
+        assert_eq!(
+            limiter.delayed_requests[&(peer_id, Protocol::Ping)].len(),
+            2,
+        );
+    }
+
+    /// Test that deadlines are removed once their request leaves the queue, so that they cannot
+    /// drop a later request reusing the same ID.
+    #[tokio::test]
+    async fn test_deadlines_are_removed_with_their_request() {
+        let chain_config = Arc::new(Config::mainnet().rapid_upgrade());
+        let fork_context = Arc::new(ForkContext::dummy::<Mainnet>(&chain_config, Phase::Phase0));
+        let mut limiter: SelfRateLimiter<AppRequestId, Mainnet> =
+            SelfRateLimiter::new(None, fork_context).unwrap();
+        let peer_id = PeerId::random();
+        let max_queue_time = Some(Duration::from_millis(10));
+
+        let request =
+            |limiter: &mut SelfRateLimiter<AppRequestId, Mainnet>, id: usize, max_queue_time| {
+                let _ = limiter.allows(
+                    peer_id,
+                    AppRequestId::Application(id),
+                    RequestType::Ping(Ping { data: id as u64 }),
+                    RequestPriority::default(),
+                    max_queue_time,
+                );
+            };
+
+        for i in 1..=3 {
+            request(&mut limiter, i, max_queue_time);
+        }
+
+        // Cancelled and sent requests remove their deadline.
+        assert_eq!(limiter.queue_deadlines.len(), 1);
+        assert!(limiter.cancel_request(peer_id, AppRequestId::Application(3)));
+        assert!(limiter.queue_deadlines.is_empty());
+
+        request(&mut limiter, 4, max_queue_time);
+        limiter.request_completed(&peer_id, Protocol::Ping);
+        limiter.next_peer_request_ready(peer_id, Protocol::Ping);
+        assert_eq!(limiter.ready_requests.len(), 1);
+        assert!(limiter.queue_deadlines.is_empty());
+
+        // A request reusing the ID of the cancelled one is kept past the old deadline.
+        request(&mut limiter, 3, None);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let expired = futures::future::poll_fn(|cx| Poll::Ready(limiter.poll_expired(cx))).await;
+        assert!(expired.is_pending());
+        assert_eq!(
+            limiter.delayed_requests[&(peer_id, Protocol::Ping)][0].request_id,
+            AppRequestId::Application(3),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let chain_config = Arc::new(Config::mainnet().rapid_upgrade());
+        let fork_context = Arc::new(ForkContext::dummy::<Mainnet>(&chain_config, Phase::Phase0));
+        let mut limiter: SelfRateLimiter<AppRequestId, Mainnet> =
+            SelfRateLimiter::new(None, fork_context).unwrap();
+        let peer_id = PeerId::random();
+
+        for i in 1..=4 {
+            let _ = limiter.allows(
+                peer_id,
+                AppRequestId::Application(i),
+                RequestType::Ping(Ping { data: i as u64 }),
+                RequestPriority::default(),
+                None,
+            );
+        }
+
+        // Requests that were already sent are not held by the limiter.
+        assert!(!limiter.cancel_request(peer_id, AppRequestId::Application(1)));
+        assert!(limiter.cancel_request(peer_id, AppRequestId::Application(3)));
+        assert!(!limiter.cancel_request(peer_id, AppRequestId::Application(3)));
+
+        let queue = limiter
+            .delayed_requests
+            .get(&(peer_id, Protocol::Ping))
+            .unwrap();
+        assert_eq!(1, queue.len());
+        assert_eq!(queue[0].request_id, AppRequestId::Application(4));
+
+        // Cancelling a request that is ready to be sent releases its concurrent request slot.
+        limiter.request_completed(&peer_id, Protocol::Ping);
+        limiter.next_peer_request_ready(peer_id, Protocol::Ping);
+        assert_eq!(limiter.ready_requests.len(), 1);
+
+        assert!(limiter.cancel_request(peer_id, AppRequestId::Application(4)));
+        assert!(limiter.ready_requests.is_empty());
+        assert_eq!(limiter.active_requests[&peer_id][&Protocol::Ping], 1);
+    }
+
     #[tokio::test]
     async fn test_peer_disconnected() {
         let chain_config = Arc::new(Config::mainnet().rapid_upgrade());
@@ -478,6 +828,8 @@ mod tests {
                     peer,
                     AppRequestId::Application(i),
                     RequestType::Ping(Ping { data: i as u64 }),
+                    RequestPriority::default(),
+                    None,
                 );
 
                 // Check that the limiter allows the first two requests.
@@ -524,4 +876,45 @@ mod tests {
                 .contains_key(&(peer2, Protocol::Ping))
         );
     }
+
+    #[tokio::test]
+    async fn test_cancelling_a_queue_removes_its_delay() {
+        let config = OutboundRateLimiterConfig(RateLimiterConfig {
+            ping_quota: Quota::n_every(NonZeroU64::new(1).unwrap(), 2),
+            ..Default::default()
+        });
+        let chain_config = Arc::new(Config::mainnet().rapid_upgrade());
+        let fork_context = Arc::new(ForkContext::dummy::<Mainnet>(&chain_config, Phase::Phase0));
+        let mut limiter: SelfRateLimiter<AppRequestId, Mainnet> =
+            SelfRateLimiter::new(Some(config), fork_context).unwrap();
+        let peer_id = PeerId::random();
+
+        for i in 1..=2 {
+            let _ = limiter.allows(
+                peer_id,
+                AppRequestId::Application(i),
+                RequestType::Ping(Ping { data: i as u64 }),
+                RequestPriority::default(),
+                None,
+            );
+        }
+
+        assert_eq!(limiter.next_peer_request.len(), 1);
+
+        // Emptying the queue removes its delay, so a new queue gets a single delay.
+        assert!(limiter.cancel_request(peer_id, AppRequestId::Application(2)));
+        assert!(limiter.next_peer_request.is_empty());
+        assert!(limiter.next_peer_request_keys.is_empty());
+
+        let _ = limiter.allows(
+            peer_id,
+            AppRequestId::Application(3),
+            RequestType::Ping(Ping { data: 3 }),
+            RequestPriority::default(),
+            None,
+        );
+
+        assert_eq!(limiter.next_peer_request.len(), 1);
+        assert_eq!(limiter.next_peer_request_keys.len(), 1);
+    }
 }
@@ -16,6 +16,9 @@ pub type Id = usize;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AppRequestId {
     Application(Id),
+    /// A request sent through `Network::request` or `Network::request_stream`. Its responses are
+    /// delivered to the returned handle rather than as `NetworkEvent`s.
+    Client(Id),
     Internal,
 }
 
@@ -51,6 +54,22 @@ pub enum Response<P: Preset> {
     LightClientUpdatesByRange(Option<Arc<LightClientUpdate<P>>>),
 }
 
+impl<P: Preset> Response<P> {
+    /// Returns `true` if this response signals the end of a chunked response stream.
+    pub fn is_stream_termination(&self) -> bool {
+        matches!(
+            self,
+            Response::BlocksByRange(None)
+                | Response::BlobsByRange(None)
+                | Response::DataColumnsByRange(None)
+                | Response::BlocksByRoot(None)
+                | Response::BlobsByRoot(None)
+                | Response::DataColumnsByRoot(None)
+                | Response::LightClientUpdatesByRange(None)
+        )
+    }
+}
+
 impl<P: Preset> std::convert::From<Response<P>> for RpcResponse<P> {
     fn from(resp: Response<P>) -> RpcResponse<P> {
         match resp {
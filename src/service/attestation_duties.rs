@@ -0,0 +1,166 @@
+//! Short-lived attestation subnet subscriptions for validator duties.
+//!
+//! Aggregators of a slot must receive every attestation of their subnet, so the subnet is
+//! subscribed to `subscribe_slots_in_advance` slots before the duty and unsubscribed from once
+//! the duty slot is over, unless a later duty still needs it.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{Result, anyhow};
+use types::phase0::primitives::{Slot, SubnetId};
+
+use super::slot_clock::SlotClock;
+
+/// How early the subnets of attestation duties are subscribed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttestationDutyConfig {
+    /// The number of slots before an aggregation duty at which its subnet is subscribed to.
+    pub subscribe_slots_in_advance: u64,
+}
+
+impl Default for AttestationDutyConfig {
+    fn default() -> Self {
+        Self {
+            subscribe_slots_in_advance: 2,
+        }
+    }
+}
+
+/// A change of the subnets needed by attestation duties.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum DutyAction {
+    Subscribe(SubnetId),
+    Unsubscribe(SubnetId),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum ScheduledAction {
+    Subscribe(SubnetId),
+    /// Unsubscribe once the duty at `duty_slot` is over, unless a later duty needs the subnet.
+    Unsubscribe {
+        subnet_id: SubnetId,
+        duty_slot: Slot,
+    },
+}
+
+/// Schedules the subscriptions of the subnets of aggregation duties.
+pub(crate) struct AttestationDuties {
+    slot_clock: SlotClock,
+    config: AttestationDutyConfig,
+    /// Pending actions ordered by the slot at which they are due.
+    actions: BTreeSet<(Slot, ScheduledAction)>,
+    /// The slot of the last scheduled duty of each subnet that is needed by a duty.
+    last_duty_slots: HashMap<SubnetId, Slot>,
+}
+
+impl AttestationDuties {
+    pub fn new(slot_clock: SlotClock, config: AttestationDutyConfig) -> Self {
+        Self {
+            slot_clock,
+            config,
+            actions: BTreeSet::new(),
+            last_duty_slots: HashMap::new(),
+        }
+    }
+
+    pub fn slot_clock(&self) -> &SlotClock {
+        &self.slot_clock
+    }
+
+    /// Schedules the subscription to `subnet_id` for an aggregation duty at `slot`.
+    ///
+    /// Fails if the duty would never be over because `slot` is the last slot.
+    pub fn schedule(&mut self, subnet_id: SubnetId, slot: Slot) -> Result<()> {
+        let unsubscribe_slot = slot
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Attestation duty slot {slot} is out of range"))?;
+        let subscribe_slot = slot.saturating_sub(self.config.subscribe_slots_in_advance);
+
+        self.actions
+            .insert((subscribe_slot, ScheduledAction::Subscribe(subnet_id)));
+        self.actions.insert((
+            unsubscribe_slot,
+            ScheduledAction::Unsubscribe {
+                subnet_id,
+                duty_slot: slot,
+            },
+        ));
+
+        let last_duty_slot = self.last_duty_slots.entry(subnet_id).or_insert(slot);
+        *last_duty_slot = (*last_duty_slot).max(slot);
+
+        Ok(())
+    }
+
+    /// Returns `true` if a duty needs `subnet_id` now or in the future.
+    pub fn is_subnet_needed(&self, subnet_id: SubnetId) -> bool {
+        self.last_duty_slots.contains_key(&subnet_id)
+    }
+
+    /// Waits until a subnet must be subscribed to or unsubscribed from and returns the action, or
+    /// never resolves if there are no duties left.
+    ///
+    /// Cancel safe: an action is only removed once it is due.
+    pub async fn next_action(&mut self) -> DutyAction {
+        loop {
+            let Some((slot, _)) = self.actions.first() else {
+                return std::future::pending().await;
+            };
+
+            tokio::time::sleep(self.slot_clock.duration_to_slot(*slot)).await;
+
+            let (_, action) = self
+                .actions
+                .pop_first()
+                .expect("an action was pending before sleeping");
+
+            match action {
+                ScheduledAction::Subscribe(subnet_id) => return DutyAction::Subscribe(subnet_id),
+                ScheduledAction::Unsubscribe {
+                    subnet_id,
+                    duty_slot,
+                } => {
+                    if self.last_duty_slots.get(&subnet_id) == Some(&duty_slot) {
+                        self.last_duty_slots.remove(&subnet_id);
+                        return DutyAction::Unsubscribe(subnet_id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn subnets_are_kept_until_the_last_duty_is_over() {
+        // Genesis was long ago, so every duty is already due.
+        let slot_clock = SlotClock::new(0, Duration::from_secs(12));
+        let mut duties = AttestationDuties::new(slot_clock, AttestationDutyConfig::default());
+
+        duties.schedule(3, 10).expect("slot is in range");
+        duties.schedule(3, 12).expect("slot is in range");
+        duties.schedule(5, 11).expect("slot is in range");
+
+        assert_eq!(duties.next_action().await, DutyAction::Subscribe(3));
+        assert_eq!(duties.next_action().await, DutyAction::Subscribe(5));
+        assert_eq!(duties.next_action().await, DutyAction::Subscribe(3));
+        // The subnet 3 is still needed at slot 12 when the duty at slot 10 is over.
+        assert_eq!(duties.next_action().await, DutyAction::Unsubscribe(5));
+        assert!(duties.is_subnet_needed(3));
+        assert_eq!(duties.next_action().await, DutyAction::Unsubscribe(3));
+        assert!(!duties.is_subnet_needed(3));
+    }
+
+    #[test]
+    fn duties_at_the_last_slot_are_rejected() {
+        let slot_clock = SlotClock::new(0, Duration::from_secs(12));
+        let mut duties = AttestationDuties::new(slot_clock, AttestationDutyConfig::default());
+
+        assert!(duties.schedule(3, Slot::MAX).is_err());
+        assert!(!duties.is_subnet_needed(3));
+    }
+}
@@ -0,0 +1,173 @@
+//! Long-lived attestation subnets of the node.
+//!
+//! Every node is subscribed to `SUBNETS_PER_NODE` attestation subnets derived from its node id.
+//! The subnets rotate every `EPOCHS_PER_SUBNET_SUBSCRIPTION` epochs, at an epoch offset that also
+//! depends on the node id so that nodes do not all rotate at once.
+
+use std::collections::BTreeSet;
+use std::time::Instant;
+
+use anyhow::Result;
+use helper_functions::misc;
+use sha2::{Digest as _, Sha256};
+use typenum::Unsigned as _;
+use types::{
+    phase0::{
+        consts::AttestationSubnetCount,
+        primitives::{Epoch, H256, SubnetId},
+    },
+    preset::Preset,
+};
+
+use super::slot_clock::SlotClock;
+
+/// The number of epochs a node stays subscribed to its long-lived subnets.
+const EPOCHS_PER_SUBNET_SUBSCRIPTION: u64 = 256;
+
+/// The number of long-lived subnets a node is subscribed to.
+const SUBNETS_PER_NODE: u64 = 2;
+
+/// The number of extra node id bits used to shuffle nodes across subnets.
+const ATTESTATION_SUBNET_EXTRA_BITS: u32 = 0;
+
+/// Computes and rotates the long-lived attestation subnets of a node.
+pub(crate) struct AttestationSubnets {
+    node_id: [u8; 32],
+    slot_clock: SlotClock,
+    /// The subnets the node is currently subscribed to.
+    subscribed: BTreeSet<SubnetId>,
+    /// The epoch at which the subnets are next computed. `None` until they are first computed.
+    next_rotation: Option<Epoch>,
+}
+
+/// The long-lived subnets of a node, valid until `expiration_epoch`.
+#[derive(Debug)]
+pub(crate) struct SubnetRotation {
+    pub subscribe: Vec<SubnetId>,
+    pub unsubscribe: Vec<SubnetId>,
+    pub subnets: Vec<SubnetId>,
+    pub expiration_epoch: Epoch,
+    /// The start of `expiration_epoch`, until which peers on `subnets` are useful.
+    pub expiration: Instant,
+}
+
+impl AttestationSubnets {
+    pub fn new(node_id: [u8; 32], slot_clock: SlotClock) -> Self {
+        Self {
+            node_id,
+            slot_clock,
+            subscribed: BTreeSet::new(),
+            next_rotation: None,
+        }
+    }
+
+    /// Returns `true` if `subnet_id` is one of the current long-lived subnets.
+    pub fn is_subscribed(&self, subnet_id: SubnetId) -> bool {
+        self.subscribed.contains(&subnet_id)
+    }
+
+    /// Waits until the subnets change and returns the subnets to subscribe to and unsubscribe
+    /// from. Resolves immediately the first time it is called.
+    ///
+    /// Cancel safe: the subnets are only updated once the rotation is returned.
+    pub async fn next_rotation<P: Preset>(&mut self) -> Result<SubnetRotation> {
+        if let Some(epoch) = self.next_rotation {
+            tokio::time::sleep(self.slot_clock.duration_to_epoch::<P>(epoch)).await;
+        }
+
+        let epoch = self.slot_clock.current_epoch::<P>();
+        let expiration_epoch = next_rotation_epoch(self.node_id, epoch);
+
+        // Wait for the next rotation even if the subnets cannot be computed.
+        self.next_rotation = Some(expiration_epoch);
+
+        let subnets = compute_subscribed_subnets::<P>(self.node_id, epoch)?;
+
+        let subscribe = subnets
+            .iter()
+            .filter(|subnet_id| !self.subscribed.contains(subnet_id))
+            .copied()
+            .collect();
+
+        let unsubscribe = self
+            .subscribed
+            .iter()
+            .filter(|subnet_id| !subnets.contains(subnet_id))
+            .copied()
+            .collect();
+
+        self.subscribed = subnets.iter().copied().collect();
+
+        Ok(SubnetRotation {
+            subscribe,
+            unsubscribe,
+            subnets,
+            expiration_epoch,
+            expiration: self.slot_clock.epoch_start_instant::<P>(expiration_epoch),
+        })
+    }
+}
+
+/// Returns the long-lived subnets of the node with `node_id` at `epoch`, as specified by
+/// `compute_subscribed_subnets` of the phase0 networking specification.
+pub(crate) fn compute_subscribed_subnets<P: Preset>(
+    node_id: [u8; 32],
+    epoch: Epoch,
+) -> Result<Vec<SubnetId>> {
+    let subnet_count = AttestationSubnetCount::U64;
+    let prefix_bits =
+        subnet_count.next_power_of_two().trailing_zeros() + ATTESTATION_SUBNET_EXTRA_BITS;
+
+    let node_id_high = u64::from_be_bytes(node_id[..8].try_into().expect("node id has 32 bytes"));
+    let node_id_prefix = node_id_high >> (u64::BITS - prefix_bits);
+
+    let period = (epoch + node_offset(node_id)) / EPOCHS_PER_SUBNET_SUBSCRIPTION;
+    let permutation_seed = H256::from_slice(&Sha256::digest(period.to_le_bytes()));
+
+    let permutated_prefix =
+        misc::compute_shuffled_index::<P>(node_id_prefix, 1 << prefix_bits, permutation_seed)?;
+
+    Ok((0..SUBNETS_PER_NODE)
+        .map(|index| (permutated_prefix + index) % subnet_count)
+        .collect())
+}
+
+/// Returns the first epoch after `epoch` at which the subnets of the node with `node_id` rotate.
+fn next_rotation_epoch(node_id: [u8; 32], epoch: Epoch) -> Epoch {
+    let position = (epoch + node_offset(node_id)) % EPOCHS_PER_SUBNET_SUBSCRIPTION;
+    epoch + EPOCHS_PER_SUBNET_SUBSCRIPTION - position
+}
+
+/// Returns `node_id % EPOCHS_PER_SUBNET_SUBSCRIPTION`.
+fn node_offset(node_id: [u8; 32]) -> u64 {
+    node_id.iter().fold(0, |remainder, byte| {
+        (remainder * 256 + u64::from(*byte)) % EPOCHS_PER_SUBNET_SUBSCRIPTION
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::preset::Mainnet;
+
+    #[test]
+    fn subnets_are_stable_until_the_rotation_epoch() {
+        let mut node_id = [0; 32];
+        node_id[0] = 0b1010_1100;
+        node_id[31] = 10;
+
+        // The node offset is 10, so subnets rotate when `epoch + 10` is a multiple of 256.
+        assert_eq!(node_offset(node_id), 10);
+        assert_eq!(next_rotation_epoch(node_id, 0), 246);
+        assert_eq!(next_rotation_epoch(node_id, 246), 502);
+
+        let subnets = compute_subscribed_subnets::<Mainnet>(node_id, 0).unwrap();
+
+        assert_eq!(subnets.len(), SUBNETS_PER_NODE as usize);
+        assert_eq!((subnets[0] + 1) % AttestationSubnetCount::U64, subnets[1]);
+        assert_eq!(
+            compute_subscribed_subnets::<Mainnet>(node_id, 245).unwrap(),
+            subnets,
+        );
+    }
+}
@@ -0,0 +1,197 @@
+//! Scheduling of the gossip and ENR changes around fork boundaries.
+//!
+//! When fork transitions are enabled on [`Network`](super::Network), the topics of every upcoming
+//! fork are subscribed to `pre_subscribe_epochs` before the fork, the fork becomes current at its
+//! first slot and the topics of the previous fork are unsubscribed from `post_unsubscribe_epochs`
+//! after it.
+
+use std::collections::BTreeMap;
+
+use typenum::Unsigned as _;
+use types::{
+    nonstandard::Phase,
+    phase0::{
+        consts::FAR_FUTURE_EPOCH,
+        primitives::{Epoch, ForkDigest, Slot},
+    },
+    preset::Preset,
+};
+
+use super::slot_clock::SlotClock;
+use crate::types::ForkContext;
+
+/// Windows around fork boundaries in which the topics of both forks are subscribed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ForkTransitionConfig {
+    /// The number of epochs before a fork at which its topics are subscribed to.
+    pub pre_subscribe_epochs: u64,
+    /// The number of epochs after a fork at which the topics of the previous fork are
+    /// unsubscribed from.
+    pub post_unsubscribe_epochs: u64,
+}
+
+impl Default for ForkTransitionConfig {
+    fn default() -> Self {
+        Self {
+            pre_subscribe_epochs: 2,
+            post_unsubscribe_epochs: 2,
+        }
+    }
+}
+
+/// The steps of a fork transition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForkTransitionStage {
+    /// The topics of the fork have been subscribed to.
+    Subscribed,
+    /// The fork became the current fork and the ENR was updated.
+    Activated,
+    /// The topics of the forks preceding it have been unsubscribed from.
+    Unsubscribed,
+}
+
+/// A step of a fork transition performed by [`Network`](super::Network).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ForkTransition {
+    pub phase: Phase,
+    pub fork_digest: ForkDigest,
+    pub fork_epoch: Epoch,
+    pub stage: ForkTransitionStage,
+}
+
+/// Produces the fork transitions of the upcoming forks at their slots.
+pub(crate) struct ForkScheduler {
+    slot_clock: SlotClock,
+    config: ForkTransitionConfig,
+    /// Pending transitions ordered by the slot at which they are due.
+    transitions: BTreeMap<(Slot, ForkTransitionStage, Epoch), ForkTransition>,
+}
+
+impl ForkScheduler {
+    pub fn new<P: Preset>(
+        fork_context: &ForkContext,
+        slot_clock: SlotClock,
+        config: ForkTransitionConfig,
+    ) -> Self {
+        let mut scheduler = Self {
+            slot_clock,
+            config,
+            transitions: BTreeMap::new(),
+        };
+
+        let current_fork_epoch = fork_context.current_fork_epoch();
+
+        for fork_epoch in fork_context
+            .all_fork_epochs()
+            .into_iter()
+            .filter(|epoch| *epoch > current_fork_epoch)
+        {
+            scheduler.add_fork::<P>(fork_context, fork_epoch);
+        }
+
+        scheduler
+    }
+
+    /// Schedules the transition to the fork of `fork_context` at `fork_epoch`.
+    pub fn add_fork<P: Preset>(&mut self, fork_context: &ForkContext, fork_epoch: Epoch) {
+        if fork_epoch == FAR_FUTURE_EPOCH {
+            return;
+        }
+
+        let slots_per_epoch = P::SlotsPerEpoch::U64;
+        let fork_digest = fork_context.context_bytes(fork_epoch);
+        let phase = fork_context
+            .get_fork_from_context_bytes(fork_digest)
+            .unwrap_or_else(|| fork_context.chain_config().phase_at_epoch(fork_epoch));
+
+        let fork_slot = fork_epoch.saturating_mul(slots_per_epoch);
+
+        let subscribe_slot = fork_epoch
+            .saturating_sub(self.config.pre_subscribe_epochs)
+            .saturating_mul(slots_per_epoch);
+
+        let unsubscribe_slot = fork_epoch
+            .saturating_add(self.config.post_unsubscribe_epochs)
+            .saturating_mul(slots_per_epoch);
+
+        for (slot, stage) in [
+            (subscribe_slot, ForkTransitionStage::Subscribed),
+            (fork_slot, ForkTransitionStage::Activated),
+            (unsubscribe_slot, ForkTransitionStage::Unsubscribed),
+        ] {
+            let transition = ForkTransition {
+                phase,
+                fork_digest,
+                fork_epoch,
+                stage,
+            };
+
+            self.transitions
+                .insert((slot, stage, fork_epoch), transition);
+        }
+    }
+
+    /// Waits until the next transition is due and returns it, or never resolves if there are no
+    /// transitions left.
+    ///
+    /// Cancel safe: a transition is only removed once it is returned.
+    pub async fn next_transition(&mut self) -> ForkTransition {
+        let Some(((slot, _, _), _)) = self.transitions.first_key_value() else {
+            return std::future::pending().await;
+        };
+
+        tokio::time::sleep(self.slot_clock.duration_to_slot(*slot)).await;
+
+        self.transitions
+            .pop_first()
+            .map(|(_, transition)| transition)
+            .expect("a transition was pending before sleeping")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use types::{config::Config as ChainConfig, preset::Mainnet};
+
+    #[test]
+    fn transitions_are_ordered_by_slot_around_each_upcoming_fork() {
+        let mut chain_config = ChainConfig::mainnet();
+        chain_config.altair_fork_epoch = 10;
+        chain_config.bellatrix_fork_epoch = 11;
+        chain_config.capella_fork_epoch = FAR_FUTURE_EPOCH;
+        chain_config.deneb_fork_epoch = FAR_FUTURE_EPOCH;
+        chain_config.electra_fork_epoch = FAR_FUTURE_EPOCH;
+        chain_config.fulu_fork_epoch = FAR_FUTURE_EPOCH;
+        chain_config.gloas_fork_epoch = FAR_FUTURE_EPOCH;
+        chain_config.blob_schedule = vec![];
+
+        let chain_config = Arc::new(chain_config);
+        let fork_context = ForkContext::dummy::<Mainnet>(&chain_config, Phase::Phase0);
+        let scheduler = ForkScheduler::new::<Mainnet>(
+            &fork_context,
+            SlotClock::new(0, chain_config.slot_duration_ms),
+            ForkTransitionConfig::default(),
+        );
+
+        let transitions = scheduler
+            .transitions
+            .iter()
+            .map(|((slot, stage, _), transition)| (*slot / 32, *stage, transition.phase))
+            .collect::<Vec<_>>();
+
+        // The windows of close forks overlap, so Bellatrix is subscribed to before Altair starts.
+        assert_eq!(
+            transitions,
+            [
+                (8, ForkTransitionStage::Subscribed, Phase::Altair),
+                (9, ForkTransitionStage::Subscribed, Phase::Bellatrix),
+                (10, ForkTransitionStage::Activated, Phase::Altair),
+                (11, ForkTransitionStage::Activated, Phase::Bellatrix),
+                (12, ForkTransitionStage::Unsubscribed, Phase::Altair),
+                (13, ForkTransitionStage::Unsubscribed, Phase::Bellatrix),
+            ],
+        );
+    }
+}
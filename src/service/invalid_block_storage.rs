@@ -0,0 +1,486 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use libp2p::PeerId;
+use libp2p::gossipsub::{MessageId, TopicHash};
+use lru::LruCache;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::task_executor::TaskExecutor;
+use crate::types::{GossipKind, GossipTopic};
+
+/// Maximum combined size in bytes of the block and sidecar messages kept in memory while awaiting
+/// a validation result (64 MiB). The least recently received messages are dropped first.
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+/// Number of invalid messages that may wait for the writer before new ones are dropped.
+const WRITER_CHANNEL_SIZE: usize = 16;
+
+const SSZ_EXTENSION: &str = "ssz";
+const METADATA_EXTENSION: &str = "meta";
+
+/// A gossip message that has been handed to the application but not yet validated.
+struct PendingMessage {
+    topic: TopicHash,
+    source: PeerId,
+    data: Vec<u8>,
+    received_at: SystemTime,
+}
+
+/// A message to be written to disk by the writer task.
+struct InvalidMessage {
+    topic: TopicHash,
+    source: PeerId,
+    message_id: MessageId,
+    data: Vec<u8>,
+    received_at: SystemTime,
+    reason: String,
+}
+
+/// An entry written to disk by this node.
+struct StoredMessage {
+    ssz_path: PathBuf,
+    metadata_path: PathBuf,
+    size: u64,
+}
+
+/// Keeps beacon blocks, blob sidecars and data column sidecars that failed decoding or were
+/// rejected by the application and hands them to a blocking writer task, which writes them to a
+/// directory for later inspection.
+///
+/// Every message is stored as a `<name>.ssz` file holding the raw (decompressed) SSZ bytes and a
+/// `<name>.meta` file describing where the message came from and why it was stored.
+pub struct InvalidBlockStorage {
+    /// Block and sidecar messages awaiting a validation result from the application.
+    pending: LruCache<MessageId, PendingMessage>,
+    /// Combined size of the messages in `pending`.
+    pending_bytes: usize,
+    /// Messages to be written to disk.
+    writer: mpsc::Sender<InvalidMessage>,
+}
+
+impl InvalidBlockStorage {
+    /// Spawns the writer task, which keeps at most `max_messages` messages and `max_bytes` bytes
+    /// in `dir`.
+    pub fn new(executor: &TaskExecutor, dir: PathBuf, max_messages: usize, max_bytes: u64) -> Self {
+        let (writer, mut receiver) = mpsc::channel(WRITER_CHANNEL_SIZE);
+
+        executor.spawn_blocking(
+            move || {
+                let mut writer = InvalidBlockWriter::new(dir, max_messages, max_bytes);
+
+                while let Some(message) = receiver.blocking_recv() {
+                    writer.store(&message);
+                }
+            },
+            "invalid_block_storage",
+        );
+
+        Self::with_writer(writer)
+    }
+
+    fn with_writer(writer: mpsc::Sender<InvalidMessage>) -> Self {
+        Self {
+            pending: LruCache::unbounded(),
+            pending_bytes: 0,
+            writer,
+        }
+    }
+
+    /// Returns `true` if messages published on `topic` are kept by this storage.
+    pub fn is_tracked_topic(topic: &TopicHash) -> bool {
+        GossipTopic::decode(topic.as_str()).is_ok_and(|topic| {
+            matches!(
+                topic.kind(),
+                GossipKind::BeaconBlock
+                    | GossipKind::BlobSidecar(_)
+                    | GossipKind::DataColumnSidecar(_)
+            )
+        })
+    }
+
+    /// Keeps the raw data of a successfully decoded message until the application reports a
+    /// validation result for it.
+    pub fn insert_pending(
+        &mut self,
+        message_id: MessageId,
+        topic: TopicHash,
+        source: PeerId,
+        data: Vec<u8>,
+    ) {
+        if !Self::is_tracked_topic(&topic) || data.len() > MAX_PENDING_BYTES {
+            return;
+        }
+
+        while self.pending_bytes + data.len() > MAX_PENDING_BYTES {
+            let Some((_, message)) = self.pending.pop_lru() else {
+                break;
+            };
+
+            self.pending_bytes -= message.data.len();
+        }
+
+        self.pending_bytes += data.len();
+
+        let replaced = self.pending.put(
+            message_id,
+            PendingMessage {
+                topic,
+                source,
+                data,
+                received_at: SystemTime::now(),
+            },
+        );
+
+        if let Some(message) = replaced {
+            self.pending_bytes -= message.data.len();
+        }
+    }
+
+    /// Handles the validation result of a message, storing it if it was rejected.
+    pub fn on_validation_result(&mut self, message_id: &MessageId, rejected: bool) {
+        let Some(message) = self.pending.pop(message_id) else {
+            return;
+        };
+
+        self.pending_bytes -= message.data.len();
+
+        if rejected {
+            self.send(InvalidMessage {
+                topic: message.topic,
+                source: message.source,
+                message_id: message_id.clone(),
+                data: message.data,
+                received_at: message.received_at,
+                reason: "rejected by application".to_owned(),
+            });
+        }
+    }
+
+    /// Stores a message that could not be decoded.
+    pub fn store_undecodable(
+        &mut self,
+        topic: &TopicHash,
+        source: &PeerId,
+        message_id: &MessageId,
+        data: &[u8],
+        error: &str,
+    ) {
+        if !Self::is_tracked_topic(topic) {
+            return;
+        }
+
+        self.send(InvalidMessage {
+            topic: topic.clone(),
+            source: *source,
+            message_id: message_id.clone(),
+            data: data.to_vec(),
+            received_at: SystemTime::now(),
+            reason: format!("decoding failed: {error}"),
+        });
+    }
+
+    fn send(&self, message: InvalidMessage) {
+        if let Err(error) = self.writer.try_send(message) {
+            warn!(%error, "Could not queue invalid gossip message for storage");
+        }
+    }
+}
+
+/// Writes invalid messages to disk and prunes the oldest ones. Runs on a blocking thread.
+struct InvalidBlockWriter {
+    /// Directory the messages are written to.
+    dir: PathBuf,
+    /// Maximum number of messages kept on disk.
+    max_messages: usize,
+    /// Maximum combined size of the files kept on disk.
+    max_bytes: u64,
+    /// Messages currently on disk, oldest first.
+    stored: VecDeque<StoredMessage>,
+    /// Combined size of the files in `stored`.
+    stored_bytes: u64,
+}
+
+impl InvalidBlockWriter {
+    fn new(dir: PathBuf, max_messages: usize, max_bytes: u64) -> Self {
+        let stored = existing_messages(&dir);
+        let stored_bytes = stored.iter().map(|message| message.size).sum();
+
+        let mut writer = Self {
+            dir,
+            max_messages,
+            max_bytes,
+            stored,
+            stored_bytes,
+        };
+
+        writer.prune(0);
+        writer
+    }
+
+    fn store(&mut self, message: &InvalidMessage) {
+        let InvalidMessage {
+            topic,
+            source,
+            message_id,
+            data,
+            received_at,
+            reason,
+        } = message;
+
+        let received_at_ms = received_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        let kind = GossipTopic::decode(topic.as_str())
+            .map(|topic| topic.kind().to_string())
+            .unwrap_or_else(|_| "unknown".to_owned());
+
+        let id = hex::encode(&message_id.0);
+        let name = format!("{received_at_ms}_{kind}_{id}");
+        let ssz_path = self.dir.join(&name).with_extension(SSZ_EXTENSION);
+        let metadata_path = self.dir.join(&name).with_extension(METADATA_EXTENSION);
+
+        let mut metadata = String::new();
+        let _ = writeln!(metadata, "topic: {topic}");
+        let _ = writeln!(metadata, "source: {source}");
+        let _ = writeln!(metadata, "message_id: {id}");
+        let _ = writeln!(metadata, "received_at_ms: {received_at_ms}");
+        let _ = writeln!(metadata, "reason: {reason}");
+
+        let size = (data.len() + metadata.len()) as u64;
+
+        if size > self.max_bytes {
+            warn!(
+                size,
+                max_bytes = self.max_bytes,
+                "Invalid gossip message too large to store"
+            );
+            return;
+        }
+
+        let write_to_disk = || -> Result<()> {
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(&ssz_path, data)?;
+            std::fs::write(&metadata_path, &metadata)?;
+            Ok(())
+        };
+
+        match write_to_disk() {
+            Ok(_) => {
+                debug!(file = ?ssz_path, %reason, "Stored invalid gossip message");
+
+                self.prune(size);
+                self.stored.push_back(StoredMessage {
+                    ssz_path,
+                    metadata_path,
+                    size,
+                });
+                self.stored_bytes += size;
+            }
+            Err(e) => {
+                warn!(file = ?ssz_path, error = %e, "Could not store invalid gossip message");
+            }
+        }
+    }
+
+    /// Removes the oldest messages until there is room for one more message of `incoming` bytes.
+    fn prune(&mut self, incoming: u64) {
+        let reserved = usize::from(incoming > 0);
+
+        while self.stored.len() + reserved > self.max_messages
+            || self.stored_bytes + incoming > self.max_bytes
+        {
+            let Some(message) = self.stored.pop_front() else {
+                break;
+            };
+
+            self.stored_bytes -= message.size;
+
+            for path in [&message.ssz_path, &message.metadata_path] {
+                if let Err(e) = std::fs::remove_file(path) {
+                    debug!(file = ?path, error = %e, "Could not remove stored invalid gossip message");
+                }
+            }
+        }
+    }
+}
+
+/// Collects messages left in `dir` by previous runs, oldest first.
+fn existing_messages(dir: &Path) -> VecDeque<StoredMessage> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return VecDeque::new();
+    };
+
+    let mut ssz_paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == SSZ_EXTENSION))
+        .collect::<Vec<_>>();
+
+    // File names start with the receive time, so ordering by name orders by age.
+    ssz_paths.sort();
+
+    ssz_paths
+        .into_iter()
+        .map(|ssz_path| {
+            let metadata_path = ssz_path.with_extension(METADATA_EXTENSION);
+            let size = [&ssz_path, &metadata_path]
+                .into_iter()
+                .filter_map(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len())
+                .sum();
+
+            StoredMessage {
+                ssz_path,
+                metadata_path,
+                size,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::GossipEncoding;
+    use types::phase0::primitives::ForkDigest;
+
+    /// Maximum combined size in bytes used by tests that only limit the number of messages.
+    const MAX_STORED_BYTES: u64 = 512 * 1024 * 1024;
+
+    fn block_topic() -> TopicHash {
+        TopicHash::from_raw(
+            GossipTopic::new(
+                GossipKind::BeaconBlock,
+                GossipEncoding::default(),
+                ForkDigest::zero(),
+            )
+            .to_string(),
+        )
+    }
+
+    fn undecodable_block(id: u8) -> InvalidMessage {
+        InvalidMessage {
+            topic: block_topic(),
+            source: PeerId::random(),
+            message_id: MessageId::new(&[id]),
+            data: vec![id],
+            received_at: SystemTime::now(),
+            reason: "decoding failed: bad ssz".to_owned(),
+        }
+    }
+
+    fn stored_files(dir: &Path, extension: &str) -> usize {
+        std::fs::read_dir(dir)
+            .expect("directory exists")
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == extension))
+            .count()
+    }
+
+    #[test]
+    fn queues_rejected_blocks_for_storage() {
+        let (sender, mut receiver) = mpsc::channel(WRITER_CHANNEL_SIZE);
+        let mut storage = InvalidBlockStorage::with_writer(sender);
+        let source = PeerId::random();
+
+        storage.insert_pending(MessageId::new(&[1]), block_topic(), source, vec![1, 2, 3]);
+        storage.insert_pending(MessageId::new(&[2]), block_topic(), source, vec![4, 5, 6]);
+
+        storage.on_validation_result(&MessageId::new(&[1]), false);
+        storage.on_validation_result(&MessageId::new(&[2]), true);
+
+        let message = receiver.try_recv().expect("rejected block is queued");
+
+        assert_eq!(message.message_id, MessageId::new(&[2]));
+        assert_eq!(message.source, source);
+        assert_eq!(message.data, vec![4, 5, 6]);
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(storage.pending_bytes, 0);
+    }
+
+    #[test]
+    fn ignores_untracked_topics() {
+        let (sender, mut receiver) = mpsc::channel(WRITER_CHANNEL_SIZE);
+        let mut storage = InvalidBlockStorage::with_writer(sender);
+        let topic = TopicHash::from_raw(
+            GossipTopic::new(
+                GossipKind::VoluntaryExit,
+                GossipEncoding::default(),
+                ForkDigest::zero(),
+            )
+            .to_string(),
+        );
+
+        storage.store_undecodable(&topic, &PeerId::random(), &MessageId::new(&[1]), &[1], "");
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn drops_least_recent_pending_messages_over_byte_limit() {
+        let (sender, _receiver) = mpsc::channel(WRITER_CHANNEL_SIZE);
+        let mut storage = InvalidBlockStorage::with_writer(sender);
+        let half = MAX_PENDING_BYTES / 2;
+
+        for id in 0..3_u8 {
+            storage.insert_pending(
+                MessageId::new(&[id]),
+                block_topic(),
+                PeerId::random(),
+                vec![0; half],
+            );
+        }
+
+        assert_eq!(storage.pending.len(), 2);
+        assert_eq!(storage.pending_bytes, MAX_PENDING_BYTES);
+        assert!(!storage.pending.contains(&MessageId::new(&[0])));
+    }
+
+    #[test]
+    fn writes_messages_with_metadata() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut writer = InvalidBlockWriter::new(dir.path().to_path_buf(), 2, MAX_STORED_BYTES);
+        let message = undecodable_block(2);
+
+        writer.store(&message);
+
+        assert_eq!(stored_files(dir.path(), SSZ_EXTENSION), 1);
+        assert_eq!(stored_files(dir.path(), METADATA_EXTENSION), 1);
+
+        let metadata =
+            std::fs::read_to_string(&writer.stored[0].metadata_path).expect("metadata is written");
+
+        assert!(metadata.contains(&format!("source: {}", message.source)));
+        assert!(metadata.contains("message_id: 02"));
+        assert!(metadata.contains("reason: decoding failed: bad ssz"));
+        assert_eq!(
+            std::fs::read(&writer.stored[0].ssz_path).expect("ssz is written"),
+            vec![2],
+        );
+    }
+
+    #[test]
+    fn prunes_oldest_messages_over_count_limit() {
+        let dir = tempfile::tempdir().expect("temp dir");
+        let mut writer = InvalidBlockWriter::new(dir.path().to_path_buf(), 2, MAX_STORED_BYTES);
+
+        for id in 0..4_u8 {
+            writer.store(&undecodable_block(id));
+        }
+
+        assert_eq!(writer.stored.len(), 2);
+        assert_eq!(stored_files(dir.path(), SSZ_EXTENSION), 2);
+        assert_eq!(stored_files(dir.path(), METADATA_EXTENSION), 2);
+
+        // Files left by a previous run count towards the limits.
+        let writer = InvalidBlockWriter::new(dir.path().to_path_buf(), 1, MAX_STORED_BYTES);
+
+        assert_eq!(writer.stored.len(), 1);
+        assert_eq!(stored_files(dir.path(), SSZ_EXTENSION), 1);
+    }
+}
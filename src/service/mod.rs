@@ -1,30 +1,49 @@
+use self::attestation_duties::{AttestationDuties, AttestationDutyConfig, DutyAction};
+use self::attestation_subnets::{AttestationSubnets, SubnetRotation};
+use self::fork_scheduler::{
+    ForkScheduler, ForkTransition, ForkTransitionConfig, ForkTransitionStage,
+};
 use self::gossip_cache::GossipCache;
+use self::invalid_block_storage::InvalidBlockStorage;
+use self::rpc_client::{ResponseFuture, ResponseStream, RpcClient};
+use self::slot_clock::SlotClock;
+use self::status_policy::{StatusClassification, StatusPolicy};
 use crate::EnrExt;
 use crate::config::{GossipsubConfigParams, NetworkLoad, gossipsub_config};
 use crate::discovery::{
     DiscoveredPeers, Discovery, FIND_NODE_QUERY_CLOSEST_PEERS, subnet_predicate,
 };
+use crate::peer_manager::peerdb::manual_bans::Ban;
+use crate::peer_manager::peerdb::snapshot::{BanList, PeerDBSnapshot, PeerDBWriter};
 use crate::peer_manager::{
     ConnectionDirection, PeerManager, PeerManagerEvent, config::Config as PeerManagerCfg,
     peerdb::score::PeerAction, peerdb::score::ReportSource,
 };
-use crate::peer_manager::{MIN_OUTBOUND_ONLY_FACTOR, PEER_EXCESS_FACTOR, PRIORITY_PEER_EXCESS};
+use crate::peer_manager::{
+    MIN_OUTBOUND_ONLY_FACTOR, PEER_EXCESS_FACTOR, PRIORITY_PEER_EXCESS, PROPOSER_ONLY_TARGET_PEERS,
+};
+use crate::rpc::config::{
+    InboundRateLimiterConfig, OutboundRateLimiterConfig, PeerQuotaMultipliers,
+};
 use crate::rpc::methods::MetadataRequest;
 use crate::rpc::{
-    GoodbyeReason, HandlerErr, InboundRequestId, Protocol, RPC, RPCError, RPCMessage, RPCReceived,
-    RequestType, ResponseTermination, RpcResponse, RpcSuccessResponse,
+    DataAvailability, GoodbyeReason, HandlerErr, InboundRequestId, MetaData, Protocol, RPC,
+    RPCError, RPCMessage, RPCReceived, RequestPriority, RequestType, ResponseTermination,
+    RpcResponse, RpcSuccessResponse, StatusMessage,
 };
 use crate::types::{
     EnrForkId, ForkContext, GossipEncoding, GossipKind, GossipTopic, SnappyTransform, Subnet,
-    SubnetDiscovery, all_topics_at_fork, core_topics_to_subscribe, is_fork_non_core_topic,
-    subnet_from_topic_hash,
+    SubnetDiscovery, SyncState, all_topics_at_fork, core_topics_to_subscribe,
+    is_fork_non_core_topic, subnet_from_topic_hash,
 };
 use crate::{Enr, NetworkGlobals, PubsubMessage, TopicHash, metrics};
-use crate::{Eth2Enr, task_executor};
+use crate::{Eth2Enr, ShutdownReason, task_executor};
 use anyhow::{Error, Result, anyhow};
 use api_types::{AppRequestId, Response};
 use futures::stream::StreamExt;
 use gossipsub_scoring_parameters::{PeerScoreSettings, peer_gossip_thresholds};
+use ipnet::IpNet;
+use libp2p::core::transport::ListenerId;
 use libp2p::gossipsub::{
     self, IdentTopic as Topic, MessageAcceptance, MessageAuthenticity, MessageId, PublishError,
     TopicScoreParams,
@@ -36,6 +55,7 @@ use libp2p::swarm::{NetworkBehaviour, Swarm, SwarmEvent};
 use libp2p::upnp::tokio::Behaviour as Upnp;
 use libp2p::{PeerId, SwarmBuilder, identify};
 use logging::exception;
+use std::collections::HashSet;
 use std::num::{NonZeroU8, NonZeroUsize};
 use std::path::PathBuf;
 use std::pin::Pin;
@@ -43,6 +63,7 @@ use std::sync::Arc;
 use std::time::Duration;
 use std::usize;
 use std_ext::ArcExt as _;
+use tokio::sync::watch;
 use tracing::{debug, error, info, trace, warn};
 use typenum::Unsigned as _;
 
@@ -51,20 +72,40 @@ use types::{
     config::Config as ChainConfig,
     nonstandard::Phase,
     phase0::{
-        consts::AttestationSubnetCount,
-        primitives::{ForkDigest, Slot},
+        consts::{AttestationSubnetCount, FAR_FUTURE_EPOCH},
+        primitives::{Epoch, ForkDigest, Slot, SubnetId},
     },
     preset::Preset,
 };
 use utils::{Context as ServiceContext, build_transport, strip_peer_id};
 
 pub mod api_types;
+pub mod attestation_duties;
+mod attestation_subnets;
+pub mod fork_scheduler;
 mod gossip_cache;
 pub mod gossipsub_scoring_parameters;
+mod invalid_block_storage;
+pub mod rpc_client;
+mod slot_clock;
+pub mod status_policy;
 pub mod utils;
 
 const MAX_IDENTIFY_ADDRESSES: usize = 10;
 
+/// The maximum time `Network::shutdown` waits for peers to disconnect.
+const NETWORK_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The time `Network::shutdown` leaves to deliver the errors of inbound requests that were not
+/// answered in time.
+const INBOUND_REQUEST_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often good peers are persisted to disk when `persist_peer_db` is enabled.
+const PEERDB_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(300);
+
+/// The maximum number of peers kept in a peer snapshot.
+const MAX_SNAPSHOT_PEERS: usize = 500;
+
 /// The types of events than can be obtained from polling the behaviour.
 #[derive(Debug)]
 pub enum NetworkEvent<P: Preset> {
@@ -115,11 +156,13 @@ pub enum NetworkEvent<P: Preset> {
     ZeroListeners,
     /// A peer has an updated custody group count from MetaData.
     PeerUpdatedCustodyGroupCount(PeerId),
+    /// A step of a scheduled fork transition has been performed.
+    ForkTransition(ForkTransition),
 }
 
 pub type Gossipsub = gossipsub::Behaviour<SnappyTransform, SubscriptionFilter>;
 pub type SubscriptionFilter =
-    gossipsub::MaxCountSubscriptionFilter<gossipsub::WhitelistSubscriptionFilter>;
+    gossipsub::MaxCountSubscriptionFilter<utils::SharedWhitelistSubscriptionFilter>;
 
 #[derive(NetworkBehaviour)]
 pub(crate) struct Behaviour<P>
@@ -172,6 +215,38 @@ pub struct Network<P: Preset> {
     /// The interval for updating gossipsub scores
     update_gossipsub_scores: tokio::time::Interval,
     gossip_cache: GossipCache,
+    /// Sync state updates, watched only when the node should shut down once synced.
+    shutdown_after_sync: Option<watch::Receiver<SyncState>>,
+    /// Used to request a shutdown of the node.
+    executor: task_executor::TaskExecutor,
+    /// The interval for persisting good peers to disk, if enabled.
+    peer_db_snapshot_interval: Option<tokio::time::Interval>,
+    /// Whether good peers are persisted.
+    persist_peer_db: bool,
+    /// Whether the ban list is persisted whenever the bans change.
+    persist_ban_list: bool,
+    /// Writes good peers and the ban list to disk, if either is persisted.
+    peer_db_writer: Option<PeerDBWriter>,
+    /// Listeners opened by `start`, closed on shutdown.
+    listener_ids: Vec<ListenerId>,
+    /// Storage for blocks and sidecars that failed decoding or validation, if enabled.
+    invalid_block_storage: Option<InvalidBlockStorage>,
+    /// Requests whose responses are delivered to handles.
+    rpc_client: RpcClient<P>,
+    /// Classifies peers by their `Status`, if registered.
+    status_policy: Option<Box<dyn StatusPolicy>>,
+    /// Our latest `Status`, compared against the `Status` of peers by `status_policy`.
+    local_status: Option<StatusMessage>,
+    /// Multipliers of the RPC rate limiter quotas of peer classes.
+    peer_quota_multipliers: PeerQuotaMultipliers,
+    /// Performs the fork transitions of upcoming forks, if enabled.
+    fork_scheduler: Option<ForkScheduler>,
+    /// Rotates the long-lived attestation subnets, if enabled.
+    attestation_subnets: Option<AttestationSubnets>,
+    /// Subscribes to the subnets of aggregation duties, if enabled.
+    attestation_duties: Option<AttestationDuties>,
+    /// The gossipsub topic whitelist, extended when forks are scheduled at runtime.
+    whitelist_filter: utils::SharedWhitelistSubscriptionFilter,
     /// This node's PeerId.
     pub local_peer_id: PeerId,
 }
@@ -216,8 +291,13 @@ impl<P: Preset> Network<P> {
         )?;
 
         // Construct the metadata
-        let meta_data =
-            utils::load_or_build_metadata(config.network_dir.as_deref(), custody_group_count_opt);
+        let (attnets, syncnets) = config.initial_subnet_bitfields();
+        let meta_data = utils::load_or_build_metadata(
+            config.network_dir.as_deref(),
+            custody_group_count_opt,
+            attnets,
+            syncnets,
+        );
         let seq_number = meta_data.seq_number();
         let globals = NetworkGlobals::new::<P>(
             chain_config.clone_arc(),
@@ -277,7 +357,7 @@ impl<P: Preset> Network<P> {
 
         let local_peer_id = network_globals.local_peer_id();
 
-        let (gossipsub, update_gossipsub_scores) = {
+        let (gossipsub, update_gossipsub_scores, whitelist_filter) = {
             let thresholds = peer_gossip_thresholds();
 
             // Prepare scoring parameters
@@ -340,13 +420,15 @@ impl<P: Preset> Network<P> {
 
             let possible_fork_digests = ctx.fork_context.all_fork_digests();
 
+            let whitelist_filter = utils::create_whitelist_filter(
+                possible_fork_digests,
+                &chain_config,
+                AttestationSubnetCount::U64,
+                SyncCommitteeSubnetCount::U64,
+            );
+
             let filter = gossipsub::MaxCountSubscriptionFilter {
-                filter: utils::create_whitelist_filter(
-                    possible_fork_digests,
-                    &chain_config,
-                    AttestationSubnetCount::U64,
-                    SyncCommitteeSubnetCount::U64,
-                ),
+                filter: whitelist_filter.clone(),
                 // during a fork we subscribe to both the old and new topics
                 max_subscribed_topics: max_topics_at_any_fork * 4,
                 // 424 in theory = (64 attestation + 4 sync committee + 7 core topics + 9 blob topics + 128 column topics) * 2
@@ -391,7 +473,7 @@ impl<P: Preset> Network<P> {
                 }
             }
 
-            (gossipsub, update_gossipsub_scores)
+            (gossipsub, update_gossipsub_scores, whitelist_filter)
         };
 
         let eth2_rpc = RPC::new(
@@ -399,7 +481,10 @@ impl<P: Preset> Network<P> {
             ctx.fork_context.clone(),
             config.enable_light_client_server,
             config.inbound_rate_limiter_config.clone(),
+            config.inbound_byte_quota_config.clone(),
             config.outbound_rate_limiter_config.clone(),
+            config.rpc_handler_config.clone(),
+            config.inbound_scheduler_config,
             seq_number,
         );
 
@@ -435,12 +520,19 @@ impl<P: Preset> Network<P> {
             identify::Behaviour::new(identify_config)
         };
 
+        // Proposer-only nodes skip attestation and sync committee duties and need fewer peers.
+        let target_peers = if config.proposer_only {
+            config.target_peers.min(PROPOSER_ONLY_TARGET_PEERS)
+        } else {
+            config.target_peers
+        };
+
         let peer_manager = {
             let peer_manager_cfg = PeerManagerCfg {
                 discovery_enabled: !config.disable_discovery,
                 quic_enabled: !config.disable_quic_support,
                 metrics_enabled: config.metrics_enabled,
-                target_peer_count: config.target_peers,
+                target_peer_count: target_peers,
                 ..Default::default()
             };
             PeerManager::new::<P>(peer_manager_cfg, network_globals.clone())?
@@ -451,16 +543,15 @@ impl<P: Preset> Network<P> {
                 .with_max_pending_incoming(Some(5))
                 .with_max_pending_outgoing(Some(16))
                 .with_max_established_incoming(Some(
-                    (config.target_peers as f32
-                        * (1.0 + PEER_EXCESS_FACTOR - MIN_OUTBOUND_ONLY_FACTOR))
+                    (target_peers as f32 * (1.0 + PEER_EXCESS_FACTOR - MIN_OUTBOUND_ONLY_FACTOR))
                         .ceil() as u32,
                 ))
                 .with_max_established_outgoing(Some(
-                    (config.target_peers as f32 * (1.0 + PEER_EXCESS_FACTOR)).ceil() as u32,
+                    (target_peers as f32 * (1.0 + PEER_EXCESS_FACTOR)).ceil() as u32,
                 ))
                 .with_max_established(Some(
-                    (config.target_peers as f32 * (1.0 + PEER_EXCESS_FACTOR + PRIORITY_PEER_EXCESS))
-                        .ceil() as u32,
+                    (target_peers as f32 * (1.0 + PEER_EXCESS_FACTOR + PRIORITY_PEER_EXCESS)).ceil()
+                        as u32,
                 ))
                 .with_max_established_per_peer(Some(1));
 
@@ -499,7 +590,7 @@ impl<P: Preset> Network<P> {
         // sets up the libp2p swarm.
 
         let swarm = {
-            let config = libp2p::swarm::Config::with_executor(Executor(executor))
+            let config = libp2p::swarm::Config::with_executor(Executor(executor.clone()))
                 .with_notify_handler_buffer_size(NonZeroUsize::new(7).expect("Not zero"))
                 .with_per_connection_event_buffer_size(4)
                 .with_idle_connection_timeout(Duration::from_secs(10)) // Other clients can timeout
@@ -528,6 +619,31 @@ impl<P: Preset> Network<P> {
             }
         };
 
+        let shutdown_after_sync = config
+            .shutdown_after_sync
+            .then(|| network_globals.watch_sync_state());
+
+        let peer_db_snapshot_interval = (config.persist_peer_db && config.network_dir.is_some())
+            .then(|| {
+                let start = tokio::time::Instant::now() + PEERDB_SNAPSHOT_INTERVAL;
+                tokio::time::interval_at(start, PEERDB_SNAPSHOT_INTERVAL)
+            });
+
+        let peer_db_writer = config
+            .network_dir
+            .clone()
+            .filter(|_| config.persist_peer_db || config.persist_ban_list)
+            .map(|dir| PeerDBWriter::new(&executor, dir));
+
+        let invalid_block_storage = config.invalid_block_storage.clone().map(|dir| {
+            InvalidBlockStorage::new(
+                &executor,
+                dir,
+                config.invalid_block_storage_max_messages,
+                config.invalid_block_storage_max_bytes,
+            )
+        });
+
         let mut network = Network {
             swarm,
             network_globals,
@@ -537,11 +653,31 @@ impl<P: Preset> Network<P> {
             score_settings,
             update_gossipsub_scores,
             gossip_cache,
+            shutdown_after_sync,
+            executor,
+            peer_db_snapshot_interval,
+            persist_peer_db: config.persist_peer_db,
+            persist_ban_list: config.persist_ban_list,
+            peer_db_writer,
+            listener_ids: Vec::new(),
+            invalid_block_storage,
+            rpc_client: RpcClient::default(),
+            status_policy: None,
+            local_status: None,
+            peer_quota_multipliers: config.peer_quota_multipliers.clone(),
+            fork_scheduler: None,
+            attestation_subnets: None,
+            attestation_duties: None,
+            whitelist_filter,
             local_peer_id,
         };
 
         network.start(&config).await?;
 
+        if config.subscribes_to_all_subnets() {
+            network.subscribe_to_all_subnets();
+        }
+
         let network_globals = network.network_globals.clone();
 
         Ok((network, network_globals))
@@ -576,7 +712,8 @@ impl<P: Preset> Network<P> {
             }
 
             match self.swarm.listen_on(listen_multiaddr.clone()) {
-                Ok(_) => {
+                Ok(listener_id) => {
+                    self.listener_ids.push(listener_id);
                     let mut log_address = listen_multiaddr;
                     log_address.push(MProtocol::P2p(enr.peer_id()));
                     info!(address = %log_address, "Listening established");
@@ -658,9 +795,44 @@ impl<P: Preset> Network<P> {
             }
         }
 
+        // keep peers banned before the last shutdown banned until their ban expires
+        if config.persist_ban_list {
+            if let Some(ban_list) = config
+                .network_dir
+                .as_deref()
+                .and_then(BanList::load_from_disk)
+            {
+                self.peer_manager_mut().restore_ban_list(ban_list);
+            }
+        }
+
+        // warm-start with the peers we were connected to before the last shutdown
+        if config.persist_peer_db {
+            if let Some(snapshot) = config
+                .network_dir
+                .as_deref()
+                .and_then(PeerDBSnapshot::load_from_disk)
+            {
+                self.peer_manager_mut().dial_snapshot_peers(snapshot);
+            }
+        }
+
         let mut subscribed_topics: Vec<GossipKind> = vec![];
 
         for topic_kind in &config.topics {
+            let subnet = match topic_kind {
+                GossipKind::Attestation(subnet_id) => Some(Subnet::Attestation(*subnet_id)),
+                GossipKind::SyncCommitteeMessage(subnet_id) => {
+                    Some(Subnet::SyncCommittee(*subnet_id))
+                }
+                _ => None,
+            };
+
+            if subnet.is_some_and(|subnet| self.is_proposer_only_excluded_subnet(subnet)) {
+                debug!(topic = %topic_kind, "Proposer-only node, not subscribing to topic");
+                continue;
+            }
+
             if self.subscribe_kind(topic_kind.clone()) {
                 subscribed_topics.push(topic_kind.clone());
             } else {
@@ -779,38 +951,12 @@ impl<P: Preset> Network<P> {
 
     /// Unsubscribe from all topics that doesn't have the given fork_digest
     pub fn unsubscribe_from_fork_topics_except(&mut self, except: ForkDigest) {
-        let subscriptions = self.network_globals.gossipsub_subscriptions.read().clone();
-        for topic in subscriptions
-            .iter()
-            .filter(|topic| topic.fork_digest != except)
-            .cloned()
-        {
-            self.unsubscribe(topic);
-        }
+        self.unsubscribe_from_fork_topics_where(|fork_digest| fork_digest != except);
     }
 
     /// Remove topic weight from all topics that don't have the given fork digest.
     pub fn remove_topic_weight_except(&mut self, except: ForkDigest) {
-        let new_param = TopicScoreParams {
-            topic_weight: 0.0,
-            ..Default::default()
-        };
-        let subscriptions = self.network_globals.gossipsub_subscriptions.read().clone();
-        for topic in subscriptions
-            .iter()
-            .filter(|topic| topic.fork_digest != except)
-        {
-            let libp2p_topic: Topic = topic.clone().into();
-            match self
-                .gossipsub_mut()
-                .set_topic_params(libp2p_topic, new_param.clone())
-            {
-                Ok(_) => debug!(%topic, "Removed topic weight"),
-                Err(e) => {
-                    warn!(%topic, error = e, "Failed to remove topic weight")
-                }
-            }
-        }
+        self.remove_topic_weight_where(|fork_digest| fork_digest != except);
     }
 
     /// Subscribe to all data columns determined by the cgc.
@@ -960,6 +1106,13 @@ impl<P: Preset> Network<P> {
             }
         }
 
+        if let Some(storage) = self.invalid_block_storage.as_mut() {
+            storage.on_validation_result(
+                &message_id,
+                matches!(validation_result, MessageAcceptance::Reject),
+            );
+        }
+
         self.gossipsub_mut().report_message_validation_result(
             &message_id,
             propagation_source,
@@ -1033,6 +1186,140 @@ impl<P: Preset> Network<P> {
         Ok(())
     }
 
+    /// Send a request to a peer over RPC with a priority.
+    ///
+    /// When requests to the peer are self rate limited, the request is sent ahead of queued
+    /// requests of lower priority. A request still queued after `max_queue_time` is dropped and
+    /// reported as [`NetworkEvent::RPCFailed`] with `RPCError::InternalError`.
+    pub fn send_request_with_priority(
+        &mut self,
+        peer_id: PeerId,
+        app_request_id: AppRequestId,
+        request: RequestType<P>,
+        priority: RequestPriority,
+        max_queue_time: Option<Duration>,
+    ) -> Result<(), (AppRequestId, RPCError)> {
+        if !self.swarm.is_connected(&peer_id) {
+            return Err((app_request_id, RPCError::Disconnected));
+        }
+
+        self.eth2_rpc_mut().send_request_with_priority(
+            peer_id,
+            app_request_id,
+            request,
+            priority,
+            max_queue_time,
+        );
+        Ok(())
+    }
+
+    /// Send a request that expects a single response, such as `Status` or
+    /// `LightClientBootstrap`, to a peer over RPC.
+    ///
+    /// The response is delivered to the returned future instead of as a
+    /// [`NetworkEvent::ResponseReceived`]. Dropping the future cancels the request.
+    pub fn request(
+        &mut self,
+        peer_id: PeerId,
+        request: RequestType<P>,
+    ) -> Result<ResponseFuture<P>, RPCError> {
+        if !request.expect_exactly_one_response() || Self::is_behaviour_request(&request) {
+            return Err(RPCError::InternalError(
+                "Request does not expect a single response",
+            ));
+        }
+
+        if !self.swarm.is_connected(&peer_id) {
+            return Err(RPCError::Disconnected);
+        }
+
+        let (id, future) = self.rpc_client.register_single(peer_id);
+        self.eth2_rpc_mut()
+            .send_request(peer_id, AppRequestId::Client(id), request);
+        Ok(future)
+    }
+
+    /// Send a request that expects a stream of responses, such as `BlocksByRange` or
+    /// `DataColumnsByRoot`, to a peer over RPC.
+    ///
+    /// The responses are delivered to the returned stream instead of as
+    /// [`NetworkEvent::ResponseReceived`]. The stream ends after the stream terminator or the first
+    /// error. Dropping the stream cancels the request.
+    pub fn request_stream(
+        &mut self,
+        peer_id: PeerId,
+        request: RequestType<P>,
+    ) -> Result<ResponseStream<P>, RPCError> {
+        if request.expect_exactly_one_response() || Self::is_behaviour_request(&request) {
+            return Err(RPCError::InternalError(
+                "Request does not expect a stream of responses",
+            ));
+        }
+
+        if !self.swarm.is_connected(&peer_id) {
+            return Err(RPCError::Disconnected);
+        }
+
+        let (id, stream) = self.rpc_client.register_stream(peer_id);
+        self.eth2_rpc_mut()
+            .send_request(peer_id, AppRequestId::Client(id), request);
+        Ok(stream)
+    }
+
+    /// Send the same request, such as `BlocksByRoot` for a missing parent or `DataColumnsByRoot`
+    /// for sampling, to up to `peer_count` of the best scoring connected peers that are synced
+    /// and advertise support for the protocol of the request.
+    ///
+    /// The responses of the first peer to answer are delivered to the returned stream and the
+    /// requests to the other peers are cancelled. Peers without any responses or failing do not
+    /// win the race. The stream fails only if all of the requests fail.
+    ///
+    /// Every request counts against the outbound rate limits. Cancelled requests that are still
+    /// waiting for quota are dropped without consuming it, those already sent have their substream
+    /// closed.
+    pub fn request_hedged(
+        &mut self,
+        request: RequestType<P>,
+        peer_count: usize,
+    ) -> Result<ResponseStream<P>, RPCError> {
+        if request.expect_exactly_one_response() || Self::is_behaviour_request(&request) {
+            return Err(RPCError::InternalError(
+                "Request does not expect a stream of responses",
+            ));
+        }
+
+        let protocols = request.supported_protocols();
+
+        let peers = self
+            .network_globals
+            .peers
+            .read()
+            .best_peers_by_status(|info| {
+                info.is_connected()
+                    && info.is_synced_or_advanced()
+                    && protocols
+                        .iter()
+                        .any(|protocol| info.supports_protocol(protocol.as_ref()))
+            })
+            .into_iter()
+            .take(peer_count)
+            .map(|(peer_id, _)| *peer_id)
+            .collect::<Vec<_>>();
+
+        if peers.is_empty() {
+            return Err(RPCError::Disconnected);
+        }
+
+        let (requests, stream) = self.rpc_client.register_hedged(&peers);
+
+        for (peer_id, id) in requests {
+            self.eth2_rpc_mut()
+                .send_request(peer_id, AppRequestId::Client(id), request.clone());
+        }
+
+        Ok(stream)
+    }
+
     /// Send a successful response to a peer over RPC.
     pub fn send_response<T: Into<RpcResponse<P>>>(
         &mut self,
@@ -1052,6 +1339,216 @@ impl<P: Preset> Network<P> {
         }
     }
 
+    /// Registers the range of slots this node can serve. Inbound `BlocksByRange`, `BlobsByRange`
+    /// and `DataColumnsByRange` requests starting before `earliest_available_slot` or outside the
+    /// sidecar retention windows are answered with `ResourceUnavailable` without being emitted as
+    /// `NetworkEvent::RequestReceived`.
+    ///
+    /// Should be called every slot and whenever `earliest_available_slot` changes.
+    pub fn update_data_availability(&mut self, earliest_available_slot: Slot, current_slot: Slot) {
+        self.eth2_rpc_mut()
+            .update_data_availability(DataAvailability {
+                earliest_available_slot,
+                current_slot,
+            });
+    }
+
+    /// Replaces the quotas of the RPC rate limiters without restarting the node. A `None`
+    /// configuration leaves the corresponding limiter unchanged.
+    ///
+    /// Returns an error, leaving both limiters unchanged, if a configuration is invalid.
+    pub fn update_rate_limits(
+        &mut self,
+        inbound_rate_limiter_config: Option<InboundRateLimiterConfig>,
+        outbound_rate_limiter_config: Option<OutboundRateLimiterConfig>,
+    ) -> Result<(), &'static str> {
+        self.eth2_rpc_mut()
+            .update_rate_limits(inbound_rate_limiter_config, outbound_rate_limiter_config)
+    }
+
+    /// Registers a policy that classifies peers by their `Status`.
+    ///
+    /// Irrelevant peers are sent a goodbye with `GoodbyeReason::IrrelevantNetwork` and their
+    /// `Status` is not propagated to the application. Classification starts once our own
+    /// `Status` is known through [`Self::update_local_status`].
+    pub fn set_status_policy(&mut self, status_policy: impl StatusPolicy + 'static) {
+        self.status_policy = Some(Box::new(status_policy));
+    }
+
+    /// Performs the fork transitions of upcoming forks at their slots, counted from
+    /// `genesis_time`.
+    ///
+    /// Topics of a fork are subscribed to `pre_subscribe_epochs` before it. At the fork, the fork
+    /// context, the ENR and the topic weights are updated. Topics of the previous forks are
+    /// unsubscribed from `post_unsubscribe_epochs` after it. Each step is reported with a
+    /// `NetworkEvent::ForkTransition`.
+    pub fn enable_fork_transitions(&mut self, genesis_time: u64, config: ForkTransitionConfig) {
+        self.fork_scheduler = Some(ForkScheduler::new::<P>(
+            &self.fork_context,
+            self.slot_clock(genesis_time),
+            config,
+        ));
+    }
+
+    /// Subscribes to the long-lived attestation subnets derived from the local node id and
+    /// rotates them as the epochs counted from `genesis_time` pass.
+    ///
+    /// The subnets are advertised in the ENR and metadata `attnets` and peers on them are
+    /// discovered until their next rotation.
+    pub fn enable_long_lived_attestation_subnets(&mut self, genesis_time: u64) {
+        let node_id = self.network_globals.local_enr().node_id().raw();
+
+        self.attestation_subnets = Some(AttestationSubnets::new(
+            node_id,
+            self.slot_clock(genesis_time),
+        ));
+    }
+
+    /// Enables [`Self::schedule_attestation_duty`], with slots counted from `genesis_time`.
+    pub fn enable_attestation_duties(&mut self, genesis_time: u64, config: AttestationDutyConfig) {
+        self.attestation_duties = Some(AttestationDuties::new(
+            self.slot_clock(genesis_time),
+            config,
+        ));
+    }
+
+    /// Prepares the attestation subnet `subnet_id` for a validator duty at `slot`.
+    ///
+    /// Peers on the subnet are discovered and kept until the end of the slot. Aggregators also
+    /// subscribe to the subnet `subscribe_slots_in_advance` slots before the duty and unsubscribe
+    /// after it, unless another duty or the long-lived subnets still need it.
+    pub fn schedule_attestation_duty(
+        &mut self,
+        subnet_id: SubnetId,
+        slot: Slot,
+        is_aggregator: bool,
+    ) -> Result<()> {
+        let attestation_duties = self
+            .attestation_duties
+            .as_mut()
+            .ok_or_else(|| anyhow!("Attestation duties are not enabled"))?;
+
+        let slot_clock = *attestation_duties.slot_clock();
+
+        if subnet_id >= AttestationSubnetCount::U64 {
+            return Err(anyhow!("Attestation subnet {subnet_id} does not exist"));
+        }
+
+        if slot < slot_clock.current_slot() {
+            return Err(anyhow!("Attestation duty slot {slot} is in the past"));
+        }
+
+        let end_slot = slot
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("Attestation duty slot {slot} is out of range"))?;
+
+        if is_aggregator {
+            attestation_duties.schedule(subnet_id, slot)?;
+        }
+
+        self.discover_subnet_peers(vec![SubnetDiscovery {
+            subnet: Subnet::Attestation(subnet_id),
+            min_ttl: Some(slot_clock.slot_start_instant(end_slot)),
+        }]);
+
+        Ok(())
+    }
+
+    /// Adds a fork scheduled after startup, such as a blob-parameter-only fork.
+    ///
+    /// The topics of the fork are whitelisted in gossipsub so that they can be subscribed to,
+    /// either by the application or at the scheduled transition if fork transitions are enabled.
+    /// The ENR is updated if the fork becomes the next fork. A fork already scheduled at
+    /// `fork_epoch` is replaced, which keeps the topics of its digest whitelisted. Returns `false`
+    /// if the fork is not after the current fork.
+    pub fn add_scheduled_fork(
+        &mut self,
+        phase: Phase,
+        fork_digest: ForkDigest,
+        fork_epoch: Epoch,
+    ) -> bool {
+        if !self
+            .fork_context
+            .add_scheduled_fork(phase, fork_digest, fork_epoch)
+        {
+            return false;
+        }
+
+        let chain_config = self.fork_context.chain_config().clone_arc();
+
+        self.whitelist_filter.add_fork_digest(
+            fork_digest,
+            &chain_config,
+            AttestationSubnetCount::U64,
+            SyncCommitteeSubnetCount::U64,
+        );
+
+        let topics = all_topics_at_fork(&chain_config, phase)
+            .into_iter()
+            .map(|kind| {
+                Topic::new(GossipTopic::new(
+                    kind,
+                    GossipEncoding::default(),
+                    fork_digest,
+                ))
+                .into()
+            })
+            .collect::<Vec<TopicHash>>();
+
+        self.gossipsub_mut().register_topics_for_metrics(topics);
+
+        if let Some(fork_scheduler) = self.fork_scheduler.as_mut() {
+            fork_scheduler.add_fork::<P>(&self.fork_context, fork_epoch);
+        }
+
+        if self
+            .fork_context
+            .next_fork()
+            .is_some_and(|(_, _, next_fork_epoch)| next_fork_epoch == fork_epoch)
+        {
+            self.update_enr_from_fork_context();
+        }
+
+        info!(?phase, ?fork_digest, fork_epoch, "Added scheduled fork");
+
+        true
+    }
+
+    /// Updates our `Status`, used by the registered `StatusPolicy`.
+    pub fn update_local_status(&mut self, status: StatusMessage) {
+        self.local_status = Some(status);
+    }
+
+    /// Classifies a peer by its `Status` with the registered `StatusPolicy`.
+    ///
+    /// Returns `false` if the peer is irrelevant and has been sent a goodbye.
+    fn on_status(&mut self, peer_id: PeerId, status: StatusMessage) -> bool {
+        let (Some(status_policy), Some(local_status)) = (&self.status_policy, self.local_status)
+        else {
+            return true;
+        };
+
+        match status_policy.classify(local_status, status) {
+            StatusClassification::Relevant => true,
+            StatusClassification::Irrelevant => {
+                debug!(%peer_id, ?status, "Disconnecting irrelevant peer");
+                self.goodbye_peer(
+                    &peer_id,
+                    GoodbyeReason::IrrelevantNetwork,
+                    ReportSource::PeerManager,
+                );
+                false
+            }
+            StatusClassification::Sync(sync_status) => {
+                self.network_globals
+                    .peers
+                    .write()
+                    .update_sync_status(&peer_id, sync_status);
+                true
+            }
+        }
+    }
+
     /* Peer management functions */
 
     pub fn testing_dial(&mut self, addr: Multiaddr) -> Result<(), libp2p::swarm::DialError> {
@@ -1078,6 +1575,136 @@ impl<P: Preset> Network<P> {
             .goodbye_peer(peer_id, reason, source);
     }
 
+    /// Bans a peer for `duration` regardless of its score.
+    ///
+    /// The peer is disconnected and banned in both libp2p and discovery.
+    pub fn ban_peer(&mut self, peer_id: PeerId, duration: Duration) {
+        self.peer_manager_mut().ban_peer_manually(peer_id, duration);
+        self.save_ban_list();
+    }
+
+    /// Lifts a ban placed with [`Self::ban_peer`]. Returns `false` if the peer was not banned this
+    /// way.
+    pub fn unban_peer(&mut self, peer_id: &PeerId) -> bool {
+        let unbanned = self.peer_manager_mut().unban_peer_manually(peer_id);
+
+        if unbanned {
+            self.save_ban_list();
+        }
+
+        unbanned
+    }
+
+    /// Bans all addresses within `range` for `duration`.
+    ///
+    /// Connections from the range are refused and connected peers within it are disconnected.
+    /// Discovery bans every address of small ranges and the known peer addresses of larger ones.
+    pub fn ban_ip_range(&mut self, range: IpNet, duration: Duration) {
+        self.peer_manager_mut()
+            .ban_ip_range_manually(range, duration);
+        self.save_ban_list();
+    }
+
+    /// Lifts a ban placed with [`Self::ban_ip_range`]. Returns `false` if the range was not
+    /// banned.
+    pub fn unban_ip_range(&mut self, range: &IpNet) -> bool {
+        let unbanned = self.peer_manager_mut().unban_ip_range_manually(range);
+
+        if unbanned {
+            self.save_ban_list();
+        }
+
+        unbanned
+    }
+
+    /// Returns all active bans, whether placed manually or caused by a bad score.
+    pub fn list_bans(&self) -> Vec<Ban> {
+        self.peer_manager().bans()
+    }
+
+    /// Gracefully shuts down the network.
+    ///
+    /// Rejects new inbound requests, sends a `ClientShutdown` goodbye to every connected peer,
+    /// persists the local ENR and metadata and closes all listeners. The swarm is then driven so
+    /// that goodbyes and the responses of active inbound requests are delivered. Inbound requests
+    /// still awaiting a response `INBOUND_REQUEST_FLUSH_TIMEOUT` before `NETWORK_SHUTDOWN_TIMEOUT`
+    /// elapses are terminated with a `ServerError`.
+    pub async fn shutdown(&mut self, reason: ShutdownReason) {
+        info!(reason = reason.message(), "Shutting down network");
+
+        let deadline = tokio::time::Instant::now() + NETWORK_SHUTDOWN_TIMEOUT;
+
+        let rejected_requests = self.eth2_rpc_mut().stop_accepting_inbound_requests();
+
+        if rejected_requests > 0 {
+            debug!(rejected_requests, "Rejected queued inbound requests");
+        }
+
+        let connected_peers = self.swarm.connected_peers().copied().collect::<Vec<_>>();
+
+        for peer_id in connected_peers {
+            self.peer_manager_mut()
+                .disconnect_peer(peer_id, GoodbyeReason::ClientShutdown);
+        }
+
+        crate::discovery::enr::save_enr_to_disk(
+            self.network_dir.as_deref(),
+            &self.discovery().local_enr(),
+        );
+        let meta_data = self.network_globals.local_metadata.read().clone();
+        utils::save_metadata_to_disk(self.network_dir.as_deref(), meta_data);
+        self.save_peer_db();
+
+        if let Some(writer) = &self.peer_db_writer {
+            if tokio::time::timeout_at(deadline, writer.flush())
+                .await
+                .is_err()
+            {
+                warn!("Timed out writing the peer snapshot and ban list");
+            }
+        }
+
+        for listener_id in std::mem::take(&mut self.listener_ids) {
+            self.swarm.remove_listener(listener_id);
+        }
+
+        let request_deadline = deadline - INBOUND_REQUEST_FLUSH_TIMEOUT;
+
+        if tokio::time::timeout_at(request_deadline, self.drain_swarm())
+            .await
+            .is_ok()
+        {
+            return;
+        }
+
+        let cancelled_requests = self.eth2_rpc_mut().cancel_active_inbound_requests();
+
+        if cancelled_requests > 0 {
+            debug!(cancelled_requests, "Terminated active inbound requests");
+        }
+
+        if tokio::time::timeout_at(deadline, self.drain_swarm())
+            .await
+            .is_err()
+        {
+            debug!(
+                remaining_peers = self.swarm.connected_peers().count(),
+                "Network shutdown timed out"
+            );
+        }
+    }
+
+    /// Polls the swarm until all peers have disconnected, delivering goodbyes and responses.
+    async fn drain_swarm(&mut self) {
+        while self.swarm.connected_peers().next().is_some() {
+            let Some(event) = self.swarm.next().await else {
+                break;
+            };
+
+            let _ = self.parse_swarm_event(event);
+        }
+    }
+
     /// Hard (ungraceful) disconnect for testing purposes only
     /// Use goodbye_peer for disconnections, do not use this function.
     pub fn __hard_disconnect_testing_only(&mut self, peer_id: PeerId) {
@@ -1098,6 +1725,24 @@ impl<P: Preset> Network<P> {
     ///
     /// The `value` is `true` if a subnet is being added and false otherwise.
     pub fn update_enr_subnet(&mut self, subnet_id: Subnet, value: bool) {
+        // Proposer-only nodes always advertise empty `attnets` and `syncnets`.
+        if value && self.is_proposer_only_excluded_subnet(subnet_id) {
+            debug!(subnet = ?subnet_id, "Proposer-only node, not advertising subnet");
+            return;
+        }
+
+        // Nodes subscribed to every subnet always advertise full `attnets` and `syncnets`.
+        if !value
+            && self
+                .network_globals
+                .network_config
+                .subscribes_to_all_subnets()
+            && matches!(subnet_id, Subnet::Attestation(_) | Subnet::SyncCommittee(_))
+        {
+            debug!(subnet = ?subnet_id, "Subscribed to all subnets, still advertising subnet");
+            return;
+        }
+
         if let Err(e) = self.discovery_mut().update_enr_bitfield(subnet_id, value) {
             exception!(error = ?e, "Could not update ENR bitfield");
         }
@@ -1126,6 +1771,15 @@ impl<P: Preset> Network<P> {
         let filtered: Vec<SubnetDiscovery> = subnets_to_discover
             .into_iter()
             .filter(|s| {
+                // Proposer-only nodes do not look for attestation or sync committee peers
+                if self.is_proposer_only_excluded_subnet(s.subnet) {
+                    trace!(
+                        subnet = ?s.subnet,
+                        reason = "Proposer-only node",
+                        "Discovery query ignored"
+                    );
+                    return false;
+                }
                 // Extend min_ttl of connected peers on required subnets
                 if let Some(min_ttl) = s.min_ttl {
                     self.network_globals
@@ -1186,6 +1840,247 @@ impl<P: Preset> Network<P> {
 
     /* Private internal functions */
 
+    /// Unsubscribe from all topics whose fork digest matches `predicate`.
+    fn unsubscribe_from_fork_topics_where(&mut self, predicate: impl Fn(ForkDigest) -> bool) {
+        let subscriptions = self.network_globals.gossipsub_subscriptions.read().clone();
+        for topic in subscriptions
+            .iter()
+            .filter(|topic| predicate(topic.fork_digest))
+            .cloned()
+        {
+            self.unsubscribe(topic);
+        }
+    }
+
+    /// Remove topic weight from all topics whose fork digest matches `predicate`.
+    fn remove_topic_weight_where(&mut self, predicate: impl Fn(ForkDigest) -> bool) {
+        let new_param = TopicScoreParams {
+            topic_weight: 0.0,
+            ..Default::default()
+        };
+        let subscriptions = self.network_globals.gossipsub_subscriptions.read().clone();
+        for topic in subscriptions
+            .iter()
+            .filter(|topic| predicate(topic.fork_digest))
+        {
+            let libp2p_topic: Topic = topic.clone().into();
+            match self
+                .gossipsub_mut()
+                .set_topic_params(libp2p_topic, new_param.clone())
+            {
+                Ok(_) => debug!(%topic, "Removed topic weight"),
+                Err(e) => {
+                    warn!(%topic, error = e, "Failed to remove topic weight")
+                }
+            }
+        }
+    }
+
+    /// Updates the "eth2" and "nfd" fields of the local ENR from the current and next forks of the
+    /// fork context.
+    fn update_enr_from_fork_context(&mut self) {
+        let chain_config = self.fork_context.chain_config();
+        let (next_fork_version, next_fork_epoch) = match self.fork_context.next_fork() {
+            Some((next_phase, _, next_fork_epoch)) => {
+                (chain_config.version(next_phase), next_fork_epoch)
+            }
+            None => (
+                chain_config.version(self.fork_context.current_fork_name()),
+                FAR_FUTURE_EPOCH,
+            ),
+        };
+
+        let next_fork_digest = self
+            .fork_context
+            .next_fork_digest()
+            .unwrap_or_else(|| self.fork_context.current_fork_digest());
+
+        self.update_fork_version(EnrForkId {
+            fork_digest: self.fork_context.current_fork_digest(),
+            next_fork_version,
+            next_fork_epoch,
+        });
+        self.update_nfd(next_fork_digest);
+    }
+
+    /// Returns a clock of the slots since `genesis_time`.
+    fn slot_clock(&self, genesis_time: u64) -> SlotClock {
+        SlotClock::new(
+            genesis_time,
+            self.fork_context.chain_config().slot_duration_ms,
+        )
+    }
+
+    /// Moves the gossip subscriptions and `attnets` to the new long-lived attestation subnets and
+    /// looks for peers on them until they rotate again.
+    fn on_subnet_rotation(&mut self, rotation: SubnetRotation) {
+        let SubnetRotation {
+            subscribe,
+            unsubscribe,
+            subnets,
+            expiration_epoch,
+            expiration,
+        } = rotation;
+
+        debug!(
+            ?subnets,
+            expiration_epoch, "Updating long-lived attestation subnets"
+        );
+
+        for subnet_id in unsubscribe {
+            if !self.is_attestation_subnet_needed(subnet_id) {
+                self.unsubscribe_kind(GossipKind::Attestation(subnet_id));
+            }
+            self.update_enr_subnet(Subnet::Attestation(subnet_id), false);
+        }
+
+        for subnet_id in subscribe {
+            // Proposer-only nodes do not take part in attestation subnets.
+            if self.is_proposer_only_excluded_subnet(Subnet::Attestation(subnet_id)) {
+                continue;
+            }
+
+            self.subscribe_kind(GossipKind::Attestation(subnet_id));
+            self.update_enr_subnet(Subnet::Attestation(subnet_id), true);
+        }
+
+        self.discover_subnet_peers(
+            subnets
+                .into_iter()
+                .map(|subnet_id| SubnetDiscovery {
+                    subnet: Subnet::Attestation(subnet_id),
+                    min_ttl: Some(expiration),
+                })
+                .collect(),
+        );
+    }
+
+    /// Subscribes to or unsubscribes from the subnet of an aggregation duty.
+    fn on_duty_action(&mut self, action: DutyAction) {
+        match action {
+            DutyAction::Subscribe(subnet_id) => {
+                // Proposer-only nodes do not take part in attestation subnets.
+                if !self.is_proposer_only_excluded_subnet(Subnet::Attestation(subnet_id)) {
+                    self.subscribe_kind(GossipKind::Attestation(subnet_id));
+                }
+            }
+            DutyAction::Unsubscribe(subnet_id) => {
+                if !self.is_attestation_subnet_needed(subnet_id) {
+                    self.unsubscribe_kind(GossipKind::Attestation(subnet_id));
+                }
+            }
+        }
+    }
+
+    /// Subscribes to every attestation subnet and, from Altair, every sync committee subnet of the
+    /// current fork. Later forks subscribe to them through their core topics.
+    fn subscribe_to_all_subnets(&mut self) {
+        for subnet_id in 0..AttestationSubnetCount::U64 {
+            self.subscribe_kind(GossipKind::Attestation(subnet_id));
+        }
+
+        if self.fork_context.current_fork_name() >= Phase::Altair {
+            for subnet_id in 0..SyncCommitteeSubnetCount::U64 {
+                self.subscribe_kind(GossipKind::SyncCommitteeMessage(subnet_id));
+            }
+        }
+    }
+
+    /// Returns `true` if the node must stay subscribed to the attestation subnet `subnet_id`,
+    /// because it subscribes to every subnet, the subnet is long-lived or an aggregation duty
+    /// needs it.
+    fn is_attestation_subnet_needed(&self, subnet_id: SubnetId) -> bool {
+        self.network_globals
+            .network_config
+            .subscribes_to_all_subnets()
+            || self
+                .attestation_subnets
+                .as_ref()
+                .is_some_and(|attestation_subnets| attestation_subnets.is_subscribed(subnet_id))
+            || self
+                .attestation_duties
+                .as_ref()
+                .is_some_and(|attestation_duties| attestation_duties.is_subnet_needed(subnet_id))
+    }
+
+    /// Performs a step of a fork transition scheduled by the `ForkScheduler`.
+    fn on_fork_transition(&mut self, transition: ForkTransition) {
+        let ForkTransition {
+            phase,
+            fork_digest,
+            fork_epoch,
+            stage,
+        } = transition;
+
+        info!(?phase, ?fork_digest, fork_epoch, ?stage, "Fork transition");
+
+        // Topics of the forks preceding this one. Other upcoming forks may already have been
+        // subscribed to when fork windows overlap, so their topics are left alone.
+        let previous_digests = self
+            .fork_context
+            .all_fork_epochs()
+            .into_iter()
+            .filter(|epoch| *epoch < fork_epoch)
+            .map(|epoch| self.fork_context.context_bytes(epoch))
+            .collect::<HashSet<_>>();
+
+        match stage {
+            ForkTransitionStage::Subscribed => {
+                self.subscribe_new_fork_topics(phase, fork_digest);
+            }
+            ForkTransitionStage::Activated => {
+                // The application may have moved the fork context to the fork already.
+                while self.fork_context.current_fork_epoch() < fork_epoch {
+                    self.fork_context.update_current_fork();
+                }
+
+                self.update_enr_from_fork_context();
+                self.remove_topic_weight_where(|fork_digest| {
+                    previous_digests.contains(&fork_digest)
+                });
+            }
+            ForkTransitionStage::Unsubscribed => {
+                self.unsubscribe_from_fork_topics_where(|fork_digest| {
+                    previous_digests.contains(&fork_digest)
+                });
+            }
+        }
+    }
+
+    /// Queues the good peers of the `PeerDB` to be written to disk, if enabled.
+    fn save_peer_db(&self) {
+        if let Some(writer) = self
+            .peer_db_writer
+            .as_ref()
+            .filter(|_| self.persist_peer_db)
+        {
+            writer.save_snapshot(
+                self.network_globals
+                    .peers
+                    .read()
+                    .snapshot(MAX_SNAPSHOT_PEERS),
+            );
+        }
+    }
+
+    /// Queues the ban list to be written to disk, if enabled.
+    fn save_ban_list(&self) {
+        if let Some(writer) = self
+            .peer_db_writer
+            .as_ref()
+            .filter(|_| self.persist_ban_list)
+        {
+            writer.save_ban_list(self.network_globals.peers.read().ban_list());
+        }
+    }
+
+    /// Returns `true` if `subnet` is an attestation or sync committee subnet and this node runs in
+    /// proposer-only mode.
+    fn is_proposer_only_excluded_subnet(&self, subnet: Subnet) -> bool {
+        self.network_globals.network_config.proposer_only
+            && matches!(subnet, Subnet::Attestation(_) | Subnet::SyncCommittee(_))
+    }
+
     /// Updates the current meta data of the node to match the local ENR.
     fn update_metadata_bitfields(&mut self) {
         let local_attnets = self
@@ -1265,7 +2160,17 @@ impl<P: Preset> Network<P> {
     ) -> Option<NetworkEvent<P>> {
         match app_request_id {
             AppRequestId::Internal => None,
-            _ => Some(NetworkEvent::ResponseReceived {
+            AppRequestId::Client(id) => {
+                self.rpc_client.on_response(id, response);
+
+                for (peer_id, id) in self.rpc_client.take_losers() {
+                    self.eth2_rpc_mut()
+                        .cancel_request(peer_id, AppRequestId::Client(id));
+                }
+
+                None
+            }
+            AppRequestId::Application(_) => Some(NetworkEvent::ResponseReceived {
                 peer_id,
                 app_request_id,
                 response,
@@ -1273,6 +2178,14 @@ impl<P: Preset> Network<P> {
         }
     }
 
+    /// Returns `true` for requests whose responses are handled within the behaviour.
+    fn is_behaviour_request(request: &RequestType<P>) -> bool {
+        matches!(
+            request,
+            RequestType::Goodbye(_) | RequestType::Ping(_) | RequestType::MetaData(_)
+        )
+    }
+
     /// Dial cached Enrs in discovery service that are in the given `subnet_id` and aren't
     /// in Connected, Dialing or Banned state.
     fn dial_cached_enrs_in_subnet(&mut self, chain_config: Arc<ChainConfig>, subnet: Subnet) {
@@ -1303,6 +2216,7 @@ impl<P: Preset> Network<P> {
     /// every heartbeat to maintain the connection.
     pub fn dial_trusted_peer(&mut self, enr: Enr) {
         self.peer_manager_mut().add_trusted_peer(enr.clone());
+        self.update_peer_quota_multiplier(enr.peer_id());
         self.peer_manager_mut().dial_peer(enr);
     }
 
@@ -1314,6 +2228,34 @@ impl<P: Preset> Network<P> {
             .disconnect_peer(enr.peer_id(), GoodbyeReason::TooManyPeers);
     }
 
+    /// Applies the quota multiplier of the classes of a connected peer to the RPC rate limiters.
+    fn update_peer_quota_multiplier(&mut self, peer_id: PeerId) {
+        let multiplier = {
+            let peers = self.network_globals.peers.read();
+
+            let Some(peer_info) = peers
+                .peer_info(&peer_id)
+                .filter(|peer_info| peer_info.is_connected())
+            else {
+                return;
+            };
+
+            let has_full_custody = peer_info
+                .meta_data()
+                .and_then(MetaData::custody_group_count)
+                == Some(self.fork_context.chain_config().number_of_custody_groups);
+
+            self.peer_quota_multipliers.multiplier(
+                &peer_id,
+                peer_info.is_trusted(),
+                has_full_custody,
+            )
+        };
+
+        self.eth2_rpc_mut()
+            .set_peer_quota_multiplier(peer_id, multiplier);
+    }
+
     /* Sub-behaviour event handling functions */
 
     /// Handle a gossipsub event.
@@ -1329,6 +2271,15 @@ impl<P: Preset> Network<P> {
                 match PubsubMessage::decode(&gs_msg.topic, &gs_msg.data, &self.fork_context) {
                     Err(e) => {
                         debug!(topic = ?gs_msg.topic, error = e, "Could not decode gossipsub message");
+                        if let Some(storage) = self.invalid_block_storage.as_mut() {
+                            storage.store_undecodable(
+                                &gs_msg.topic,
+                                &propagation_source,
+                                &id,
+                                &gs_msg.data,
+                                &e,
+                            );
+                        }
                         //reject the message
                         self.gossipsub_mut().report_message_validation_result(
                             &id,
@@ -1337,6 +2288,15 @@ impl<P: Preset> Network<P> {
                         );
                     }
                     Ok(msg) => {
+                        if let Some(storage) = self.invalid_block_storage.as_mut() {
+                            storage.insert_pending(
+                                id.clone(),
+                                gs_msg.topic.clone(),
+                                propagation_source,
+                                gs_msg.data,
+                            );
+                        }
+
                         // Notify the network
                         return Some(NetworkEvent::PubsubMessage {
                             id,
@@ -1497,14 +2457,17 @@ impl<P: Preset> Network<P> {
                             ConnectionDirection::Outgoing,
                         );
                         // inform failures of requests coming outside the behaviour
-                        if let AppRequestId::Internal = id {
-                            None
-                        } else {
-                            Some(NetworkEvent::RPCFailed {
+                        match id {
+                            AppRequestId::Internal => None,
+                            AppRequestId::Client(id) => {
+                                self.rpc_client.on_error(id, error);
+                                None
+                            }
+                            AppRequestId::Application(_) => Some(NetworkEvent::RPCFailed {
                                 peer_id,
                                 app_request_id: id,
                                 error,
-                            })
+                            }),
                         }
                     }
                 }
@@ -1541,10 +2504,13 @@ impl<P: Preset> Network<P> {
                         None
                     }
                     /* Protocols propagated to the Network */
-                    RequestType::Status(_) => {
+                    RequestType::Status(status) => {
                         // inform the peer manager that we have received a status from a peer
                         self.peer_manager_mut().peer_statusd(&peer_id);
                         metrics::inc_counter_vec(&metrics::TOTAL_RPC_REQUESTS, &["status"]);
+                        if !self.on_status(peer_id, status) {
+                            return None;
+                        }
                         // propagate the STATUS message upwards
                         Some(NetworkEvent::RequestReceived {
                             peer_id,
@@ -1678,6 +2644,7 @@ impl<P: Preset> Network<P> {
                         let updated_cgc = self
                             .peer_manager_mut()
                             .meta_data_response(&peer_id, *meta_data);
+                        self.update_peer_quota_multiplier(peer_id);
                         // Send event after calling into peer_manager so the PeerDB is updated.
                         updated_cgc.then(|| NetworkEvent::PeerUpdatedCustodyGroupCount(peer_id))
                     }
@@ -1685,6 +2652,13 @@ impl<P: Preset> Network<P> {
                     RpcSuccessResponse::Status(msg) => {
                         // inform the peer manager that we have received a status from a peer
                         self.peer_manager_mut().peer_statusd(&peer_id);
+                        if !self.on_status(peer_id, msg) {
+                            // the peer is being disconnected, fail the handle awaiting the status
+                            if let AppRequestId::Client(id) = id {
+                                self.rpc_client.on_error(id, RPCError::Disconnected);
+                            }
+                            return None;
+                        }
                         // propagate the STATUS message upwards
                         self.build_response(id, peer_id, Response::Status(msg))
                     }
@@ -1770,9 +2744,11 @@ impl<P: Preset> Network<P> {
     fn inject_pm_event(&mut self, event: PeerManagerEvent) -> Option<NetworkEvent<P>> {
         match event {
             PeerManagerEvent::PeerConnectedIncoming(peer_id) => {
+                self.update_peer_quota_multiplier(peer_id);
                 Some(NetworkEvent::PeerConnectedIncoming(peer_id))
             }
             PeerManagerEvent::PeerConnectedOutgoing(peer_id) => {
+                self.update_peer_quota_multiplier(peer_id);
                 Some(NetworkEvent::PeerConnectedOutgoing(peer_id))
             }
             PeerManagerEvent::PeerDisconnected(peer_id) => {
@@ -1780,10 +2756,20 @@ impl<P: Preset> Network<P> {
             }
             PeerManagerEvent::Banned(peer_id, associated_ips) => {
                 self.discovery_mut().ban_peer(&peer_id, associated_ips);
+                self.save_ban_list();
                 None
             }
             PeerManagerEvent::UnBanned(peer_id, associated_ips) => {
                 self.discovery_mut().unban_peer(&peer_id, associated_ips);
+                self.save_ban_list();
+                None
+            }
+            PeerManagerEvent::BannedIps(ips) => {
+                self.discovery_mut().ban_ips(ips);
+                None
+            }
+            PeerManagerEvent::UnBannedIps(ips) => {
+                self.discovery_mut().unban_ips(ips);
                 None
             }
             PeerManagerEvent::Status(peer_id) => {
@@ -1875,11 +2861,45 @@ impl<P: Preset> Network<P> {
                     }
                 },
 
+                // cancel the requests whose handles have been dropped
+                requests = self.rpc_client.next_dropped() => {
+                    for (peer_id, id) in requests {
+                        self.eth2_rpc_mut()
+                            .cancel_request(peer_id, AppRequestId::Client(id));
+                    }
+                }
+
                 // perform gossipsub score updates when necessary
                 _ = self.update_gossipsub_scores.tick() => {
                     let this = self.swarm.behaviour_mut();
                     this.peer_manager.update_gossipsub_scores(&this.gossipsub);
                 }
+                // persist good peers to disk
+                _ = Self::tick(&mut self.peer_db_snapshot_interval) => {
+                    self.save_peer_db();
+                }
+                // perform the scheduled fork transitions
+                transition = Self::next_fork_transition(&mut self.fork_scheduler) => {
+                    self.on_fork_transition(transition);
+                    return NetworkEvent::ForkTransition(transition);
+                }
+                // rotate the long-lived attestation subnets
+                result = Self::next_subnet_rotation(&mut self.attestation_subnets) => {
+                    match result {
+                        Ok(rotation) => self.on_subnet_rotation(rotation),
+                        Err(error) => {
+                            exception!(error = ?error, "Could not compute long-lived attestation subnets")
+                        }
+                    }
+                }
+                // subscribe to and unsubscribe from the subnets of attestation duties
+                action = Self::next_duty_action(&mut self.attestation_duties) => {
+                    self.on_duty_action(action);
+                }
+                // shut down once the node has synced, if requested
+                Some(()) = Self::wait_for_synced(&mut self.shutdown_after_sync) => {
+                    self.shutdown_once_synced().await;
+                }
                 // poll the gossipsub cache to clear expired messages
                 Some(result) = self.gossip_cache.next() => {
                     match result {
@@ -1898,6 +2918,72 @@ impl<P: Preset> Network<P> {
         }
     }
 
+    /// Ticks `interval` if it is set, otherwise never resolves.
+    async fn tick(interval: &mut Option<tokio::time::Interval>) {
+        match interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Resolves with the next due fork transition, or never if transitions are not enabled.
+    async fn next_fork_transition(scheduler: &mut Option<ForkScheduler>) -> ForkTransition {
+        match scheduler {
+            Some(scheduler) => scheduler.next_transition().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Resolves with the next rotation of the long-lived attestation subnets, or never if they are
+    /// not enabled.
+    async fn next_subnet_rotation(
+        attestation_subnets: &mut Option<AttestationSubnets>,
+    ) -> Result<SubnetRotation> {
+        match attestation_subnets {
+            Some(attestation_subnets) => attestation_subnets.next_rotation::<P>().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Resolves with the next subnet change of attestation duties, or never if they are not
+    /// enabled.
+    async fn next_duty_action(attestation_duties: &mut Option<AttestationDuties>) -> DutyAction {
+        match attestation_duties {
+            Some(attestation_duties) => attestation_duties.next_action().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Resolves once the node first reaches `SyncState::Synced`.
+    ///
+    /// Resolves to `None` immediately if the sync state is not being watched.
+    async fn wait_for_synced(receiver: &mut Option<watch::Receiver<SyncState>>) -> Option<()> {
+        receiver
+            .as_mut()?
+            .wait_for(|state| *state == SyncState::Synced)
+            .await
+            .ok()
+            .map(drop)
+    }
+
+    /// Gracefully shuts down the network and requests a shutdown of the node.
+    async fn shutdown_once_synced(&mut self) {
+        // Only shut down on the first transition to synced.
+        self.shutdown_after_sync = None;
+
+        info!("Node synced, shutting down as requested by shutdown_after_sync");
+
+        let reason = ShutdownReason::Success("Shutting down after sync");
+
+        self.shutdown(reason).await;
+
+        if let Err(error) = self.executor.shutdown_sender().try_send(reason) {
+            warn!(%error, "Could not request shutdown after sync");
+        }
+    }
+
     fn parse_swarm_event(
         &mut self,
         event: SwarmEvent<BehaviourEvent<P>>,
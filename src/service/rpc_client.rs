@@ -0,0 +1,508 @@
+//! Handle-based tracking of outbound RPC requests.
+//!
+//! Responses to requests sent with [`Network::request`](super::Network::request) and
+//! [`Network::request_stream`](super::Network::request_stream) are delivered to a
+//! [`ResponseFuture`] or a [`ResponseStream`] instead of being emitted as `NetworkEvent`s, so that
+//! callers do not need to correlate `AppRequestId`s themselves.
+//!
+//! Requests sent with [`Network::request_hedged`](super::Network::request_hedged) race the same
+//! request against several peers. The responses of the first peer to answer are delivered and the
+//! requests to the other peers are cancelled.
+//!
+//! Dropping a handle notifies the client, which forgets the request and returns it from
+//! [`RpcClient::next_dropped`] to be cancelled.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use libp2p::PeerId;
+use tokio::sync::{mpsc, oneshot};
+use types::preset::Preset;
+
+use crate::rpc::RPCError;
+
+use super::api_types::{Id, Response};
+
+type ResponseResult<P> = Result<Response<P>, RPCError>;
+
+/// The receiving side of a request awaiting responses.
+enum PendingRequest<P: Preset> {
+    Single(oneshot::Sender<ResponseResult<P>>),
+    Stream(mpsc::UnboundedSender<ResponseResult<P>>),
+    /// One of the requests of the hedged request with the given id.
+    Hedged(Id),
+}
+
+/// The same request sent to several peers, delivering the responses of the first one to answer.
+struct HedgedRequest<P: Preset> {
+    sender: mpsc::UnboundedSender<ResponseResult<P>>,
+    /// The requests still taking part, with the peers they were sent to.
+    racing: HashMap<Id, PeerId>,
+    /// The request whose responses are delivered, once one of the peers has answered.
+    winner: Option<Id>,
+    /// The error of the latest failed request, delivered if all of them fail.
+    last_error: Option<RPCError>,
+}
+
+/// Outbound requests whose responses are delivered to a handle.
+pub(crate) struct RpcClient<P: Preset> {
+    next_id: Id,
+    /// Requests awaiting responses, with the peers they were sent to.
+    pending: HashMap<Id, (PeerId, PendingRequest<P>)>,
+    hedged: HashMap<Id, HedgedRequest<P>>,
+    /// Requests of hedged requests that lost the race and should be cancelled.
+    losers: Vec<(PeerId, Id)>,
+    /// Given to handles, which send their id when they are dropped.
+    dropped_sender: mpsc::UnboundedSender<Id>,
+    dropped_receiver: mpsc::UnboundedReceiver<Id>,
+}
+
+impl<P: Preset> Default for RpcClient<P> {
+    fn default() -> Self {
+        let (dropped_sender, dropped_receiver) = mpsc::unbounded_channel();
+
+        Self {
+            next_id: 0,
+            pending: HashMap::new(),
+            hedged: HashMap::new(),
+            losers: Vec::new(),
+            dropped_sender,
+            dropped_receiver,
+        }
+    }
+}
+
+impl<P: Preset> RpcClient<P> {
+    /// Registers a request to `peer_id` that expects a single response.
+    pub fn register_single(&mut self, peer_id: PeerId) -> (Id, ResponseFuture<P>) {
+        let (sender, receiver) = oneshot::channel();
+        let id = self.next_id();
+        self.pending
+            .insert(id, (peer_id, PendingRequest::Single(sender)));
+        (id, ResponseFuture::new(receiver, self.drop_notifier(id)))
+    }
+
+    /// Registers a request to `peer_id` that expects a stream of responses.
+    pub fn register_stream(&mut self, peer_id: PeerId) -> (Id, ResponseStream<P>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = self.next_id();
+        self.pending
+            .insert(id, (peer_id, PendingRequest::Stream(sender)));
+        (id, ResponseStream::new(receiver, self.drop_notifier(id)))
+    }
+
+    /// Registers a request that expects a stream of responses and is sent to each of `peers`.
+    ///
+    /// Returns the id of the request sent to each peer.
+    pub fn register_hedged(&mut self, peers: &[PeerId]) -> (Vec<(PeerId, Id)>, ResponseStream<P>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let hedged_id = self.next_id();
+        let mut racing = HashMap::new();
+
+        let requests = peers
+            .iter()
+            .map(|peer_id| {
+                let id = self.next_id();
+                self.pending
+                    .insert(id, (*peer_id, PendingRequest::Hedged(hedged_id)));
+                racing.insert(id, *peer_id);
+                (*peer_id, id)
+            })
+            .collect();
+
+        self.hedged.insert(
+            hedged_id,
+            HedgedRequest {
+                sender,
+                racing,
+                winner: None,
+                last_error: None,
+            },
+        );
+
+        (
+            requests,
+            ResponseStream::new(receiver, self.drop_notifier(hedged_id)),
+        )
+    }
+
+    /// Delivers a response to the handle of request `id`.
+    pub fn on_response(&mut self, id: Id, response: Response<P>) {
+        let Some((peer_id, pending)) = self.pending.remove(&id) else {
+            return;
+        };
+
+        match pending {
+            PendingRequest::Single(sender) => {
+                let _ = sender.send(Ok(response));
+            }
+            PendingRequest::Stream(sender) => {
+                // Dropping the sender ends the stream. A dropped stream is kept until its drop
+                // notification is received so that the request is cancelled.
+                if !response.is_stream_termination() {
+                    let _ = sender.send(Ok(response));
+                    self.pending
+                        .insert(id, (peer_id, PendingRequest::Stream(sender)));
+                }
+            }
+            PendingRequest::Hedged(hedged_id) => {
+                self.on_hedged_response(hedged_id, peer_id, id, response)
+            }
+        }
+    }
+
+    /// Delivers an error to the handle of request `id`, ending it.
+    ///
+    /// A hedged request only ends once all of its requests have failed or the request whose
+    /// responses are being delivered fails.
+    pub fn on_error(&mut self, id: Id, error: RPCError) {
+        match self.pending.remove(&id).map(|(_, pending)| pending) {
+            Some(PendingRequest::Single(sender)) => {
+                let _ = sender.send(Err(error));
+            }
+            Some(PendingRequest::Stream(sender)) => {
+                let _ = sender.send(Err(error));
+            }
+            Some(PendingRequest::Hedged(hedged_id)) => {
+                let Some(hedged) = self.hedged.get_mut(&hedged_id) else {
+                    return;
+                };
+
+                hedged.racing.remove(&id);
+
+                if hedged.winner == Some(id) || hedged.racing.is_empty() {
+                    let _ = hedged.sender.send(Err(error));
+                    self.hedged.remove(&hedged_id);
+                } else {
+                    hedged.last_error = Some(error);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Returns the requests of hedged requests that lost the race. They should be cancelled.
+    pub fn take_losers(&mut self) -> Vec<(PeerId, Id)> {
+        std::mem::take(&mut self.losers)
+    }
+
+    /// Waits until a handle is dropped, forgets its request and returns the requests to cancel.
+    ///
+    /// Cancel safe: requests are only forgotten once they are returned.
+    pub async fn next_dropped(&mut self) -> Vec<(PeerId, Id)> {
+        loop {
+            let Some(id) = self.dropped_receiver.recv().await else {
+                // The client holds a sender, so the channel is never closed.
+                return std::future::pending().await;
+            };
+
+            if let Some((peer_id, _)) = self.pending.remove(&id) {
+                return vec![(peer_id, id)];
+            }
+
+            if let Some(hedged) = self.hedged.remove(&id) {
+                let mut requests = hedged
+                    .racing
+                    .into_iter()
+                    .map(|(id, peer_id)| (peer_id, id))
+                    .collect::<Vec<_>>();
+
+                // The winner of the race is no longer racing.
+                let winner = hedged.winner.and_then(|winner| {
+                    let (peer_id, _) = self.pending.remove(&winner)?;
+                    Some((peer_id, winner))
+                });
+
+                requests.extend(winner);
+
+                for (_, id) in &requests {
+                    self.pending.remove(id);
+                }
+
+                return requests;
+            }
+
+            // The request already completed.
+        }
+    }
+
+    fn on_hedged_response(
+        &mut self,
+        hedged_id: Id,
+        peer_id: PeerId,
+        id: Id,
+        response: Response<P>,
+    ) {
+        let Some(hedged) = self.hedged.get_mut(&hedged_id) else {
+            return;
+        };
+
+        if response.is_stream_termination() {
+            hedged.racing.remove(&id);
+
+            // A peer without any responses does not win the race.
+            if hedged.winner == Some(id) || hedged.racing.is_empty() {
+                if hedged.winner.is_none() {
+                    if let Some(error) = hedged.last_error.take() {
+                        let _ = hedged.sender.send(Err(error));
+                    }
+                }
+                // Dropping the sender ends the stream.
+                self.hedged.remove(&hedged_id);
+            }
+
+            return;
+        }
+
+        if hedged.winner.is_none() {
+            hedged.winner = Some(id);
+
+            for (loser_id, peer_id) in hedged.racing.drain() {
+                if loser_id != id {
+                    self.pending.remove(&loser_id);
+                    self.losers.push((peer_id, loser_id));
+                }
+            }
+        }
+
+        // A dropped stream is kept until its drop notification is received.
+        let _ = hedged.sender.send(Ok(response));
+        self.pending
+            .insert(id, (peer_id, PendingRequest::Hedged(hedged_id)));
+    }
+
+    fn drop_notifier(&self, id: Id) -> DropNotifier {
+        DropNotifier {
+            id,
+            sender: self.dropped_sender.clone(),
+        }
+    }
+
+    fn next_id(&mut self) -> Id {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        id
+    }
+}
+
+/// Notifies the client that a handle was dropped.
+struct DropNotifier {
+    id: Id,
+    sender: mpsc::UnboundedSender<Id>,
+}
+
+impl Drop for DropNotifier {
+    fn drop(&mut self) {
+        // The network may have been dropped first.
+        let _ = self.sender.send(self.id);
+    }
+}
+
+/// Resolves to the response of a request that expects a single response.
+///
+/// Dropping the future cancels the request: it is dropped if it has not been sent yet, otherwise
+/// its substream is closed and a response arriving later is discarded.
+#[must_use = "futures do nothing unless polled"]
+pub struct ResponseFuture<P: Preset> {
+    receiver: oneshot::Receiver<ResponseResult<P>>,
+    _drop_notifier: DropNotifier,
+}
+
+impl<P: Preset> ResponseFuture<P> {
+    fn new(receiver: oneshot::Receiver<ResponseResult<P>>, drop_notifier: DropNotifier) -> Self {
+        Self {
+            receiver,
+            _drop_notifier: drop_notifier,
+        }
+    }
+}
+
+impl<P: Preset> Future for ResponseFuture<P> {
+    type Output = ResponseResult<P>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver).poll(cx).map(|result| {
+            result.unwrap_or(Err(RPCError::InternalError("Network dropped the request")))
+        })
+    }
+}
+
+/// Yields the responses of a request that expects a stream of responses.
+///
+/// The stream ends after the stream terminator or the first error. Dropping the stream cancels
+/// the request: it is dropped if it has not been sent yet, otherwise its substream is closed and
+/// responses arriving later are discarded.
+#[must_use = "streams do nothing unless polled"]
+pub struct ResponseStream<P: Preset> {
+    receiver: mpsc::UnboundedReceiver<ResponseResult<P>>,
+    _drop_notifier: DropNotifier,
+}
+
+impl<P: Preset> ResponseStream<P> {
+    fn new(
+        receiver: mpsc::UnboundedReceiver<ResponseResult<P>>,
+        drop_notifier: DropNotifier,
+    ) -> Self {
+        Self {
+            receiver,
+            _drop_notifier: drop_notifier,
+        }
+    }
+}
+
+impl<P: Preset> Stream for ResponseStream<P> {
+    type Item = ResponseResult<P>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::factory;
+    use crate::rpc::methods::{StatusMessage, StatusMessageV1};
+    use futures::StreamExt as _;
+    use ssz::H256;
+    use std::sync::Arc;
+    use types::phase0::primitives::ForkDigest;
+    use types::preset::Mainnet;
+
+    fn status_response() -> Response<Mainnet> {
+        Response::Status(StatusMessage::V1(StatusMessageV1 {
+            fork_digest: ForkDigest::zero(),
+            finalized_root: H256::zero(),
+            finalized_epoch: 1,
+            head_root: H256::zero(),
+            head_slot: 1,
+        }))
+    }
+
+    #[tokio::test]
+    async fn single_response_resolves_future() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let (id, future) = client.register_single(PeerId::random());
+
+        client.on_response(id, status_response());
+
+        assert_eq!(future.await, Ok(status_response()));
+        assert!(client.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_ends_on_terminator() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let (id, stream) = client.register_stream(PeerId::random());
+
+        client.on_response(id, status_response());
+        client.on_response(id, status_response());
+        client.on_response(id, Response::BlocksByRange(None));
+        // Responses after the terminator are not delivered.
+        client.on_response(id, status_response());
+
+        assert_eq!(stream.collect::<Vec<_>>().await.len(), 2);
+        assert!(client.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_ends_on_error() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let (id, stream) = client.register_stream(PeerId::random());
+
+        client.on_response(id, status_response());
+        client.on_error(id, RPCError::StreamTimeout);
+
+        assert_eq!(
+            stream.collect::<Vec<_>>().await,
+            vec![Ok(status_response()), Err(RPCError::StreamTimeout)],
+        );
+    }
+
+    fn blocks_by_root_response() -> Response<Mainnet> {
+        Response::BlocksByRoot(Some(Arc::new(
+            factory::empty_phase0_signed_beacon_block::<Mainnet>().into(),
+        )))
+    }
+
+    #[tokio::test]
+    async fn hedged_request_delivers_first_responder_and_cancels_others() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let peers = [PeerId::random(), PeerId::random(), PeerId::random()];
+        let (requests, stream) = client.register_hedged(&peers);
+        let [(_, empty), (_, winner), (loser_peer, loser)] = requests[..] else {
+            panic!("one request per peer");
+        };
+
+        // A peer that has nothing to return does not win the race.
+        client.on_response(empty, Response::BlocksByRoot(None));
+        client.on_response(winner, blocks_by_root_response());
+        assert_eq!(client.take_losers(), vec![(loser_peer, loser)]);
+
+        // Responses of the cancelled request are ignored.
+        client.on_response(loser, blocks_by_root_response());
+        client.on_response(winner, Response::BlocksByRoot(None));
+
+        assert_eq!(
+            stream.collect::<Vec<_>>().await,
+            vec![Ok(blocks_by_root_response())],
+        );
+        assert!(client.pending.is_empty());
+        assert!(client.hedged.is_empty());
+    }
+
+    #[tokio::test]
+    async fn hedged_request_fails_once_all_requests_fail() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let (requests, stream) = client.register_hedged(&[PeerId::random(), PeerId::random()]);
+
+        client.on_error(requests[0].1, RPCError::StreamTimeout);
+        client.on_error(requests[1].1, RPCError::Disconnected);
+
+        assert_eq!(
+            stream.collect::<Vec<_>>().await,
+            vec![Err(RPCError::Disconnected)],
+        );
+        assert!(client.take_losers().is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_handles_are_cancelled() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let peer_id = PeerId::random();
+        let (stream_id, stream) = client.register_stream(peer_id);
+        let (future_id, future) = client.register_single(peer_id);
+        let (completed_id, completed) = client.register_single(peer_id);
+
+        client.on_response(completed_id, status_response());
+        assert_eq!(completed.await, Ok(status_response()));
+
+        drop(stream);
+        drop(future);
+
+        // Responses arriving before the notification is received do not forget the request.
+        client.on_response(stream_id, status_response());
+
+        assert_eq!(client.next_dropped().await, vec![(peer_id, stream_id)]);
+        assert_eq!(client.next_dropped().await, vec![(peer_id, future_id)]);
+        assert!(client.pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dropped_hedged_request_cancels_all_requests() {
+        let mut client = RpcClient::<Mainnet>::default();
+        let (requests, stream) = client.register_hedged(&[PeerId::random(), PeerId::random()]);
+
+        drop(stream);
+
+        let mut cancelled = client.next_dropped().await;
+        cancelled.sort_by_key(|(_, id)| *id);
+
+        assert_eq!(cancelled, requests);
+        assert!(client.pending.is_empty());
+        assert!(client.hedged.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Wall clock time of slots, for the schedulers driven by `Network`.
+
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use typenum::Unsigned as _;
+use types::{
+    phase0::primitives::{Epoch, Slot},
+    preset::Preset,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SlotClock {
+    genesis_time: u64,
+    slot_duration: Duration,
+}
+
+impl SlotClock {
+    pub fn new(genesis_time: u64, slot_duration: Duration) -> Self {
+        Self {
+            genesis_time,
+            slot_duration,
+        }
+    }
+
+    /// Returns the current slot, which is the genesis slot before genesis.
+    pub fn current_slot(&self) -> Slot {
+        let since_genesis = Self::since_unix_epoch().saturating_sub(self.genesis_time());
+
+        match self.slot_duration.as_millis() {
+            0 => 0,
+            slot_duration => (since_genesis.as_millis() / slot_duration) as Slot,
+        }
+    }
+
+    /// Returns the current epoch, which is the genesis epoch before genesis.
+    pub fn current_epoch<P: Preset>(&self) -> Epoch {
+        self.current_slot() / P::SlotsPerEpoch::U64
+    }
+
+    /// Returns the time left until the start of `slot`, which is zero if it has already started.
+    pub fn duration_to_slot(&self, slot: Slot) -> Duration {
+        self.slot_start(slot)
+            .saturating_sub(Self::since_unix_epoch())
+    }
+
+    /// Returns the time left until the start of `epoch`, which is zero if it has already started.
+    pub fn duration_to_epoch<P: Preset>(&self, epoch: Epoch) -> Duration {
+        self.duration_to_slot(epoch.saturating_mul(P::SlotsPerEpoch::U64))
+    }
+
+    /// Returns the instant at which `slot` starts, for discovery queries that should keep their
+    /// peers until then.
+    pub fn slot_start_instant(&self, slot: Slot) -> Instant {
+        Instant::now() + self.duration_to_slot(slot)
+    }
+
+    /// Returns the instant at which `epoch` starts, for discovery queries that should keep their
+    /// peers until then.
+    pub fn epoch_start_instant<P: Preset>(&self, epoch: Epoch) -> Instant {
+        Instant::now() + self.duration_to_epoch::<P>(epoch)
+    }
+
+    fn genesis_time(&self) -> Duration {
+        Duration::from_secs(self.genesis_time)
+    }
+
+    fn slot_start(&self, slot: Slot) -> Duration {
+        self.genesis_time().saturating_add(
+            self.slot_duration
+                .saturating_mul(u32::try_from(slot).unwrap_or(u32::MAX)),
+        )
+    }
+
+    fn since_unix_epoch() -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
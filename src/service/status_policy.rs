@@ -0,0 +1,139 @@
+//! Classification of peers based on their `Status` handshake.
+//!
+//! When a [`StatusPolicy`] is registered with [`Network`](super::Network), every `Status` received
+//! from a peer is compared against the local `Status`. Irrelevant peers are sent a goodbye with
+//! [`GoodbyeReason::IrrelevantNetwork`](crate::rpc::GoodbyeReason::IrrelevantNetwork) and their
+//! `Status` is not propagated to the application.
+
+use std::sync::Arc;
+
+use types::phase0::primitives::Slot;
+
+use crate::peer_manager::{SyncInfo, SyncStatus};
+use crate::rpc::StatusMessage;
+use crate::types::ForkContext;
+
+/// The number of slots a peer's head may be ahead of ours while still being considered synced.
+const SLOT_IMPORT_TOLERANCE: Slot = 32;
+
+/// The outcome of a `Status` handshake.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StatusClassification {
+    /// The peer is on our chain. Its sync status is left unchanged.
+    Relevant,
+    /// The peer is on a different network or chain and should be disconnected.
+    Irrelevant,
+    /// The peer is on our chain with the given sync status.
+    Sync(SyncStatus),
+}
+
+/// Decides whether a peer is relevant based on its `Status`.
+pub trait StatusPolicy: Send + Sync {
+    /// Classifies the peer that sent `remote` given our own `local` status.
+    fn classify(&self, local: StatusMessage, remote: StatusMessage) -> StatusClassification;
+}
+
+/// Rejects peers on a different fork or with a conflicting finalized checkpoint and classifies
+/// the rest by comparing finalized epochs and head slots.
+///
+/// Finalized checkpoints can only be compared when their epochs are equal, as checking that one
+/// is an ancestor of the other requires the chain. Peers that finalized a conflicting checkpoint
+/// at a different epoch are classified as `Advanced` or `Behind`, so applications that need to
+/// reject them must check the finalized root in their own [`StatusPolicy`].
+pub struct DefaultStatusPolicy {
+    fork_context: Arc<ForkContext>,
+}
+
+impl DefaultStatusPolicy {
+    pub fn new(fork_context: Arc<ForkContext>) -> Self {
+        Self { fork_context }
+    }
+}
+
+impl StatusPolicy for DefaultStatusPolicy {
+    fn classify(&self, local: StatusMessage, remote: StatusMessage) -> StatusClassification {
+        if remote.fork_digest() != self.fork_context.current_fork_digest() {
+            return StatusClassification::Irrelevant;
+        }
+
+        // Checkpoints at different epochs are left to the application, which has the chain.
+        if remote.finalized_epoch() == local.finalized_epoch()
+            && remote.finalized_root() != local.finalized_root()
+        {
+            return StatusClassification::Irrelevant;
+        }
+
+        let info = SyncInfo::from(remote);
+
+        let sync_status = if remote.finalized_epoch() > local.finalized_epoch() {
+            SyncStatus::Advanced { info }
+        } else if remote.finalized_epoch() < local.finalized_epoch() {
+            SyncStatus::Behind { info }
+        } else if remote.head_slot() > local.head_slot() + SLOT_IMPORT_TOLERANCE {
+            SyncStatus::Advanced { info }
+        } else {
+            SyncStatus::Synced { info }
+        };
+
+        StatusClassification::Sync(sync_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::StatusMessageV1;
+    use types::{
+        config::Config as ChainConfig, nonstandard::Phase, phase0::primitives::H256,
+        preset::Mainnet,
+    };
+
+    fn status(fork_context: &ForkContext, finalized_epoch: u64, head_slot: Slot) -> StatusMessage {
+        StatusMessage::V1(StatusMessageV1 {
+            fork_digest: fork_context.current_fork_digest(),
+            finalized_root: H256::repeat_byte(finalized_epoch as u8),
+            finalized_epoch,
+            head_root: H256::zero(),
+            head_slot,
+        })
+    }
+
+    #[test]
+    fn classifies_peers_by_fork_and_finalized_checkpoint() {
+        let config = Arc::new(ChainConfig::mainnet());
+        let fork_context = Arc::new(ForkContext::dummy::<Mainnet>(&config, Phase::Phase0));
+        let policy = DefaultStatusPolicy::new(fork_context.clone());
+        let local = status(&fork_context, 10, 400);
+
+        let mut other_fork = status(&fork_context, 10, 400);
+        if let StatusMessage::V1(ref mut message) = other_fork {
+            message.fork_digest = [0xff; 4].into();
+        }
+
+        let mut other_chain = status(&fork_context, 10, 400);
+        if let StatusMessage::V1(ref mut message) = other_chain {
+            message.finalized_root = H256::repeat_byte(0xff);
+        }
+
+        assert_eq!(
+            policy.classify(local, other_fork),
+            StatusClassification::Irrelevant,
+        );
+        assert_eq!(
+            policy.classify(local, other_chain),
+            StatusClassification::Irrelevant,
+        );
+        assert!(matches!(
+            policy.classify(local, status(&fork_context, 12, 500)),
+            StatusClassification::Sync(SyncStatus::Advanced { .. }),
+        ));
+        assert!(matches!(
+            policy.classify(local, status(&fork_context, 8, 300)),
+            StatusClassification::Sync(SyncStatus::Behind { .. }),
+        ));
+        assert!(matches!(
+            policy.classify(local, status(&fork_context, 10, 420)),
+            StatusClassification::Sync(SyncStatus::Synced { .. }),
+        ));
+    }
+}
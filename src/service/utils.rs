@@ -8,9 +8,11 @@ use crate::{GossipTopic, NetworkConfig};
 use anyhow::{Result, anyhow};
 use futures::future::Either;
 use libp2p::core::{multiaddr::Multiaddr, muxing::StreamMuxerBox, transport::Boxed};
+use libp2p::gossipsub::TopicHash;
 use libp2p::identity::{Keypair, secp256k1};
 use libp2p::metrics::Registry;
 use libp2p::{PeerId, Transport, core, gossipsub, noise, yamux};
+use parking_lot::RwLock;
 use ssz::SszReadDefault;
 use std::collections::HashSet;
 use std::fs::File;
@@ -215,10 +217,12 @@ pub fn strip_peer_id(addr: &mut Multiaddr) {
     }
 }
 
-/// Load metadata from persisted file. Return default metadata if loading fails.
+/// Load metadata from persisted file. Return metadata with the given bitfields if loading fails.
 pub fn load_or_build_metadata(
     network_dir: Option<&Path>,
     custody_group_count_opt: Option<u64>,
+    attnets: EnrAttestationBitfield,
+    syncnets: EnrSyncCommitteeBitfield,
 ) -> MetaData {
     // We load a V3 metadata version by default
     // since a V3 metadata can be converted to V2 and V1. The RPC encoder is responsible
@@ -226,15 +230,15 @@ pub fn load_or_build_metadata(
     let mut meta_data = if let Some(custody_group_count) = custody_group_count_opt {
         MetaData::V3(MetaDataV3 {
             seq_number: 0,
-            attnets: EnrAttestationBitfield::default(),
-            syncnets: EnrSyncCommitteeBitfield::default(),
+            attnets,
+            syncnets,
             custody_group_count,
         })
     } else {
         MetaData::V2(MetaDataV2 {
             seq_number: 0,
-            attnets: EnrAttestationBitfield::default(),
-            syncnets: EnrSyncCommitteeBitfield::default(),
+            attnets,
+            syncnets,
         })
     };
 
@@ -312,9 +316,36 @@ pub(crate) fn create_whitelist_filter(
     chain_config: &ChainConfig,
     attestation_subnet_count: u64,
     sync_committee_subnet_count: u64,
-) -> gossipsub::WhitelistSubscriptionFilter {
-    let mut possible_hashes = HashSet::new();
+) -> SharedWhitelistSubscriptionFilter {
+    let filter = SharedWhitelistSubscriptionFilter::default();
     for fork_digest in possible_fork_digests {
+        filter.add_fork_digest(
+            fork_digest,
+            chain_config,
+            attestation_subnet_count,
+            sync_committee_subnet_count,
+        );
+    }
+    filter
+}
+
+/// A whitelist of gossipsub topics that can be extended after `Gossipsub` has been built.
+///
+/// Clones share the whitelist, so topics added through a clone held outside of `Gossipsub` are
+/// subscribable right away.
+#[derive(Clone, Default)]
+pub struct SharedWhitelistSubscriptionFilter(Arc<RwLock<HashSet<TopicHash>>>);
+
+impl SharedWhitelistSubscriptionFilter {
+    /// Whitelists all possible topics with the given `fork_digest`.
+    pub(crate) fn add_fork_digest(
+        &self,
+        fork_digest: ForkDigest,
+        chain_config: &ChainConfig,
+        attestation_subnet_count: u64,
+        sync_committee_subnet_count: u64,
+    ) {
+        let mut possible_hashes = self.0.write();
         let mut add = |kind| {
             let topic: gossipsub::IdentTopic =
                 GossipTopic::new(kind, GossipEncoding::SSZSnappy, fork_digest).into();
@@ -349,7 +380,12 @@ pub(crate) fn create_whitelist_filter(
             add(DataColumnSidecar(id));
         }
     }
-    gossipsub::WhitelistSubscriptionFilter(possible_hashes)
+}
+
+impl gossipsub::TopicSubscriptionFilter for SharedWhitelistSubscriptionFilter {
+    fn can_subscribe(&mut self, topic_hash: &TopicHash) -> bool {
+        self.0.read().contains(topic_hash)
+    }
 }
 
 /// Persist metadata to disk
@@ -381,3 +417,30 @@ pub(crate) fn save_metadata_to_disk(dir: Option<&Path>, metadata: MetaData) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gossipsub::TopicSubscriptionFilter as _;
+
+    #[test]
+    fn added_fork_digests_are_subscribable_through_clones() {
+        let chain_config = ChainConfig::mainnet();
+        let fork_digest = ForkDigest::from_slice(&[1, 2, 3, 4]);
+        let filter = SharedWhitelistSubscriptionFilter::default();
+        let mut gossipsub_filter = filter.clone();
+
+        let topic: gossipsub::IdentTopic = GossipTopic::new(
+            GossipKind::BeaconBlock,
+            GossipEncoding::SSZSnappy,
+            fork_digest,
+        )
+        .into();
+
+        assert!(!gossipsub_filter.can_subscribe(&topic.hash()));
+
+        filter.add_fork_digest(fork_digest, &chain_config, 64, 4);
+
+        assert!(gossipsub_filter.can_subscribe(&topic.hash()));
+    }
+}
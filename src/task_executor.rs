@@ -6,6 +6,8 @@ use tracing::trace;
 /// Provides a reason when client is shut down.
 #[derive(Copy, Clone, Debug)]
 pub enum ShutdownReason {
+    /// The node shut down after completing a requested task.
+    Success(&'static str),
     /// The node shut down due to an error condition.
     Failure(&'static str),
 }
@@ -13,6 +15,7 @@ pub enum ShutdownReason {
 impl ShutdownReason {
     pub fn message(&self) -> &'static str {
         match self {
+            ShutdownReason::Success(msg) => msg,
             ShutdownReason::Failure(msg) => msg,
         }
     }
@@ -73,6 +76,22 @@ impl TaskExecutor {
         self.spawn_monitor(self.spawn_handle(task, name), name)
     }
 
+    /// Spawn a blocking task on a dedicated tokio thread pool.
+    ///
+    /// The task is monitored via another spawned future to ensure that it doesn't panic. In case
+    /// of a panic, the executor will be shut down via `self.signal_tx`.
+    pub fn spawn_blocking<F>(&self, task: F, name: &'static str)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let join_handle = tokio::task::spawn_blocking(move || {
+            task();
+            trace!(task = name, "Blocking task completed");
+        });
+
+        self.spawn_monitor(join_handle, name)
+    }
+
     /// Spawn a future on the tokio runtime returning a join handle to the future.
     fn spawn_handle<R: Send + 'static>(
         &self,
@@ -85,7 +104,7 @@ impl TaskExecutor {
     }
 
     /// Get a channel to request shutting down.
-    fn shutdown_sender(&self) -> Sender<ShutdownReason> {
+    pub fn shutdown_sender(&self) -> Sender<ShutdownReason> {
         self.signal_tx.clone()
     }
 }
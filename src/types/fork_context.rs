@@ -43,7 +43,7 @@ impl HardFork {
 pub struct ForkContext {
     chain_config: Arc<Config>,
     current_fork: RwLock<HardFork>,
-    epoch_to_forks: BTreeMap<Epoch, HardFork>,
+    epoch_to_forks: RwLock<BTreeMap<Epoch, HardFork>>,
 }
 
 impl ForkContext {
@@ -76,7 +76,7 @@ impl ForkContext {
         Self {
             chain_config: config.clone(),
             current_fork: RwLock::new(current_fork),
-            epoch_to_forks,
+            epoch_to_forks: RwLock::new(epoch_to_forks),
         }
     }
 
@@ -113,6 +113,7 @@ impl ForkContext {
     pub fn next_fork_digest(&self) -> Option<ForkDigest> {
         let current_fork_epoch = self.current_fork_epoch();
         self.epoch_to_forks
+            .read()
             .range(current_fork_epoch..)
             .nth(1)
             .map(|(_, fork)| fork.fork_digest)
@@ -122,6 +123,7 @@ impl ForkContext {
     pub fn next_fork(&self) -> Option<(Phase, ForkDigest, Epoch)> {
         let current_fork_epoch = self.current_fork_epoch();
         self.epoch_to_forks
+            .read()
             .range(current_fork_epoch..)
             .nth(1)
             .map(|(_, fork)| (fork.fork_name, fork.fork_digest, fork.fork_epoch))
@@ -132,6 +134,7 @@ impl ForkContext {
         let current_fork_epoch = self.current_fork_epoch();
         let next_fork = self
             .epoch_to_forks
+            .read()
             .range(current_fork_epoch..)
             .nth(1)
             .map(|(_, fork)| fork.clone())
@@ -147,6 +150,7 @@ impl ForkContext {
     /// Returns the context bytes/fork_digest corresponding to the genesis fork version.
     pub fn genesis_context_bytes(&self) -> ForkDigest {
         self.epoch_to_forks
+            .read()
             .first_key_value()
             .expect("must contain genesis epoch")
             .1
@@ -157,6 +161,7 @@ impl ForkContext {
     /// Returns `None` if context bytes doesn't correspond to any valid `Phase`.
     pub fn get_fork_from_context_bytes(&self, context: ForkDigest) -> Option<Phase> {
         self.epoch_to_forks
+            .read()
             .values()
             .find(|fork| fork.fork_digest == context)
             .map(|fork| fork.fork_name)
@@ -165,6 +170,7 @@ impl ForkContext {
     /// Returns the context bytes/fork_digest corresponding to an epoch.
     pub fn context_bytes(&self, epoch: Epoch) -> ForkDigest {
         self.epoch_to_forks
+            .read()
             .range(..=epoch)
             .next_back()
             .expect("should match at least genesis epoch")
@@ -182,6 +188,7 @@ impl ForkContext {
     /// Returns all `fork_digest`s that are currently in the `ForkContext` object.
     pub fn all_fork_digests(&self) -> Vec<ForkDigest> {
         self.epoch_to_forks
+            .read()
             .values()
             .map(|fork| fork.fork_digest)
             .collect()
@@ -189,7 +196,28 @@ impl ForkContext {
 
     /// Returns all `fork_epoch`s that are currently in the `ForkContext` object.
     pub fn all_fork_epochs(&self) -> Vec<Epoch> {
-        self.epoch_to_forks.keys().cloned().collect()
+        self.epoch_to_forks.read().keys().cloned().collect()
+    }
+
+    /// Adds a fork scheduled after the `ForkContext` was created, such as a blob-parameter-only
+    /// fork. A fork already scheduled at `fork_epoch` is replaced. Returns `false` if the fork is
+    /// not after the current fork.
+    pub fn add_scheduled_fork(
+        &self,
+        fork_name: Phase,
+        fork_digest: ForkDigest,
+        fork_epoch: Epoch,
+    ) -> bool {
+        if fork_epoch <= self.current_fork_epoch() || fork_epoch == FAR_FUTURE_EPOCH {
+            return false;
+        }
+
+        self.epoch_to_forks.write().insert(
+            fork_epoch,
+            HardFork::new(fork_name, fork_digest, fork_epoch),
+        );
+
+        true
     }
 
     pub fn chain_config(&self) -> &Arc<Config> {
@@ -295,4 +323,28 @@ mod tests {
             misc::compute_fork_digest(&chain_config, genesis_root, 10)
         );
     }
+
+    #[test]
+    fn test_add_scheduled_fork() {
+        let chain_config = make_chain_config();
+        let context = ForkContext::dummy::<Mainnet>(&chain_config, Phase::Fulu);
+        let digest = ForkDigest::from_slice(&[1, 2, 3, 4]);
+
+        assert!(!context.add_scheduled_fork(Phase::Fulu, digest, context.current_fork_epoch()));
+        assert!(!context.add_scheduled_fork(Phase::Fulu, digest, 50));
+        assert!(context.add_scheduled_fork(Phase::Fulu, digest, 200));
+
+        assert!(context.all_fork_digests().contains(&digest));
+        assert_eq!(context.context_bytes(250), digest);
+        assert_eq!(
+            context.get_fork_from_context_bytes(digest),
+            Some(Phase::Fulu)
+        );
+
+        let replacement = ForkDigest::from_slice(&[5, 6, 7, 8]);
+
+        assert!(context.add_scheduled_fork(Phase::Fulu, replacement, 200));
+        assert!(!context.all_fork_digests().contains(&digest));
+        assert_eq!(context.context_bytes(250), replacement);
+    }
 }
@@ -10,6 +10,7 @@ use parking_lot::RwLock;
 use std::collections::HashSet;
 use std::sync::Arc;
 use std_ext::ArcExt as _;
+use tokio::sync::watch;
 use tracing::{debug, error};
 use types::config::Config as ChainConfig;
 use types::fulu::primitives::ColumnIndex;
@@ -33,6 +34,8 @@ pub struct NetworkGlobals {
     pub gossipsub_subscriptions: RwLock<HashSet<GossipTopic>>,
     /// The current sync status of the node.
     pub sync_state: RwLock<SyncState>,
+    /// Broadcasts sync state transitions made through `set_sync_state`.
+    sync_state_tx: watch::Sender<SyncState>,
     /// The current state of the backfill sync.
     pub backfill_state: RwLock<BackFillState>,
     /// The computed sampling subnets and columns is stored to avoid re-computing.
@@ -96,6 +99,7 @@ impl NetworkGlobals {
             peers: RwLock::new(PeerDB::new(config, trusted_peers, disable_peer_scoring)),
             gossipsub_subscriptions: RwLock::new(HashSet::new()),
             sync_state: RwLock::new(SyncState::Stalled),
+            sync_state_tx: watch::Sender::new(SyncState::Stalled),
             backfill_state: RwLock::new(BackFillState::Paused),
             sampling_subnets: RwLock::new(sampling_subnets),
             target_subnet_peers,
@@ -198,7 +202,14 @@ impl NetworkGlobals {
     ///
     /// The old state is returned
     pub fn set_sync_state(&self, new_state: SyncState) -> SyncState {
-        std::mem::replace(&mut *self.sync_state.write(), new_state)
+        let old_state = std::mem::replace(&mut *self.sync_state.write(), new_state.clone());
+        self.sync_state_tx.send_replace(new_state);
+        old_state
+    }
+
+    /// Returns a receiver that is notified of every sync state update.
+    pub fn watch_sync_state(&self) -> watch::Receiver<SyncState> {
+        self.sync_state_tx.subscribe()
     }
 
     /// Returns a connected peer that:
@@ -234,7 +245,8 @@ impl NetworkGlobals {
     pub fn as_topic_config(&self) -> TopicConfig {
         TopicConfig {
             enable_light_client_server: self.network_config.enable_light_client_server,
-            subscribe_all_subnets: self.network_config.subscribe_all_subnets,
+            proposer_only: self.network_config.proposer_only,
+            subscribe_all_subnets: self.network_config.subscribes_to_all_subnets(),
             subscribe_all_data_column_subnets: self
                 .network_config
                 .subscribe_all_data_column_subnets,
@@ -320,6 +332,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_set_sync_state_notifies_watchers() {
+        let globals = NetworkGlobals::new_test_globals::<Mainnet>(
+            Arc::new(ChainConfig::mainnet()),
+            vec![],
+            Arc::new(NetworkConfig::default()),
+        );
+        let mut receiver = globals.watch_sync_state();
+
+        assert!(!receiver.has_changed().expect("sender is alive"));
+
+        let old_state = globals.set_sync_state(SyncState::Synced);
+
+        assert_eq!(old_state, SyncState::Stalled);
+        assert!(receiver.has_changed().expect("sender is alive"));
+        assert_eq!(*receiver.borrow_and_update(), SyncState::Synced);
+    }
+
     fn get_metadata(custody_group_count: u64) -> MetaData {
         MetaData::V3(MetaDataV3 {
             seq_number: 0,
@@ -39,6 +39,7 @@ pub const EXECUTION_PAYLOAD_BID_TOPIC: &str = "execution_payload_bid";
 #[derive(Debug)]
 pub struct TopicConfig {
     pub enable_light_client_server: bool,
+    pub proposer_only: bool,
     pub subscribe_all_subnets: bool,
     pub subscribe_all_data_column_subnets: bool,
     pub sampling_subnets: HashSet<SubnetId>,
@@ -58,7 +59,10 @@ pub fn core_topics_to_subscribe(
         GossipKind::AttesterSlashing,
     ];
 
-    if opts.subscribe_all_subnets {
+    // Proposer-only nodes do not take part in attestation or sync committee subnets.
+    let subscribe_all_subnets = opts.subscribe_all_subnets && !opts.proposer_only;
+
+    if subscribe_all_subnets {
         for i in 0..AttestationSubnetCount::U64 {
             topics.push(GossipKind::Attestation(i.into()));
         }
@@ -67,13 +71,13 @@ pub fn core_topics_to_subscribe(
     if current_phase >= Phase::Altair {
         topics.push(GossipKind::SignedContributionAndProof);
 
-        if opts.subscribe_all_subnets {
+        if subscribe_all_subnets {
             for i in 0..SyncCommitteeSubnetCount::U64 {
                 topics.push(GossipKind::SyncCommitteeMessage(i.into()));
             }
         }
 
-        if opts.enable_light_client_server {
+        if opts.enable_light_client_server && !opts.proposer_only {
             topics.push(GossipKind::LightClientFinalityUpdate);
             topics.push(GossipKind::LightClientOptimisticUpdate);
         }
@@ -140,6 +144,7 @@ pub fn all_topics_at_fork(chain_config: &ChainConfig, current_phase: Phase) -> V
     let sampling_subnets = HashSet::from_iter(0..chain_config.data_column_sidecar_subnet_count);
     let opts = TopicConfig {
         enable_light_client_server: true,
+        proposer_only: false,
         subscribe_all_subnets: true,
         subscribe_all_data_column_subnets: true,
         sampling_subnets,
@@ -532,6 +537,7 @@ mod tests {
     fn get_topic_config(sampling_subnets: HashSet<SubnetId>) -> TopicConfig {
         TopicConfig {
             enable_light_client_server: false,
+            proposer_only: false,
             subscribe_all_subnets: false,
             subscribe_all_data_column_subnets: false,
             sampling_subnets,
@@ -606,4 +612,26 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn proposer_only_skips_subnet_and_light_client_topics() {
+        let config = get_chain_config();
+        let s = get_sampling_subnets();
+        let mut topic_config = get_topic_config(s);
+        topic_config.enable_light_client_server = true;
+        topic_config.subscribe_all_subnets = true;
+        topic_config.proposer_only = true;
+        let topics = core_topics_to_subscribe(&config, Phase::Deneb, &topic_config);
+
+        assert!(topics.contains(&GossipKind::BeaconBlock));
+        assert!(topics.contains(&GossipKind::BlobSidecar(0)));
+        assert!(topics.contains(&GossipKind::SignedContributionAndProof));
+        assert!(!topics.iter().any(|kind| matches!(
+            kind,
+            GossipKind::Attestation(_)
+                | GossipKind::SyncCommitteeMessage(_)
+                | GossipKind::LightClientFinalityUpdate
+                | GossipKind::LightClientOptimisticUpdate
+        )));
+    }
 }